@@ -26,8 +26,6 @@ use async_std::prelude::*;
 use async_std::sync::{Arc, RwLock};
 use async_std::task;
 
-use futures::stream::TryStreamExt;
-
 use zenoh::*;
 
 use fog05_sdk::fresult::{FError, FResult};
@@ -45,11 +43,9 @@ use structopt::StructOpt;
 
 use git_version::git_version;
 
-use fog05_networking_linux::types::NamespaceManager;
-
-use netlink_packet_route::rtnl::address::nlas::Nla;
-use rtnetlink::new_connection;
-use rtnetlink::packet::rtnl::link::nlas::Nla as LinkNla;
+use fog05_networking_linux::netlink::{NetlinkBackend, NetlinkEvent, RtNetlinkBackend};
+use fog05_networking_linux::services::{NSManagerOps, NetlinkNSManager};
+use fog05_networking_linux::types::{NamespaceManager, NS_MANAGER_READY};
 
 use ipnetwork::IpNetwork;
 
@@ -76,15 +72,18 @@ struct NSManagerArgs {
 
 pub struct NSManagerState {
     pub tokio_rt: tokio::runtime::Runtime,
-    pub nl_handler: rtnetlink::Handle,
 }
 
 #[derive(Clone)]
 pub struct NSManager {
     pub z: Arc<zenoh::net::Session>,
+    pub connector: Arc<ZConnector>,
     pub pid: u32,
     pub uuid: Uuid,
     pub state: Arc<RwLock<NSManagerState>>,
+    pub nl: Arc<dyn NetlinkBackend>,
+    /// Operations served to the plugin
+    pub ops: NetlinkNSManager,
 }
 
 fn main() {
@@ -181,9 +180,12 @@ fn main() {
 
                 let properties = format!("mode=client;peer={}", args.locator.clone());
                 let zproperties = Properties::from(properties);
+                let z = Arc::new(Zenoh::new(zproperties.clone().into()).await.unwrap());
                 let zenoh = Arc::new(zenoh::net::open(zproperties.into()).await.unwrap());
+                let zconnector = Arc::new(ZConnector::new(z, None, None));
 
-                let mut manager = match NSManager::new(zenoh, my_pid, args.id, rt).await {
+                let mut manager = match NSManager::new(zenoh, zconnector, my_pid, args.id, rt).await
+                {
                     Ok(m) => m,
                     Err(e) => {
                         log::error!("Error when creating manager: {}", e);
@@ -246,30 +248,24 @@ fn main() {
 impl NSManager {
     pub async fn new(
         z: Arc<zenoh::net::Session>,
+        connector: Arc<ZConnector>,
         pid: u32,
         uuid: Uuid,
         rt: tokio::runtime::Runtime,
     ) -> FResult<Self> {
-        // This will disappear once netlink merges async-std support
-        let handle = rt
-            .spawn_blocking(|| {
-                let (connection, handle, _) = new_connection().unwrap();
-                tokio::spawn(connection);
-                handle
-            })
-            .await
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+        let nl: Arc<dyn NetlinkBackend> = Arc::new(RtNetlinkBackend::new(&rt).await?);
+        let ops = NetlinkNSManager::new(nl.clone());
 
-        let state = NSManagerState {
-            tokio_rt: rt,
-            nl_handler: handle,
-        };
+        let state = NSManagerState { tokio_rt: rt };
 
         Ok(Self {
             z,
+            connector,
             pid,
             uuid,
             state: Arc::new(RwLock::new(state)),
+            nl,
+            ops,
         })
     }
 
@@ -285,8 +281,22 @@ impl NSManager {
 
         let (sender, handle) = ns_manager_server.start().await?;
 
-        log::trace!("Interfaces in namespace {:?}", self.dump_links().await);
+        // the changes of the namespace are queued from now on,
+        // the plugin polls them with next_netlink_events
+        self.ops.subscribe().await?;
 
+        // Telling the plugin that we are ready to serve
+        {
+            use std::io::Write;
+            let mut stdout = std::io::stdout();
+            writeln!(stdout, "{}", NS_MANAGER_READY)?;
+            stdout.flush()?;
+        }
+
+        log::trace!("Interfaces in namespace {:?}", self.nl.dump_links().await);
+
+        // the plugin is the only writer of the store,
+        // it updates the records from the events passed by this manager
         stop.recv().await;
 
         ns_manager_server.stop(sender).await?;
@@ -317,638 +327,37 @@ impl NSManager {
         log::info!("Stopped");
         Ok(())
     }
-
-    async fn create_bridge(&self, br_name: String) -> FResult<()> {
-        log::trace!("create_bridge {}", br_name);
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                state
-                    .nl_handler
-                    .link()
-                    .add()
-                    .bridge(br_name)
-                    .execute()
-                    .await
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
-    }
-
-    async fn create_veth(&self, iface_i: String, iface_e: String) -> FResult<()> {
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                state
-                    .nl_handler
-                    .link()
-                    .add()
-                    .veth(iface_i, iface_e)
-                    .execute()
-                    .await
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
-    }
-
-    async fn create_vlan(&self, iface: String, dev: String, tag: u16) -> FResult<()> {
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                let mut links = state.nl_handler.link().get().set_name_filter(dev).execute();
-                if let Some(link) = links
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    state
-                        .nl_handler
-                        .link()
-                        .add()
-                        .vlan(iface, link.header.index, tag)
-                        .execute()
-                        .await
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
-                } else {
-                    Err(FError::NotFound)
-                }
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
-    }
-
-    async fn create_mcast_vxlan(
-        &self,
-        iface: String,
-        dev: String,
-        vni: u32,
-        mcast_addr: IPAddress,
-        port: u16,
-    ) -> FResult<()> {
-        log::trace!(
-            "create_mcast_vxlan {} {} {} {} {}",
-            iface,
-            dev,
-            vni,
-            mcast_addr,
-            port
-        );
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                let mut links = state.nl_handler.link().get().set_name_filter(dev).execute();
-                if let Some(link) = links
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    let vxlan = state
-                        .nl_handler
-                        .link()
-                        .add()
-                        .vxlan(iface, vni)
-                        .link(link.header.index);
-
-                    let vxlan = match mcast_addr {
-                        IPAddress::V4(v4) => vxlan.group(v4),
-                        IPAddress::V6(v6) => vxlan.group6(v6),
-                    };
-
-                    vxlan
-                        .port(port)
-                        .execute()
-                        .await
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
-                } else {
-                    Err(FError::NotFound)
-                }
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
-    }
-
-    async fn create_ptp_vxlan(
-        &self,
-        iface: String,
-        dev: String,
-        vni: u32,
-        local_addr: IPAddress,
-        remote_addr: IPAddress,
-        port: u16,
-    ) -> FResult<()> {
-        log::trace!(
-            "create_ptp_vxlan {} {} {} {} {} {}",
-            iface,
-            dev,
-            vni,
-            local_addr,
-            remote_addr,
-            port
-        );
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                let mut links = state.nl_handler.link().get().set_name_filter(dev).execute();
-                if let Some(link) = links
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    let vxlan = state
-                        .nl_handler
-                        .link()
-                        .add()
-                        .vxlan(iface, vni)
-                        .link(link.header.index);
-
-                    let vxlan = match local_addr {
-                        IPAddress::V4(v4) => vxlan.local(v4),
-                        IPAddress::V6(v6) => vxlan.local6(v6),
-                    };
-
-                    let vxlan = match remote_addr {
-                        IPAddress::V4(v4) => vxlan.remote(v4),
-                        IPAddress::V6(v6) => vxlan.remote6(v6),
-                    };
-
-                    vxlan
-                        .port(port)
-                        .execute()
-                        .await
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
-                } else {
-                    Err(FError::NotFound)
-                }
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
-    }
-
-    async fn del_iface(&self, iface: String) -> FResult<()> {
-        log::trace!("del_iface {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .del(link.header.index)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn set_iface_master(&self, iface: String, master: String) -> FResult<()> {
-        log::trace!("set_iface_master {} {}", iface, master);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                let mut masters = state
-                    .nl_handler
-                    .link()
-                    .get()
-                    .set_name_filter(master)
-                    .execute();
-                if let Some(master) = masters
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    state
-                        .nl_handler
-                        .link()
-                        .set(link.header.index)
-                        .master(master.header.index)
-                        .execute()
-                        .await
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
-                } else {
-                    log::error!("set_iface_master master not found");
-                    Err(FError::NotFound)
-                }
-            } else {
-                log::error!("set_iface_master iface not found");
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn del_iface_master(&self, iface: String) -> FResult<()> {
-        log::trace!("del_iface_master {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .nomaster()
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                log::error!("del_iface_master iface not found");
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn add_iface_address(&self, iface: String, addr: IPAddress, prefix: u8) -> FResult<()> {
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .address()
-                    .add(link.header.index, addr, prefix)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn get_iface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>> {
-        log::trace!("get_iface_addresses {}", iface);
-        let mut state = self.state.write().await;
-        use netlink_packet_route::rtnl::address::nlas::Nla;
-        use netlink_packet_route::rtnl::address::AddressMessage;
-        state.tokio_rt.block_on(async {
-            let mut nl_addresses = Vec::new();
-            let mut f_addresses: Vec<IPAddress> = Vec::new();
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface.clone())
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                let mut addresses = state
-                    .nl_handler
-                    .address()
-                    .get()
-                    .set_link_index_filter(link.header.index)
-                    .execute();
-                while let Some(msg) = addresses
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    for nla in &msg.nlas {
-                        match nla {
-                            Nla::Address(nl_addr) => {
-                                nl_addresses.push((msg.header.clone(), nl_addr.clone()))
-                            }
-                            _ => continue,
-                        }
-                    }
-                }
-                for (_, x) in nl_addresses {
-                    if x.len() == 4 {
-                        let octects: [u8; 4] = [x[0], x[1], x[2], x[3]];
-                        f_addresses.push(IPAddress::from(octects))
-                    }
-                    if x.len() == 16 {
-                        let octects: [u8; 16] = [
-                            x[0], x[1], x[2], x[3], x[4], x[5], x[6], x[7], x[8], x[9], x[10],
-                            x[11], x[12], x[13], x[14], x[15],
-                        ];
-                        f_addresses.push(IPAddress::from(octects))
-                    }
-                }
-                Ok(f_addresses)
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn del_iface_address(&self, iface: String, addr: IPAddress) -> FResult<()> {
-        let mut state = self.state.write().await;
-        use netlink_packet_route::rtnl::address::nlas::Nla;
-        use netlink_packet_route::rtnl::address::AddressMessage;
-        state.tokio_rt.block_on(async {
-            let octets = match addr {
-                IPAddress::V4(a) => a.octets().to_vec(),
-                IPAddress::V6(a) => a.octets().to_vec(),
-            };
-            let mut nl_addresses = Vec::new();
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface.clone())
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                let mut addresses = state
-                    .nl_handler
-                    .address()
-                    .get()
-                    .set_link_index_filter(link.header.index)
-                    .execute();
-                while let Some(msg) = addresses
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    for nla in &msg.nlas {
-                        match nla {
-                            Nla::Address(nl_addr) => {
-                                nl_addresses.push((msg.header.clone(), nl_addr.clone()))
-                            }
-                            _ => continue,
-                        }
-                    }
-                }
-                match nl_addresses.into_iter().find(|(_, x)| *x == octets) {
-                    Some((hdr, addr)) => {
-                        let msg = AddressMessage {
-                            header: hdr,
-                            nlas: vec![Nla::Address(addr)],
-                        };
-                        state
-                            .nl_handler
-                            .address()
-                            .del(msg)
-                            .execute()
-                            .await
-                            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-                        Ok(())
-                    }
-                    None => Err(FError::NotFound),
-                }
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn set_iface_name(&self, iface: String, new_name: String) -> FResult<()> {
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .name(new_name)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn set_iface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()> {
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .address(address)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn set_iface_default_ns(&self, iface: String) -> FResult<()> {
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .setns_by_pid(1)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn set_iface_up(&self, iface: String) -> FResult<()> {
-        log::trace!("set_iface_up {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .up()
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn set_iface_down(&self, iface: String) -> FResult<()> {
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .down()
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
-
-    async fn iface_exists(&self, iface: String) -> FResult<bool> {
-        log::trace!("iface_exists {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        })
-    }
-
-    async fn dump_links(&self) -> FResult<Vec<String>> {
-        log::trace!("dump_links");
-        let mut ifaces = Vec::new();
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state.nl_handler.link().get().execute();
-            while let Some(msg) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                for nla in msg.nlas.into_iter() {
-                    if let LinkNla::IfName(name) = nla {
-                        ifaces.push(name);
-                        break;
-                    }
-                }
-            }
-            Ok(ifaces)
-        })
-    }
 }
 
 #[znserver]
 impl NamespaceManager for NSManager {
     async fn set_virtual_interface_up(&self, iface: String) -> FResult<()> {
-        self.set_iface_up(iface).await
+        self.ops.set_virtual_interface_up(iface).await
     }
     async fn set_virtual_interface_down(&self, iface: String) -> FResult<()> {
-        self.set_iface_down(iface).await
+        self.ops.set_virtual_interface_down(iface).await
     }
     async fn check_virtual_interface_exists(&self, iface: String) -> FResult<bool> {
-        self.iface_exists(iface).await
+        self.ops.check_virtual_interface_exists(iface).await
     }
     async fn move_virtual_interface_into_default_ns(&self, iface: String) -> FResult<()> {
-        self.set_iface_default_ns(iface).await
+        self.ops.move_virtual_interface_into_default_ns(iface).await
     }
     async fn set_virtual_interface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()> {
-        self.set_iface_mac(iface, address).await
+        self.ops.set_virtual_interface_mac(iface, address).await
     }
     async fn set_virtual_interface_name(&self, iface: String, name: String) -> FResult<()> {
-        self.set_iface_name(iface, name).await
+        self.ops.set_virtual_interface_name(iface, name).await
+    }
+    async fn set_virtual_interface_alias(&self, iface: String, alias: String) -> FResult<()> {
+        self.ops.set_virtual_interface_alias(iface, alias).await
     }
     async fn del_virtual_interface_address(&self, iface: String, addr: IPAddress) -> FResult<()> {
-        self.del_iface_address(iface, addr).await
+        self.ops.del_virtual_interface_address(iface, addr).await
     }
 
     async fn get_virtual_interface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>> {
-        self.get_iface_addresses(iface).await
+        self.ops.get_virtual_interface_addresses(iface).await
     }
 
     async fn add_virtual_interface_address(
@@ -956,39 +365,16 @@ impl NamespaceManager for NSManager {
         iface: String,
         addr: Option<IpNetwork>,
     ) -> FResult<Vec<IPAddress>> {
-        log::trace!("add_virtual_interface_address {} {:?}", iface, addr);
-        match addr {
-            Some(addr) => {
-                self.add_iface_address(iface.clone(), addr.ip(), addr.prefix())
-                    .await?;
-                self.get_iface_addresses(iface).await
-            }
-            None => {
-                log::trace!("Using DHCP");
-                // If the address is None we spawn a DHCP client
-                // and then we the the address from netlink
-                let mut child = Command::new("dhclient")
-                    .arg("-i")
-                    .arg(iface.clone())
-                    .spawn()
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-                log::trace!("DHCP Client running {}", child.id());
-                let res = child
-                    .wait()
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-                log::trace!("DHCP Client exited with {:?}", res);
-                self.get_iface_addresses(iface).await
-            }
-        }
+        self.ops.add_virtual_interface_address(iface, addr).await
     }
     async fn set_virtual_interface_master(&self, iface: String, master: String) -> FResult<()> {
-        self.set_iface_master(iface, master).await
+        self.ops.set_virtual_interface_master(iface, master).await
     }
     async fn set_virtual_interface_nomaster(&self, iface: String) -> FResult<()> {
-        self.del_iface_master(iface).await
+        self.ops.set_virtual_interface_nomaster(iface).await
     }
     async fn del_virtual_interface(&self, iface: String) -> FResult<()> {
-        self.del_iface(iface).await
+        self.ops.del_virtual_interface(iface).await
     }
     async fn add_virtual_interface_ptp_vxlan(
         &self,
@@ -999,7 +385,8 @@ impl NamespaceManager for NSManager {
         remote_addr: IPAddress,
         port: u16,
     ) -> FResult<()> {
-        self.create_ptp_vxlan(iface, dev, vni, local_addr, remote_addr, port)
+        self.ops
+            .add_virtual_interface_ptp_vxlan(iface, dev, vni, local_addr, remote_addr, port)
             .await
     }
     async fn add_virtual_interface_mcast_vxlan(
@@ -1010,9 +397,9 @@ impl NamespaceManager for NSManager {
         mcast_addr: IPAddress,
         port: u16,
     ) -> FResult<()> {
-        self.create_mcast_vxlan(iface.clone(), dev, vni, mcast_addr, port)
-            .await?;
-        self.set_iface_up(iface).await
+        self.ops
+            .add_virtual_interface_mcast_vxlan(iface, dev, vni, mcast_addr, port)
+            .await
     }
     async fn add_virtual_interface_vlan(
         &self,
@@ -1020,20 +407,20 @@ impl NamespaceManager for NSManager {
         dev: String,
         tag: u16,
     ) -> FResult<()> {
-        self.create_vlan(iface.clone(), dev, tag).await?;
-        self.set_iface_up(iface).await
+        self.ops.add_virtual_interface_vlan(iface, dev, tag).await
     }
     async fn add_virtual_interface_veth(&self, iface_i: String, iface_e: String) -> FResult<()> {
-        self.create_veth(iface_i.clone(), iface_e.clone()).await?;
-        self.set_iface_up(iface_i).await?;
-        self.set_iface_up(iface_e).await
+        self.ops.add_virtual_interface_veth(iface_i, iface_e).await
     }
     async fn add_virtual_interface_bridge(&self, br_name: String) -> FResult<()> {
-        self.create_bridge(br_name.clone()).await?;
-        self.set_iface_up(br_name).await
+        self.ops.add_virtual_interface_bridge(br_name).await
     }
 
     async fn list_interfaces(&self) -> FResult<Vec<String>> {
-        self.dump_links().await
+        self.ops.list_interfaces().await
+    }
+
+    async fn next_netlink_events(&self) -> FResult<Vec<NetlinkEvent>> {
+        self.ops.next_netlink_events().await
     }
 }
@@ -0,0 +1,300 @@
+/*********************************************************************************
+* Copyright (c) 2018,2020 ADLINK Technology Inc.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0, or the Apache Software License 2.0
+* which is available at https://www.apache.org/licenses/LICENSE-2.0.
+*
+* SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+* Contributors:
+*   ADLINK fog05 team, <fog05@adlink-labs.tech>
+*********************************************************************************/
+
+//! Helpers for the dnsmasq files
+
+use fog05_sdk::fresult::{FError, FResult};
+use fog05_sdk::types::{IPAddress, MACAddress};
+
+use crate::types::{DHCPLease, DHCPOptions, DHCPReservation};
+
+/// Parses the content of a dnsmasq leases file.
+/// IPv4 leases are `<expiry> <mac> <ip> <hostname> <client-id>`,
+/// IPv6 leases follow the `duid <server duid>` line and have
+/// the IAID in place of the MAC and the client DUID as client-id.
+/// Unknown hostnames and client-ids are written as `*`.
+pub fn parse_leases(content: &str) -> FResult<Vec<DHCPLease>> {
+    let mut leases = Vec::new();
+    let mut v6 = false;
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields[0] == "duid" {
+            v6 = true;
+            continue;
+        }
+        if fields.len() < 4 {
+            return Err(FError::NetworkingError(format!(
+                "Malformed lease: {}",
+                line
+            )));
+        }
+        let optional = |field: Option<&&str>| match field {
+            Some(&"*") | None => None,
+            Some(value) => Some(value.to_string()),
+        };
+        leases.push(DHCPLease {
+            expiry: fields[0]
+                .parse()
+                .map_err(|e| FError::NetworkingError(format!("{}: {}", line, e)))?,
+            mac: if v6 { None } else { parse_mac(fields[1]) },
+            ip: fields[2]
+                .parse::<IPAddress>()
+                .map_err(|e| FError::NetworkingError(format!("{}: {}", line, e)))?,
+            hostname: optional(fields.get(3)),
+            client_id: optional(fields.get(4)),
+            interface: None,
+        });
+    }
+    Ok(leases)
+}
+
+/// Parses an Ethernet MAC address as written by dnsmasq,
+/// other hardware types are prefixed by their type and return None
+pub fn parse_mac(mac: &str) -> Option<MACAddress> {
+    let bytes = mac
+        .split(':')
+        .map(|b| u8::from_str_radix(b, 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .ok()?;
+    match bytes.as_slice() {
+        [a, b, c, d, e, f] => Some(MACAddress::new(*a, *b, *c, *d, *e, *f)),
+        _ => None,
+    }
+}
+
+/// Formats a MAC address as dnsmasq expects it
+pub fn format_mac(mac: &MACAddress) -> String {
+    format!(
+        "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        mac.0, mac.1, mac.2, mac.3, mac.4, mac.5
+    )
+}
+
+/// Checks that the hostname of a reservation can be written in a hosts file
+pub fn validate_reservation(reservation: &DHCPReservation) -> FResult<()> {
+    if let Some(hostname) = &reservation.hostname {
+        let valid = !hostname.is_empty()
+            && hostname.len() <= 63
+            && !hostname.starts_with('-')
+            && hostname
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(FError::NetworkingError(format!(
+                "Invalid hostname {:?}",
+                hostname
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Renders the reservations as the lines of a dhcp-hostsfile,
+/// each line has the syntax of a dhcp-host option
+pub fn render_hosts(reservations: &[DHCPReservation]) -> String {
+    let mut hosts = String::new();
+    for reservation in reservations {
+        let ip = match reservation.ip {
+            IPAddress::V4(ip) => format!("{}", ip),
+            IPAddress::V6(ip) => format!("[{}]", ip),
+        };
+        hosts.push_str(&format!("{},{}", format_mac(&reservation.mac), ip));
+        if let Some(hostname) = &reservation.hostname {
+            hosts.push_str(&format!(",{}", hostname));
+        }
+        hosts.push('\n');
+    }
+    hosts
+}
+
+/// Checks the DHCP options before they are written in the options file
+pub fn validate_options(options: &DHCPOptions) -> FResult<()> {
+    if options.lease_time == Some(0) {
+        return Err(FError::NetworkingError(
+            "The lease time cannot be 0".to_string(),
+        ));
+    }
+    if let Some(domain) = &options.domain {
+        let valid = !domain.is_empty()
+            && domain.split('.').all(|label| {
+                !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+        if !valid {
+            return Err(FError::NetworkingError(format!(
+                "Invalid domain {:?}",
+                domain
+            )));
+        }
+    }
+    // RFC 2132 minimum
+    if let Some(mtu) = options.mtu {
+        if mtu < 68 {
+            return Err(FError::NetworkingError(format!("Invalid MTU {}", mtu)));
+        }
+    }
+    if let Some(extra) = options
+        .extra
+        .iter()
+        .find(|e| e.trim().is_empty() || e.contains('\n'))
+    {
+        return Err(FError::NetworkingError(format!(
+            "Invalid DHCP option {:?}",
+            extra
+        )));
+    }
+    Ok(())
+}
+
+/// Renders the DHCP options as the lines of a dhcp-optsfile,
+/// each line has the syntax of a dhcp-option option
+pub fn render_options(options: &DHCPOptions) -> String {
+    let mut opts = Vec::new();
+    if let Some(domain) = &options.domain {
+        opts.push(format!("option:domain-name,{}", domain));
+    }
+    let (ntp4, ntp6): (Vec<_>, Vec<_>) = options
+        .ntp_servers
+        .iter()
+        .partition(|ip| matches!(ip, IPAddress::V4(_)));
+    if !ntp4.is_empty() {
+        opts.push(format!("option:ntp-server,{}", join_addresses(&ntp4)));
+    }
+    if !ntp6.is_empty() {
+        opts.push(format!("option6:sntp-server,{}", join_addresses(&ntp6)));
+    }
+    if let Some(mtu) = options.mtu {
+        opts.push(format!("option:mtu,{}", mtu));
+    }
+    opts.extend(options.extra.iter().cloned());
+    opts.into_iter().map(|o| format!("{}\n", o)).collect()
+}
+
+/// Joins addresses as dnsmasq expects them in options,
+/// IPv6 addresses are enclosed in brackets
+pub fn join_addresses(addresses: &[&IPAddress]) -> String {
+    addresses
+        .iter()
+        .map(|ip| match ip {
+            IPAddress::V4(ip) => format!("{}", ip),
+            IPAddress::V6(ip) => format!("[{}]", ip),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Checks if two MAC addresses are the same
+pub fn same_mac(a: &MACAddress, b: &MACAddress) -> bool {
+    (a.0, a.1, a.2, a.3, a.4, a.5) == (b.0, b.1, b.2, b.3, b.4, b.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leases() {
+        let content = "1700000000 52:54:00:12:34:56 10.240.0.10 plc-gw 01:52:54:00:12:34:56\n\
+                       0 52:54:00:ab:cd:ef 10.240.0.11 * *\n\
+                       duid 00:01:00:01:2a:3b:4c:5d:52:54:00:00:00:01\n\
+                       1700000100 1234 fd00::10 * 00:01:00:01:2a:3b:4c:5d:52:54:00:12:34:56\n";
+        let leases = parse_leases(content).unwrap();
+        assert_eq!(leases.len(), 3);
+
+        assert_eq!(leases[0].expiry, 1_700_000_000);
+        assert!(same_mac(
+            leases[0].mac.as_ref().unwrap(),
+            &MACAddress::new(0x52, 0x54, 0x00, 0x12, 0x34, 0x56)
+        ));
+        assert_eq!(leases[0].ip, "10.240.0.10".parse::<IPAddress>().unwrap());
+        assert_eq!(leases[0].hostname.as_deref(), Some("plc-gw"));
+        assert_eq!(leases[0].client_id.as_deref(), Some("01:52:54:00:12:34:56"));
+
+        assert_eq!(leases[1].expiry, 0);
+        assert_eq!(leases[1].hostname, None);
+        assert_eq!(leases[1].client_id, None);
+
+        assert!(leases[2].mac.is_none());
+        assert_eq!(leases[2].ip, "fd00::10".parse::<IPAddress>().unwrap());
+    }
+
+    #[test]
+    fn malformed_leases() {
+        assert!(parse_leases("").unwrap().is_empty());
+        assert!(parse_leases("1700000000 52:54:00:12:34:56").is_err());
+        assert!(parse_leases("soon 52:54:00:12:34:56 10.240.0.10 * *").is_err());
+        assert!(parse_leases("0 52:54:00:12:34:56 not-an-ip * *").is_err());
+    }
+
+    #[test]
+    fn hosts() {
+        let reservations = vec![
+            DHCPReservation {
+                mac: MACAddress::new(0x52, 0x54, 0x00, 0x12, 0x34, 0x56),
+                ip: "10.240.0.10".parse().unwrap(),
+                hostname: Some("plc-gw".to_string()),
+            },
+            DHCPReservation {
+                mac: MACAddress::new(0x52, 0x54, 0x00, 0xab, 0xcd, 0xef),
+                ip: "fd00::10".parse().unwrap(),
+                hostname: None,
+            },
+        ];
+        assert_eq!(
+            render_hosts(&reservations),
+            "52:54:00:12:34:56,10.240.0.10,plc-gw\n52:54:00:ab:cd:ef,[fd00::10]\n"
+        );
+        assert!(render_hosts(&[]).is_empty());
+
+        assert!(validate_reservation(&reservations[0]).is_ok());
+        let mut invalid = reservations[0].clone();
+        invalid.hostname = Some("plc,gw".to_string());
+        assert!(validate_reservation(&invalid).is_err());
+    }
+
+    #[test]
+    fn options() {
+        let mut options = DHCPOptions {
+            lease_time: Some(3600),
+            domain: Some("plant.local".to_string()),
+            ntp_servers: vec!["10.240.0.1".parse().unwrap(), "fd00::1".parse().unwrap()],
+            mtu: Some(1450),
+            extra: vec!["option:tftp-server,10.240.0.5".to_string()],
+        };
+        assert!(validate_options(&options).is_ok());
+        assert_eq!(
+            render_options(&options),
+            "option:domain-name,plant.local\n\
+             option:ntp-server,10.240.0.1\n\
+             option6:sntp-server,[fd00::1]\n\
+             option:mtu,1450\n\
+             option:tftp-server,10.240.0.5\n"
+        );
+        assert!(render_options(&DHCPOptions::default()).is_empty());
+
+        options.domain = Some("plant..local".to_string());
+        assert!(validate_options(&options).is_err());
+        options.domain = None;
+        options.mtu = Some(10);
+        assert!(validate_options(&options).is_err());
+        options.mtu = None;
+        options.extra = vec!["option:mtu,1500\ndhcp-range=0.0.0.0".to_string()];
+        assert!(validate_options(&options).is_err());
+    }
+
+    #[test]
+    fn macs() {
+        assert!(parse_mac("52:54:00:12:34:56").is_some());
+        assert!(parse_mac("20-52:54:00:12:34:56").is_none());
+        assert!(parse_mac("52:54:00:12:34").is_none());
+    }
+}
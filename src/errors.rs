@@ -0,0 +1,38 @@
+/*********************************************************************************
+* Copyright (c) 2018,2020 ADLINK Technology Inc.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0, or the Apache Software License 2.0
+* which is available at https://www.apache.org/licenses/LICENSE-2.0.
+*
+* SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+* Contributors:
+*   ADLINK fog05 team, <fog05@adlink-labs.tech>
+*********************************************************************************/
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use fog05_sdk::fresult::FError;
+
+#[derive(Error, Debug)]
+pub enum LinuxNetworkError {
+    #[error("Namespace manager for {ns_name} not ready after {timeout:?}")]
+    NSManagerTimeout { ns_name: String, timeout: Duration },
+    #[error("Namespace manager for {ns_name} exited before being ready")]
+    NSManagerExited { ns_name: String },
+    #[error("Invalid name {name:?}: {reason}")]
+    InvalidName { name: String, reason: String },
+    #[error("Name {name} is already in use")]
+    NameCollision { name: String },
+    #[error("Teardown failed: {}", .errors.join("; "))]
+    TeardownFailed { errors: Vec<String> },
+}
+
+impl From<LinuxNetworkError> for FError {
+    fn from(err: LinuxNetworkError) -> Self {
+        FError::NetworkingError(format!("{}", err))
+    }
+}
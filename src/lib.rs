@@ -11,5 +11,9 @@
 *   ADLINK fog05 team, <fog05@adlink-labs.tech>
 *********************************************************************************/
 
+pub mod dhcp;
+pub mod errors;
+pub mod netlink;
 pub mod networking;
+pub mod services;
 pub mod types;
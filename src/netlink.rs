@@ -0,0 +1,1326 @@
+/*********************************************************************************
+* Copyright (c) 2018,2020 ADLINK Technology Inc.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0, or the Apache Software License 2.0
+* which is available at https://www.apache.org/licenses/LICENSE-2.0.
+*
+* SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+* Contributors:
+*   ADLINK fog05 team, <fog05@adlink-labs.tech>
+*********************************************************************************/
+
+//! Netlink backend shared by the plugin and the namespace manager
+
+pub mod fake;
+
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::os::unix::io::IntoRawFd;
+
+use async_trait::async_trait;
+
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+
+use fog05_sdk::fresult::{FError, FResult};
+use fog05_sdk::types::IPAddress;
+
+use netlink_packet_route::rtnl::link::nlas::Nla as LinkNla;
+use netlink_packet_route::{LinkMessage, NetlinkPayload, RtnlMessage};
+use rtnetlink::NetworkNamespace as NetlinkNetworkNamespace;
+use rtnetlink::{new_connection, Handle};
+
+use ipnetwork::IpNetwork;
+
+use serde::{Deserialize, Serialize};
+
+use uuid::Uuid;
+
+use nftnl::{nft_expr, nftnl_sys::libc, Batch, Chain, FinalizedBatch, ProtoFamily, Rule, Table};
+
+use crate::types::{GRETunnelKind, MACVLANMode};
+
+/// Prefix of the link aliases and nft rule comments of the objects
+/// created by fog05, it is followed by the UUID of the owner record
+pub const OWNER_TAG_PREFIX: &str = "fog05:";
+
+/// Returns the tag marking an object as owned by the given record
+pub fn owner_tag(owner: &Uuid) -> String {
+    format!("{}{}", OWNER_TAG_PREFIX, owner)
+}
+
+/// Returns the owner UUID from a tag, None if the object is not owned by fog05
+pub fn parse_owner_tag(tag: &str) -> Option<Uuid> {
+    tag.strip_prefix(OWNER_TAG_PREFIX)
+        .and_then(|owner| Uuid::parse_str(owner).ok())
+}
+
+/// Prefix of the nft tables created by fog05
+pub const OWNER_TABLE_PREFIX: &str = "fog05-";
+
+/// Returns the name of the nft table owned by the given record.
+/// nftnl cannot set the userdata of a table, so the name is what marks
+/// the table as owned, while its rules carry [`owner_tag`] in their comment.
+pub fn owner_table_name(owner: &Uuid) -> String {
+    format!("{}{}", OWNER_TABLE_PREFIX, owner)
+}
+
+/// Returns the owner UUID from a table name, None if the table is not owned by fog05
+pub fn parse_owner_table_name(name: &str) -> Option<Uuid> {
+    name.strip_prefix(OWNER_TABLE_PREFIX)
+        .and_then(|owner| Uuid::parse_str(owner).ok())
+}
+
+/// Link and address changes notified by the kernel,
+/// Namespace Managers pass them to the plugin
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum NetlinkEvent {
+    /// A link was created or its state changed, the alias
+    /// carries the owner tag of managed links, see [`owner_tag`]
+    LinkAdded {
+        name: String,
+        alias: Option<String>,
+        up: bool,
+    },
+    /// A link was removed, the kernel still reports its alias
+    LinkRemoved { name: String, alias: Option<String> },
+    /// An address was added or removed from the link with this index
+    AddressChanged(u32),
+}
+
+/// Operations on links, addresses and network namespaces,
+/// they act on the network namespace the backend was created in
+#[async_trait]
+pub trait NetlinkBackend: Send + Sync {
+    async fn add_netns(&self, ns_name: String) -> FResult<()>;
+    async fn del_netns(&self, ns_name: String) -> FResult<()>;
+    /// Lists the network namespaces, as `ip netns list`
+    async fn list_netns(&self) -> FResult<Vec<String>>;
+    async fn create_bridge(&self, br_name: String) -> FResult<()>;
+    async fn create_veth(&self, iface_i: String, iface_e: String) -> FResult<()>;
+    async fn create_vlan(&self, iface: String, dev: String, tag: u16) -> FResult<()>;
+    async fn create_macvlan(&self, iface: String, dev: String, mode: MACVLANMode) -> FResult<()>;
+    async fn create_mcast_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        mcast_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()>;
+    async fn create_ptp_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        local_addr: IPAddress,
+        remote_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()>;
+    async fn create_gre(
+        &self,
+        iface: String,
+        kind: GRETunnelKind,
+        local_addr: IPAddress,
+        remote_addr: IPAddress,
+        ttl: u8,
+    ) -> FResult<()>;
+    async fn del_iface(&self, iface: String) -> FResult<()>;
+    async fn set_iface_master(&self, iface: String, master: String) -> FResult<()>;
+    async fn del_iface_master(&self, iface: String) -> FResult<()>;
+    async fn add_iface_address(&self, iface: String, addr: IPAddress, prefix: u8) -> FResult<()>;
+    async fn del_iface_address(&self, iface: String, addr: IPAddress) -> FResult<()>;
+    async fn get_iface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>>;
+    async fn get_iface_name(&self, index: u32) -> FResult<String>;
+    async fn set_iface_name(&self, iface: String, new_name: String) -> FResult<()>;
+    async fn set_iface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()>;
+    /// Sets the IFLA_IFALIAS of the link, see [`owner_tag`]
+    async fn set_iface_alias(&self, iface: String, alias: String) -> FResult<()>;
+    async fn get_iface_alias(&self, iface: String) -> FResult<Option<String>>;
+    async fn set_iface_ns(&self, iface: String, netns: String) -> FResult<()>;
+    async fn set_iface_default_ns(&self, iface: String) -> FResult<()>;
+    async fn set_iface_up(&self, iface: String) -> FResult<()>;
+    async fn set_iface_down(&self, iface: String) -> FResult<()>;
+    async fn iface_exists(&self, iface: String) -> FResult<bool>;
+    async fn dump_links(&self) -> FResult<Vec<String>>;
+    /// Appends an all-zeros entry toward `dst` to the FDB of a VXLAN link,
+    /// as `bridge fdb append 00:00:00:00:00:00 dev <iface> dst <dst>`
+    async fn append_fdb_entry(&self, iface: String, dst: IPAddress) -> FResult<()>;
+    async fn del_fdb_entry(&self, iface: String, dst: IPAddress) -> FResult<()>;
+    /// Subscribes to link and address changes
+    async fn subscribe(&self) -> FResult<BoxStream<'static, NetlinkEvent>>;
+    /// Creates a nftables table masquerading the given networks
+    /// toward the given interface, the rules are commented with
+    /// the tag of the owner
+    async fn add_nat_table(
+        &self,
+        table_name: String,
+        owner: Uuid,
+        nets: Vec<IpNetwork>,
+        iface: String,
+    ) -> FResult<()>;
+    async fn del_nat_table(&self, table_name: String) -> FResult<()>;
+    /// Returns the names of the nftables tables, of any family
+    async fn list_nat_tables(&self) -> FResult<Vec<String>>;
+}
+
+/// Backend based on rtnetlink, requests run on the given tokio runtime
+/// each with its own copy of the netlink handle so they run concurrently
+#[derive(Clone)]
+pub struct RtNetlinkBackend {
+    handle: Handle,
+    rt: tokio::runtime::Handle,
+}
+
+impl RtNetlinkBackend {
+    pub async fn new(rt: &tokio::runtime::Runtime) -> FResult<Self> {
+        // this will be removed once netlink merges the async-std support
+        let handle = rt
+            .spawn_blocking(|| {
+                let (connection, handle, _) = new_connection()?;
+                tokio::spawn(connection);
+                Ok::<_, std::io::Error>(handle)
+            })
+            .await
+            .map_err(|e| FError::NetworkingError(format!("{}", e)))??;
+        Ok(Self {
+            handle,
+            rt: rt.handle().clone(),
+        })
+    }
+
+    /// Runs a netlink request on the tokio runtime, this does not
+    /// block the async-std executor
+    async fn run<F, Fut, T>(&self, f: F) -> FResult<T>
+    where
+        F: FnOnce(Handle) -> Fut,
+        Fut: std::future::Future<Output = FResult<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        self.rt
+            .spawn(f(self.handle.clone()))
+            .await
+            .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+    }
+}
+
+#[async_trait]
+impl NetlinkBackend for RtNetlinkBackend {
+    async fn add_netns(&self, ns_name: String) -> FResult<()> {
+        log::trace!("add_netns {}", ns_name);
+        self.run(move |_| async move {
+            NetlinkNetworkNamespace::add(ns_name)
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        })
+        .await
+    }
+
+    async fn del_netns(&self, ns_name: String) -> FResult<()> {
+        log::trace!("del_netns {}", ns_name);
+        self.run(move |_| async move {
+            NetlinkNetworkNamespace::del(ns_name)
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        })
+        .await
+    }
+
+    async fn list_netns(&self) -> FResult<Vec<String>> {
+        let mut netns = Vec::new();
+        let mut entries = match async_std::fs::read_dir("/run/netns").await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(netns),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next().await {
+            netns.push(entry?.file_name().to_string_lossy().to_string());
+        }
+        Ok(netns)
+    }
+
+    async fn create_bridge(&self, br_name: String) -> FResult<()> {
+        log::trace!("create_bridge {}", br_name);
+        self.run(move |handle| async move {
+            handle
+                .link()
+                .add()
+                .bridge(br_name)
+                .execute()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        })
+        .await
+    }
+
+    async fn create_veth(&self, iface_i: String, iface_e: String) -> FResult<()> {
+        log::trace!("create_veth {} {}", iface_i, iface_e);
+        self.run(move |handle| async move {
+            handle
+                .link()
+                .add()
+                .veth(iface_i, iface_e)
+                .execute()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        })
+        .await
+    }
+
+    async fn create_vlan(&self, iface: String, dev: String, tag: u16) -> FResult<()> {
+        log::trace!("create_vlan {} {} {}", iface, dev, tag);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(dev).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .add()
+                    .vlan(iface, link.header.index, tag)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn create_macvlan(&self, iface: String, dev: String, mode: MACVLANMode) -> FResult<()> {
+        log::trace!("create_macvlan {} {} {:?}", iface, dev, mode);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(dev).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .add()
+                    .macvlan(iface, link.header.index, mode as u32)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn create_mcast_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        mcast_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()> {
+        log::trace!(
+            "create_mcast_vxlan {} {} {} {} {}",
+            iface,
+            dev,
+            vni,
+            mcast_addr,
+            port
+        );
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(dev).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                let vxlan = handle
+                    .link()
+                    .add()
+                    .vxlan(iface, vni)
+                    .link(link.header.index);
+
+                let vxlan = match mcast_addr {
+                    IPAddress::V4(v4) => vxlan.group(v4),
+                    IPAddress::V6(v6) => vxlan.group6(v6),
+                };
+
+                vxlan
+                    .port(port)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn create_ptp_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        local_addr: IPAddress,
+        remote_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()> {
+        log::trace!(
+            "create_ptp_vxlan {} {} {} {} {} {}",
+            iface,
+            dev,
+            vni,
+            local_addr,
+            remote_addr,
+            port
+        );
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(dev).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                let vxlan = handle
+                    .link()
+                    .add()
+                    .vxlan(iface, vni)
+                    .link(link.header.index);
+
+                let vxlan = match local_addr {
+                    IPAddress::V4(v4) => vxlan.local(v4),
+                    IPAddress::V6(v6) => vxlan.local6(v6),
+                };
+
+                let vxlan = match remote_addr {
+                    IPAddress::V4(v4) => vxlan.remote(v4),
+                    IPAddress::V6(v6) => vxlan.remote6(v6),
+                };
+
+                vxlan
+                    .port(port)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn create_gre(
+        &self,
+        iface: String,
+        kind: GRETunnelKind,
+        local_addr: IPAddress,
+        remote_addr: IPAddress,
+        ttl: u8,
+    ) -> FResult<()> {
+        log::trace!(
+            "create_gre {} {:?} {} {} {}",
+            iface,
+            kind,
+            local_addr,
+            remote_addr,
+            ttl
+        );
+        use netlink_packet_route::rtnl::link::nlas::{Info, InfoData, InfoKind, Nla as LinkNla};
+
+        // IFLA_GRE_* attributes from linux/if_tunnel.h
+        const IFLA_GRE_LOCAL: u16 = 6;
+        const IFLA_GRE_REMOTE: u16 = 7;
+        const IFLA_GRE_TTL: u16 = 8;
+
+        // rtnetlink does not provide a builder for GRE links,
+        // so the IFLA_INFO_DATA payload is encoded by hand
+        fn emit_nla(buf: &mut Vec<u8>, kind: u16, value: &[u8]) {
+            let len = (4 + value.len()) as u16;
+            buf.extend_from_slice(&len.to_ne_bytes());
+            buf.extend_from_slice(&kind.to_ne_bytes());
+            buf.extend_from_slice(value);
+            // attributes are aligned to 4 bytes
+            while buf.len() % 4 != 0 {
+                buf.push(0);
+            }
+        }
+
+        let (local, remote) = match (kind, local_addr, remote_addr) {
+            (GRETunnelKind::GRE, IPAddress::V4(l), IPAddress::V4(r))
+            | (GRETunnelKind::GRETAP, IPAddress::V4(l), IPAddress::V4(r)) => {
+                (l.octets().to_vec(), r.octets().to_vec())
+            }
+            (GRETunnelKind::IP6GRE, IPAddress::V6(l), IPAddress::V6(r))
+            | (GRETunnelKind::IP6GRETAP, IPAddress::V6(l), IPAddress::V6(r)) => {
+                (l.octets().to_vec(), r.octets().to_vec())
+            }
+            _ => {
+                return Err(FError::NetworkingError(format!(
+                    "Address family of {} and {} not valid for {:?}",
+                    local_addr, remote_addr, kind
+                )))
+            }
+        };
+
+        let mut data = Vec::new();
+        emit_nla(&mut data, IFLA_GRE_LOCAL, &local);
+        emit_nla(&mut data, IFLA_GRE_REMOTE, &remote);
+        emit_nla(&mut data, IFLA_GRE_TTL, &[ttl]);
+
+        let (info_kind, info_data) = match kind {
+            GRETunnelKind::GRE => (InfoKind::GreTun, InfoData::GreTun(data)),
+            GRETunnelKind::GRETAP => (InfoKind::GreTap, InfoData::GreTap(data)),
+            GRETunnelKind::IP6GRE => (InfoKind::GreTun6, InfoData::GreTun6(data)),
+            GRETunnelKind::IP6GRETAP => (InfoKind::GreTap6, InfoData::GreTap6(data)),
+        };
+
+        self.run(move |handle| async move {
+            let mut req = handle.link().add();
+            let msg = req.message_mut();
+            msg.nlas.push(LinkNla::IfName(iface));
+            msg.nlas.push(LinkNla::Info(vec![
+                Info::Kind(info_kind),
+                Info::Data(info_data),
+            ]));
+            req.execute()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        })
+        .await
+    }
+
+    async fn del_iface(&self, iface: String) -> FResult<()> {
+        log::trace!("del_iface {}", iface);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .del(link.header.index)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn set_iface_master(&self, iface: String, master: String) -> FResult<()> {
+        log::trace!("set_iface_master {} {}", iface, master);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                let mut masters = handle.link().get().set_name_filter(master).execute();
+                if let Some(master) = masters
+                    .try_next()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+                {
+                    handle
+                        .link()
+                        .set(link.header.index)
+                        .master(master.header.index)
+                        .execute()
+                        .await
+                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
+                } else {
+                    log::error!("set_iface_master master not found");
+                    Err(FError::NotFound)
+                }
+            } else {
+                log::error!("set_iface_master iface not found");
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn del_iface_master(&self, iface: String) -> FResult<()> {
+        log::trace!("del_iface_master {}", iface);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .set(link.header.index)
+                    .nomaster()
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                log::error!("del_iface_master iface not found");
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn add_iface_address(&self, iface: String, addr: IPAddress, prefix: u8) -> FResult<()> {
+        log::trace!("add_iface_address {} {} {}", iface, addr, prefix);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .address()
+                    .add(link.header.index, addr, prefix)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn del_iface_address(&self, iface: String, addr: IPAddress) -> FResult<()> {
+        log::trace!("del_iface_address {} {}", iface, addr);
+        use netlink_packet_route::rtnl::address::nlas::Nla;
+        use netlink_packet_route::rtnl::address::AddressMessage;
+        self.run(move |handle| async move {
+            let octets = match addr {
+                IPAddress::V4(a) => a.octets().to_vec(),
+                IPAddress::V6(a) => a.octets().to_vec(),
+            };
+            let mut nl_addresses = Vec::new();
+            let mut links = handle.link().get().set_name_filter(iface.clone()).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                let mut addresses = handle
+                    .address()
+                    .get()
+                    .set_link_index_filter(link.header.index)
+                    .execute();
+                while let Some(msg) = addresses
+                    .try_next()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+                {
+                    for nla in &msg.nlas {
+                        match nla {
+                            Nla::Address(nl_addr) => {
+                                nl_addresses.push((msg.header.clone(), nl_addr.clone()))
+                            }
+                            _ => continue,
+                        }
+                    }
+                }
+                match nl_addresses.into_iter().find(|(_, x)| *x == octets) {
+                    Some((hdr, addr)) => {
+                        let msg = AddressMessage {
+                            header: hdr,
+                            nlas: vec![Nla::Address(addr)],
+                        };
+                        handle
+                            .address()
+                            .del(msg)
+                            .execute()
+                            .await
+                            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+                        Ok(())
+                    }
+                    None => Err(FError::NotFound),
+                }
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn get_iface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>> {
+        log::trace!("get_iface_addresses {}", iface);
+        use netlink_packet_route::rtnl::address::nlas::Nla;
+        use netlink_packet_route::rtnl::address::AddressMessage;
+        self.run(move |handle| async move {
+            let mut nl_addresses = Vec::new();
+            let mut f_addresses: Vec<IPAddress> = Vec::new();
+            let mut links = handle.link().get().set_name_filter(iface.clone()).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                let mut addresses = handle
+                    .address()
+                    .get()
+                    .set_link_index_filter(link.header.index)
+                    .execute();
+                while let Some(msg) = addresses
+                    .try_next()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+                {
+                    for nla in &msg.nlas {
+                        match nla {
+                            Nla::Address(nl_addr) => {
+                                nl_addresses.push((msg.header.clone(), nl_addr.clone()))
+                            }
+                            _ => continue,
+                        }
+                    }
+                }
+                for (_, x) in nl_addresses {
+                    if x.len() == 4 {
+                        let octects: [u8; 4] = [x[0], x[1], x[2], x[3]];
+                        f_addresses.push(IPAddress::from(octects))
+                    }
+                    if x.len() == 16 {
+                        let octects: [u8; 16] = [
+                            x[0], x[1], x[2], x[3], x[4], x[5], x[6], x[7], x[8], x[9], x[10],
+                            x[11], x[12], x[13], x[14], x[15],
+                        ];
+                        f_addresses.push(IPAddress::from(octects))
+                    }
+                }
+                Ok(f_addresses)
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn get_iface_name(&self, index: u32) -> FResult<String> {
+        log::trace!("get_iface_name {}", index);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().match_index(index).execute();
+            match links.try_next().await {
+                Ok(Some(link)) => link_name(&link).ok_or(FError::NotFound),
+                Ok(None) => Err(FError::NotFound),
+                // the kernel answers ENODEV for missing links
+                Err(_) => Err(FError::NotFound),
+            }
+        })
+        .await
+    }
+
+    async fn set_iface_name(&self, iface: String, new_name: String) -> FResult<()> {
+        log::trace!("set_iface_name {} {}", iface, new_name);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .set(link.header.index)
+                    .name(new_name)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn set_iface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()> {
+        log::trace!("set_iface_mac {} {:?}", iface, address);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .set(link.header.index)
+                    .address(address)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn set_iface_alias(&self, iface: String, alias: String) -> FResult<()> {
+        log::trace!("set_iface_alias {} {}", iface, alias);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                let mut req = handle.link().set(link.header.index);
+                req.message_mut().nlas.push(LinkNla::IfAlias(alias));
+                req.execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn get_iface_alias(&self, iface: String) -> FResult<Option<String>> {
+        log::trace!("get_iface_alias {}", iface);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                Ok(link.nlas.iter().find_map(|nla| match nla {
+                    LinkNla::IfAlias(alias) => Some(alias.clone()),
+                    _ => None,
+                }))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn set_iface_ns(&self, iface: String, netns: String) -> FResult<()> {
+        log::trace!("set_iface_ns {} {}", iface, netns);
+        const NETNS_PATH: &str = "/run/netns/";
+        let netns = format!("{}{}", NETNS_PATH, netns);
+        let nsfile = std::fs::File::open(netns)?;
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .set(link.header.index)
+                    .setns_by_fd(nsfile.into_raw_fd())
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn set_iface_default_ns(&self, iface: String) -> FResult<()> {
+        log::trace!("set_iface_default_ns {}", iface);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .set(link.header.index)
+                    .setns_by_pid(1)
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn set_iface_up(&self, iface: String) -> FResult<()> {
+        log::trace!("set_iface_up {}", iface);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .set(link.header.index)
+                    .up()
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn set_iface_down(&self, iface: String) -> FResult<()> {
+        log::trace!("set_iface_down {}", iface);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                handle
+                    .link()
+                    .set(link.header.index)
+                    .down()
+                    .execute()
+                    .await
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+            } else {
+                Err(FError::NotFound)
+            }
+        })
+        .await
+    }
+
+    async fn iface_exists(&self, iface: String) -> FResult<bool> {
+        log::trace!("iface_exists {}", iface);
+        self.run(move |handle| async move {
+            let mut links = handle.link().get().set_name_filter(iface).execute();
+            if let Some(link) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                Ok(true)
+            } else {
+                Ok(false)
+            }
+        })
+        .await
+    }
+
+    async fn dump_links(&self) -> FResult<Vec<String>> {
+        log::trace!("dump_links");
+        self.run(move |handle| async move {
+            let mut ifaces = Vec::new();
+            let mut links = handle.link().get().execute();
+            while let Some(msg) = links
+                .try_next()
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            {
+                if let Some(name) = link_name(&msg) {
+                    ifaces.push(name);
+                }
+            }
+            Ok(ifaces)
+        })
+        .await
+    }
+
+    async fn append_fdb_entry(&self, iface: String, dst: IPAddress) -> FResult<()> {
+        log::trace!("append_fdb_entry {} {}", iface, dst);
+        self.run(move |handle| async move { fdb_request(handle, iface, dst, true).await })
+            .await
+    }
+
+    async fn del_fdb_entry(&self, iface: String, dst: IPAddress) -> FResult<()> {
+        log::trace!("del_fdb_entry {} {}", iface, dst);
+        self.run(move |handle| async move { fdb_request(handle, iface, dst, false).await })
+            .await
+    }
+
+    async fn subscribe(&self) -> FResult<BoxStream<'static, NetlinkEvent>> {
+        use netlink_packet_route::constants::{
+            IFF_UP, RTMGRP_IPV4_IFADDR, RTMGRP_IPV6_IFADDR, RTMGRP_LINK,
+        };
+        use rtnetlink::sys::SocketAddr;
+
+        // a dedicated connection is bound to the multicast groups
+        let messages = self
+            .run(|_| async move {
+                let (mut connection, _, messages) =
+                    new_connection().map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+                let addr =
+                    SocketAddr::new(0, RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR);
+                connection
+                    .socket_mut()
+                    .bind(&addr)
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+                tokio::spawn(connection);
+                Ok(messages)
+            })
+            .await?;
+
+        Ok(messages
+            .filter_map(|(msg, _)| async move {
+                match msg.payload {
+                    NetlinkPayload::InnerMessage(RtnlMessage::NewLink(link)) => link_name(&link)
+                        .map(|name| NetlinkEvent::LinkAdded {
+                            name,
+                            alias: link_alias(&link),
+                            up: link.header.flags & IFF_UP != 0,
+                        }),
+                    NetlinkPayload::InnerMessage(RtnlMessage::DelLink(link)) => link_name(&link)
+                        .map(|name| NetlinkEvent::LinkRemoved {
+                            name,
+                            alias: link_alias(&link),
+                        }),
+                    NetlinkPayload::InnerMessage(RtnlMessage::NewAddress(addr))
+                    | NetlinkPayload::InnerMessage(RtnlMessage::DelAddress(addr)) => {
+                        Some(NetlinkEvent::AddressChanged(addr.header.index))
+                    }
+                    _ => None,
+                }
+            })
+            .boxed())
+    }
+
+    async fn add_nat_table(
+        &self,
+        table_name: String,
+        owner: Uuid,
+        nets: Vec<IpNetwork>,
+        iface: String,
+    ) -> FResult<()> {
+        log::trace!(
+            "add_nat_table {} {} {:?} {}",
+            table_name,
+            owner,
+            nets,
+            iface
+        );
+        // libmnl sockets are blocking
+        async_std::task::spawn_blocking(move || {
+            add_nat_table_blocking(table_name, owner, nets, iface)
+        })
+        .await
+    }
+
+    async fn del_nat_table(&self, table_name: String) -> FResult<()> {
+        log::trace!("del_nat_table {}", table_name);
+        async_std::task::spawn_blocking(move || del_nat_table_blocking(table_name)).await
+    }
+
+    async fn list_nat_tables(&self) -> FResult<Vec<String>> {
+        log::trace!("list_nat_tables");
+        async_std::task::spawn_blocking(list_nat_tables_blocking).await
+    }
+}
+
+/// Body of [`RtNetlinkBackend::add_nat_table`], the nftnl objects
+/// are not Send so the batch is built on the blocking thread too
+fn add_nat_table_blocking(
+    table_name: String,
+    owner: Uuid,
+    nets: Vec<IpNetwork>,
+    iface: String,
+) -> FResult<()> {
+    let chain_name = String::from("postrouting");
+    // Create a batch. This is used to store all the netlink messages we will later send.
+    // Creating a new batch also automatically writes the initial batch begin message needed
+    // to tell netlink this is a single transaction that might arrive over multiple netlink packets.
+    let mut batch = Batch::new();
+    // Create a netfilter table operating on both IPv4 and IPv6 (ProtoFamily::Inet)
+    let table = Table::new(
+        &CString::new(table_name.clone()).map_err(|e| FError::NetworkingError(format!("{}", e)))?,
+        ProtoFamily::Inet,
+    );
+    // Add the table to the batch with the `MsgType::Add` type, thus instructing netfilter to add
+    // this table under its `ProtoFamily::Inet` ruleset.
+    batch.add(&table, nftnl::MsgType::Add);
+
+    // Create a chain under the table we created above.
+    let mut chain = Chain::new(
+        &CString::new(chain_name).map_err(|e| FError::NetworkingError(format!("{}", e)))?,
+        &table,
+    );
+
+    // Hook the chains to the input and output event hooks, with highest priority (priority zero).
+    // See the `Chain::set_hook` documentation for details.
+    chain.set_hook(nftnl::Hook::PostRouting, 0);
+    // Set the chain type.
+    // See the `Chain::set_type` documentation for details.
+    chain.set_type(nftnl::ChainType::Nat);
+
+    // Add the two chains to the batch with the `MsgType` to tell netfilter to create the chains
+    // under the table.
+    batch.add(&chain, nftnl::MsgType::Add);
+
+    // Lookup the interface index of the default gw interface.
+    let iface_index = iface_index(&iface)?;
+
+    // One rule for each network, an Inet table sees both IPv4 and IPv6
+    // packets so each rule first matches the protocol of its network
+    for net in &nets {
+        // Create a new rule object under the input chain.
+        let mut natting_rule = Rule::new(&chain);
+
+        natting_rule.add_expr(&nft_expr!(meta nfproto));
+        match net {
+            IpNetwork::V4(net) => {
+                natting_rule.add_expr(&nft_expr!(cmp == libc::NFPROTO_IPV4 as u8));
+
+                //Type of payload is source address
+                natting_rule.add_expr(&nft_expr!(payload ipv4 saddr));
+
+                //netmask of the network
+                natting_rule.add_expr(&nft_expr!(bitwise mask net.mask(), xor 0u32));
+
+                //comparing ip portion of the address
+                natting_rule.add_expr(&nft_expr!(cmp == net.ip()));
+            }
+            IpNetwork::V6(net) => {
+                natting_rule.add_expr(&nft_expr!(cmp == libc::NFPROTO_IPV6 as u8));
+
+                //Type of payload is source address
+                natting_rule.add_expr(&nft_expr!(payload ipv6 saddr));
+
+                //netmask of the network, 128 bits
+                natting_rule.add_expr(&nft_expr!(
+                    bitwise mask net.mask(),
+                    xor std::net::Ipv6Addr::UNSPECIFIED
+                ));
+
+                //comparing ip portion of the address
+                natting_rule.add_expr(&nft_expr!(cmp == net.ip()));
+            }
+        }
+
+        // passing the index of output interface oif
+        natting_rule.add_expr(&nft_expr!(meta oif));
+
+        //use interface with this index
+        natting_rule.add_expr(&nft_expr!(cmp == iface_index));
+
+        // Add masquerading
+        natting_rule.add_expr(&nft_expr!(masquerade));
+
+        // Comment the rule with the owner, as `nft` does
+        set_rule_comment(&mut natting_rule, &owner_tag(&owner))?;
+
+        // Add the rule to the batch.
+        batch.add(&natting_rule, nftnl::MsgType::Add);
+    }
+
+    // === FINALIZE THE TRANSACTION AND SEND THE DATA TO NETFILTER ===
+
+    // Finalize the batch. This means the batch end message is written into the batch, telling
+    // netfilter the we reached the end of the transaction message. It's also converted to a type
+    // that implements `IntoIterator<Item = &'a [u8]>`, thus allowing us to get the raw netlink data
+    // out so it can be sent over a netlink socket to netfilter.
+    let finalized_batch = batch.finalize();
+
+    // Look up the interface index for a given interface name.
+    fn iface_index(name: &str) -> FResult<libc::c_uint> {
+        let c_name = CString::new(name).map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+        let index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+        if index == 0 {
+            Err(FError::from(std::io::Error::last_os_error()))
+        } else {
+            Ok(index)
+        }
+    }
+
+    send_batch(&finalized_batch)
+}
+
+/// Body of [`RtNetlinkBackend::del_nat_table`]
+fn del_nat_table_blocking(table_name: String) -> FResult<()> {
+    // Create a batch. This is used to store all the netlink messages we will later send.
+    // Creating a new batch also automatically writes the initial batch begin message needed
+    // to tell netlink this is a single transaction that might arrive over multiple netlink packets.
+    let mut batch = Batch::new();
+    // Create a netfilter table operating on both IPv4 and IPv6 (ProtoFamily::Inet)
+    let table = Table::new(
+        &CString::new(table_name).map_err(|e| FError::NetworkingError(format!("{}", e)))?,
+        ProtoFamily::Inet,
+    );
+    // Add the table to the batch with the `MsgType::Del` type, thus instructing netfilter to remove
+    // this table under its `ProtoFamily::Inet` ruleset.
+    batch.add(&table, nftnl::MsgType::Del);
+
+    // === FINALIZE THE TRANSACTION AND SEND THE DATA TO NETFILTER ===
+
+    // Finalize the batch. This means the batch end message is written into the batch, telling
+    // netfilter the we reached the end of the transaction message. It's also converted to a type
+    // that implements `IntoIterator<Item = &'a [u8]>`, thus allowing us to get the raw netlink data
+    // out so it can be sent over a netlink socket to netfilter.
+    let finalized_batch = batch.finalize();
+
+    send_batch(&finalized_batch)
+}
+
+/// Body of [`RtNetlinkBackend::list_nat_tables`]
+fn list_nat_tables_blocking() -> FResult<Vec<String>> {
+    let socket = mnl::Socket::new(mnl::Bus::Netfilter)?;
+    let portid = socket.portid();
+    let seq = 0;
+    // Ask netfilter to dump the tables, the names are collected by the callback
+    let get_tables_msg = nftnl::get_tables_nlmsg(seq);
+    socket.send(&get_tables_msg[..])?;
+    let mut tables = HashSet::new();
+    let mut buffer = vec![0; nftnl::nft_nlmsg_maxsize() as usize];
+    while let Some(message) = socket_recv(&socket, &mut buffer[..])? {
+        match mnl::cb_run2(message, seq, portid, nftnl::get_tables_cb, &mut tables)? {
+            mnl::CbResult::Stop => {
+                break;
+            }
+            mnl::CbResult::Ok => (),
+        }
+    }
+    Ok(tables
+        .into_iter()
+        .filter_map(|name: CString| name.into_string().ok())
+        .collect())
+}
+
+/// Sends a finalized nftables batch to netfilter and processes the replies,
+/// it blocks on the netlink socket
+fn send_batch(batch: &FinalizedBatch) -> FResult<()> {
+    // Create a netlink socket to netfilter.
+    let socket = mnl::Socket::new(mnl::Bus::Netfilter)?;
+    // Send all the bytes in the batch.
+    socket.send_all(batch)?;
+    // Try to parse the messages coming back from netfilter. This part is still very unclear.
+    let portid = socket.portid();
+    let mut buffer = vec![0; nftnl::nft_nlmsg_maxsize() as usize];
+    let very_unclear_what_this_is_for = 2;
+    while let Some(message) = socket_recv(&socket, &mut buffer[..])? {
+        match mnl::cb_run(message, very_unclear_what_this_is_for, portid)? {
+            mnl::CbResult::Stop => {
+                break;
+            }
+            mnl::CbResult::Ok => (),
+        }
+    }
+    Ok(())
+}
+
+/// Receives a message, None once the socket is drained
+fn socket_recv<'a>(socket: &mnl::Socket, buf: &'a mut [u8]) -> FResult<Option<&'a [u8]>> {
+    let ret = socket.recv(buf)?;
+    if ret > 0 {
+        Ok(Some(&buf[..ret]))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Sends a RTM_NEWNEIGH (append) or RTM_DELNEIGH for the all-zeros
+/// entry toward `dst` of the FDB of `iface`, rtnetlink has no builder
+/// for bridge neighbours so the message is built by hand
+async fn fdb_request(
+    mut handle: Handle,
+    iface: String,
+    dst: IPAddress,
+    append: bool,
+) -> FResult<()> {
+    use netlink_packet_route::constants::{
+        AF_BRIDGE, NLM_F_ACK, NLM_F_APPEND, NLM_F_CREATE, NLM_F_REQUEST, NTF_SELF, NUD_NOARP,
+        NUD_PERMANENT,
+    };
+    use netlink_packet_route::rtnl::neighbour::nlas::Nla as NeighbourNla;
+    use netlink_packet_route::rtnl::neighbour::NeighbourMessage;
+    use netlink_packet_route::NetlinkMessage;
+
+    let mut links = handle.link().get().set_name_filter(iface).execute();
+    let link = links
+        .try_next()
+        .await
+        .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+        .ok_or(FError::NotFound)?;
+
+    let mut msg = NeighbourMessage::default();
+    msg.header.family = AF_BRIDGE as u8;
+    msg.header.ifindex = link.header.index;
+    msg.header.state = NUD_NOARP | NUD_PERMANENT;
+    msg.header.flags = NTF_SELF;
+    msg.nlas.push(NeighbourNla::LinkLocalAddress(vec![0; 6]));
+    msg.nlas.push(NeighbourNla::Destination(match dst {
+        IPAddress::V4(a) => a.octets().to_vec(),
+        IPAddress::V6(a) => a.octets().to_vec(),
+    }));
+
+    let mut req = if append {
+        let mut req = NetlinkMessage::from(RtnlMessage::NewNeighbour(msg));
+        req.header.flags = NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_APPEND;
+        req
+    } else {
+        let mut req = NetlinkMessage::from(RtnlMessage::DelNeighbour(msg));
+        req.header.flags = NLM_F_REQUEST | NLM_F_ACK;
+        req
+    };
+    req.finalize();
+
+    let mut response = handle
+        .request(req)
+        .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+    while let Some(msg) = response.next().await {
+        if let NetlinkPayload::Error(e) = msg.payload {
+            return Err(FError::NetworkingError(format!("{}", e)));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the name of a link from its netlink message
+fn link_name(link: &LinkMessage) -> Option<String> {
+    link.nlas.iter().find_map(|nla| match nla {
+        LinkNla::IfName(name) => Some(name.clone()),
+        _ => None,
+    })
+}
+
+/// Returns the alias of a link from its netlink message
+fn link_alias(link: &LinkMessage) -> Option<String> {
+    link.nlas.iter().find_map(|nla| match nla {
+        LinkNla::IfAlias(alias) => Some(alias.clone()),
+        _ => None,
+    })
+}
+
+/// Sets the comment of a nft rule, the comment is stored in the
+/// rule userdata as a NFTNL_UDATA_RULE_COMMENT TLV
+fn set_rule_comment(rule: &mut Rule, comment: &str) -> FResult<()> {
+    const NFTNL_UDATA_RULE_COMMENT: u8 = 0;
+    let comment = CString::new(comment).map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+    let value = comment.as_bytes_with_nul();
+    if value.len() > u8::MAX as usize {
+        return Err(FError::NetworkingError(format!(
+            "Comment too long: {:?}",
+            comment
+        )));
+    }
+    let mut udata = vec![NFTNL_UDATA_RULE_COMMENT, value.len() as u8];
+    udata.extend_from_slice(value);
+    unsafe {
+        nftnl::nftnl_sys::nftnl_rule_set_data(
+            rule.as_mut_ptr(),
+            nftnl::nftnl_sys::NFTNL_RULE_USERDATA as u16,
+            udata.as_ptr() as *const libc::c_void,
+            udata.len() as u32,
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,847 @@
+/*********************************************************************************
+* Copyright (c) 2018,2020 ADLINK Technology Inc.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0, or the Apache Software License 2.0
+* which is available at https://www.apache.org/licenses/LICENSE-2.0.
+*
+* SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+* Contributors:
+*   ADLINK fog05 team, <fog05@adlink-labs.tech>
+*********************************************************************************/
+
+//! In-memory netlink backend, it keeps links, addresses, network namespaces
+//! and nftables tables in memory so the plugin logic can be exercised
+//! without root privileges. Failures can be injected on any operation.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use futures::stream::{BoxStream, StreamExt};
+
+use fog05_sdk::fresult::{FError, FResult};
+use fog05_sdk::types::IPAddress;
+
+use ipnetwork::IpNetwork;
+
+use uuid::Uuid;
+
+use crate::netlink::{NetlinkBackend, NetlinkEvent};
+use crate::types::{GRETunnelKind, MACVLANMode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FakeLinkKind {
+    Loopback,
+    Bridge,
+    Veth { peer: String },
+    VLAN { dev: String, tag: u16 },
+    MACVLAN { dev: String, mode: MACVLANMode },
+    VXLAN { dev: String, vni: u32, port: u16 },
+    GRE { kind: GRETunnelKind },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeLink {
+    pub index: u32,
+    pub name: String,
+    pub kind: FakeLinkKind,
+    /// None is the default namespace
+    pub netns: Option<String>,
+    pub master: Option<String>,
+    pub up: bool,
+    pub mac: Vec<u8>,
+    pub alias: Option<String>,
+    pub addresses: Vec<(IPAddress, u8)>,
+    /// Destinations of the all-zeros FDB entries
+    pub fdb: Vec<IPAddress>,
+}
+
+impl FakeLink {
+    /// RTM_NEWLINK notification of the link
+    fn added_event(&self) -> NetlinkEvent {
+        NetlinkEvent::LinkAdded {
+            name: self.name.clone(),
+            alias: self.alias.clone(),
+            up: self.up,
+        }
+    }
+
+    /// RTM_DELLINK notification of the link
+    fn removed_event(&self) -> NetlinkEvent {
+        NetlinkEvent::LinkRemoved {
+            name: self.name.clone(),
+            alias: self.alias.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FakeNATTable {
+    pub owner: Uuid,
+    pub nets: Vec<IpNetwork>,
+    pub iface: String,
+}
+
+#[derive(Default)]
+struct FakeState {
+    links: Vec<FakeLink>,
+    netns: Vec<String>,
+    nat_tables: HashMap<String, FakeNATTable>,
+    next_index: u32,
+    /// operation name -> successful calls left before failing
+    failures: HashMap<String, usize>,
+    subscribers: Vec<(Option<String>, UnboundedSender<NetlinkEvent>)>,
+}
+
+impl FakeState {
+    fn check_failure(&mut self, op: &str) -> FResult<()> {
+        match self.failures.get_mut(op) {
+            Some(0) => {
+                self.failures.remove(op);
+                Err(FError::NetworkingError(format!(
+                    "Injected failure on {}",
+                    op
+                )))
+            }
+            Some(n) => {
+                *n -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn position(&self, netns: &Option<String>, name: &str) -> Option<usize> {
+        self.links
+            .iter()
+            .position(|l| l.netns == *netns && l.name == name)
+    }
+
+    fn link_mut(&mut self, netns: &Option<String>, name: &str) -> FResult<&mut FakeLink> {
+        let pos = self.position(netns, name).ok_or(FError::NotFound)?;
+        Ok(&mut self.links[pos])
+    }
+
+    fn add_link(
+        &mut self,
+        netns: &Option<String>,
+        name: String,
+        kind: FakeLinkKind,
+    ) -> FResult<()> {
+        if self.position(netns, &name).is_some() {
+            return Err(FError::AlreadyPresent);
+        }
+        self.next_index += 1;
+        let index = self.next_index;
+        let link = FakeLink {
+            index,
+            name,
+            kind,
+            netns: netns.clone(),
+            master: None,
+            up: false,
+            mac: vec![0x02, 0, 0, 0, (index >> 8) as u8, index as u8],
+            alias: None,
+            addresses: Vec::new(),
+            fdb: Vec::new(),
+        };
+        let event = link.added_event();
+        self.links.push(link);
+        self.notify(netns, event);
+        Ok(())
+    }
+
+    fn remove_link(&mut self, pos: usize) {
+        let link = self.links.remove(pos);
+        for other in self.links.iter_mut() {
+            if other.netns == link.netns && other.master.as_ref() == Some(&link.name) {
+                other.master = None;
+            }
+        }
+        self.notify(&link.netns, link.removed_event());
+        // removing one end of a veth pair removes the other one
+        if let FakeLinkKind::Veth { peer } = link.kind {
+            if let Some(pos) = self.links.iter().position(
+                |l| matches!(&l.kind, FakeLinkKind::Veth { peer: p } if *p == link.name && l.name == peer),
+            ) {
+                self.remove_link(pos);
+            }
+        }
+    }
+
+    fn notify(&mut self, netns: &Option<String>, event: NetlinkEvent) {
+        self.subscribers
+            .retain(|(ns, tx)| ns != netns || tx.unbounded_send(event.clone()).is_ok());
+    }
+}
+
+/// In-memory implementation of [`NetlinkBackend`].
+/// Clones and the backends returned by [`FakeNetlinkBackend::in_netns`]
+/// share the same state.
+#[derive(Clone, Default)]
+pub struct FakeNetlinkBackend {
+    state: Arc<Mutex<FakeState>>,
+    netns: Option<String>,
+}
+
+impl FakeNetlinkBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a backend acting inside the given network namespace,
+    /// as the one used by a namespace manager
+    pub fn in_netns(&self, netns: &str) -> Self {
+        Self {
+            state: self.state.clone(),
+            netns: Some(netns.to_string()),
+        }
+    }
+
+    /// Makes the call to `op` fail after `skip` successful calls,
+    /// `op` is the name of the [`NetlinkBackend`] method
+    pub fn inject_failure(&self, op: &str, skip: usize) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .insert(op.to_string(), skip);
+    }
+
+    /// Returns the links in the namespace of this backend
+    pub fn links(&self) -> Vec<FakeLink> {
+        let state = self.state.lock().unwrap();
+        state
+            .links
+            .iter()
+            .filter(|l| l.netns == self.netns)
+            .cloned()
+            .collect()
+    }
+
+    pub fn link(&self, name: &str) -> Option<FakeLink> {
+        self.links().into_iter().find(|l| l.name == name)
+    }
+
+    pub fn namespaces(&self) -> Vec<String> {
+        self.state.lock().unwrap().netns.clone()
+    }
+
+    pub fn nat_tables(&self) -> HashMap<String, FakeNATTable> {
+        self.state.lock().unwrap().nat_tables.clone()
+    }
+
+    /// Checks the injected failures and runs `f` on the state
+    fn with_state<T>(&self, op: &str, f: impl FnOnce(&mut FakeState) -> FResult<T>) -> FResult<T> {
+        let mut state = self.state.lock().unwrap();
+        state.check_failure(op)?;
+        f(&mut state)
+    }
+
+    fn create_on_dev(&self, op: &str, iface: String, dev: &str, kind: FakeLinkKind) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state(op, |state| {
+            state.position(&netns, dev).ok_or(FError::NotFound)?;
+            state.add_link(&netns, iface, kind)
+        })
+    }
+}
+
+#[async_trait]
+impl NetlinkBackend for FakeNetlinkBackend {
+    async fn add_netns(&self, ns_name: String) -> FResult<()> {
+        self.with_state("add_netns", |state| {
+            if state.netns.contains(&ns_name) {
+                return Err(FError::AlreadyPresent);
+            }
+            state.netns.push(ns_name.clone());
+            // as the kernel does, each namespace has its own loopback
+            state.add_link(&Some(ns_name), "lo".to_string(), FakeLinkKind::Loopback)
+        })
+    }
+
+    async fn del_netns(&self, ns_name: String) -> FResult<()> {
+        self.with_state("del_netns", |state| {
+            let pos = state
+                .netns
+                .iter()
+                .position(|ns| *ns == ns_name)
+                .ok_or(FError::NotFound)?;
+            state.netns.remove(pos);
+            let netns = Some(ns_name);
+            while let Some(pos) = state.links.iter().position(|l| l.netns == netns) {
+                state.remove_link(pos);
+            }
+            Ok(())
+        })
+    }
+
+    async fn list_netns(&self) -> FResult<Vec<String>> {
+        self.with_state("list_netns", |state| Ok(state.netns.clone()))
+    }
+
+    async fn create_bridge(&self, br_name: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("create_bridge", |state| {
+            state.add_link(&netns, br_name, FakeLinkKind::Bridge)
+        })
+    }
+
+    async fn create_veth(&self, iface_i: String, iface_e: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("create_veth", |state| {
+            if state.position(&netns, &iface_e).is_some() {
+                return Err(FError::AlreadyPresent);
+            }
+            state.add_link(
+                &netns,
+                iface_i.clone(),
+                FakeLinkKind::Veth {
+                    peer: iface_e.clone(),
+                },
+            )?;
+            state.add_link(&netns, iface_e, FakeLinkKind::Veth { peer: iface_i })
+        })
+    }
+
+    async fn create_vlan(&self, iface: String, dev: String, tag: u16) -> FResult<()> {
+        let kind = FakeLinkKind::VLAN {
+            dev: dev.clone(),
+            tag,
+        };
+        self.create_on_dev("create_vlan", iface, &dev, kind)
+    }
+
+    async fn create_macvlan(&self, iface: String, dev: String, mode: MACVLANMode) -> FResult<()> {
+        let kind = FakeLinkKind::MACVLAN {
+            dev: dev.clone(),
+            mode,
+        };
+        self.create_on_dev("create_macvlan", iface, &dev, kind)
+    }
+
+    async fn create_mcast_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        _mcast_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()> {
+        let kind = FakeLinkKind::VXLAN {
+            dev: dev.clone(),
+            vni,
+            port,
+        };
+        self.create_on_dev("create_mcast_vxlan", iface, &dev, kind)
+    }
+
+    async fn create_ptp_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        _local_addr: IPAddress,
+        _remote_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()> {
+        let kind = FakeLinkKind::VXLAN {
+            dev: dev.clone(),
+            vni,
+            port,
+        };
+        self.create_on_dev("create_ptp_vxlan", iface, &dev, kind)
+    }
+
+    async fn create_gre(
+        &self,
+        iface: String,
+        kind: GRETunnelKind,
+        _local_addr: IPAddress,
+        _remote_addr: IPAddress,
+        _ttl: u8,
+    ) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("create_gre", |state| {
+            state.add_link(&netns, iface, FakeLinkKind::GRE { kind })
+        })
+    }
+
+    async fn del_iface(&self, iface: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("del_iface", |state| {
+            let pos = state.position(&netns, &iface).ok_or(FError::NotFound)?;
+            state.remove_link(pos);
+            Ok(())
+        })
+    }
+
+    async fn set_iface_master(&self, iface: String, master: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("set_iface_master", |state| {
+            let pos = state.position(&netns, &master).ok_or(FError::NotFound)?;
+            if state.links[pos].kind != FakeLinkKind::Bridge {
+                return Err(FError::WrongKind);
+            }
+            state.link_mut(&netns, &iface)?.master = Some(master);
+            Ok(())
+        })
+    }
+
+    async fn del_iface_master(&self, iface: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("del_iface_master", |state| {
+            state.link_mut(&netns, &iface)?.master = None;
+            Ok(())
+        })
+    }
+
+    async fn add_iface_address(&self, iface: String, addr: IPAddress, prefix: u8) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("add_iface_address", |state| {
+            let link = state.link_mut(&netns, &iface)?;
+            if link.addresses.iter().any(|(a, _)| *a == addr) {
+                return Err(FError::AlreadyPresent);
+            }
+            link.addresses.push((addr, prefix));
+            let index = link.index;
+            state.notify(&netns, NetlinkEvent::AddressChanged(index));
+            Ok(())
+        })
+    }
+
+    async fn del_iface_address(&self, iface: String, addr: IPAddress) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("del_iface_address", |state| {
+            let link = state.link_mut(&netns, &iface)?;
+            let pos = link
+                .addresses
+                .iter()
+                .position(|(a, _)| *a == addr)
+                .ok_or(FError::NotFound)?;
+            link.addresses.remove(pos);
+            let index = link.index;
+            state.notify(&netns, NetlinkEvent::AddressChanged(index));
+            Ok(())
+        })
+    }
+
+    async fn get_iface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>> {
+        let netns = self.netns.clone();
+        self.with_state("get_iface_addresses", |state| {
+            let link = state.link_mut(&netns, &iface)?;
+            Ok(link.addresses.iter().map(|(a, _)| *a).collect())
+        })
+    }
+
+    async fn get_iface_name(&self, index: u32) -> FResult<String> {
+        let netns = self.netns.clone();
+        self.with_state("get_iface_name", |state| {
+            state
+                .links
+                .iter()
+                .find(|l| l.netns == netns && l.index == index)
+                .map(|l| l.name.clone())
+                .ok_or(FError::NotFound)
+        })
+    }
+
+    async fn set_iface_name(&self, iface: String, new_name: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("set_iface_name", |state| {
+            if state.position(&netns, &new_name).is_some() {
+                return Err(FError::AlreadyPresent);
+            }
+            state.link_mut(&netns, &iface)?.name = new_name;
+            Ok(())
+        })
+    }
+
+    async fn set_iface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("set_iface_mac", |state| {
+            if address.len() != 6 {
+                return Err(FError::NetworkingError(format!(
+                    "Invalid MAC address {:?}",
+                    address
+                )));
+            }
+            state.link_mut(&netns, &iface)?.mac = address;
+            Ok(())
+        })
+    }
+
+    async fn set_iface_alias(&self, iface: String, alias: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("set_iface_alias", |state| {
+            let link = state.link_mut(&netns, &iface)?;
+            link.alias = Some(alias);
+            let event = link.added_event();
+            state.notify(&netns, event);
+            Ok(())
+        })
+    }
+
+    async fn get_iface_alias(&self, iface: String) -> FResult<Option<String>> {
+        let netns = self.netns.clone();
+        self.with_state("get_iface_alias", |state| {
+            Ok(state.link_mut(&netns, &iface)?.alias.clone())
+        })
+    }
+
+    async fn set_iface_ns(&self, iface: String, netns: String) -> FResult<()> {
+        let current = self.netns.clone();
+        self.with_state("set_iface_ns", |state| {
+            if !state.netns.contains(&netns) {
+                return Err(FError::NotFound);
+            }
+            let target = Some(netns);
+            if state.position(&target, &iface).is_some() {
+                return Err(FError::AlreadyPresent);
+            }
+            let link = state.link_mut(&current, &iface)?;
+            let removed = link.removed_event();
+            // as the kernel does, the link is detached and set down
+            link.netns = target.clone();
+            link.master = None;
+            link.up = false;
+            let added = link.added_event();
+            state.notify(&current, removed);
+            state.notify(&target, added);
+            Ok(())
+        })
+    }
+
+    async fn set_iface_default_ns(&self, iface: String) -> FResult<()> {
+        let current = self.netns.clone();
+        self.with_state("set_iface_default_ns", |state| {
+            if state.position(&None, &iface).is_some() {
+                return Err(FError::AlreadyPresent);
+            }
+            let link = state.link_mut(&current, &iface)?;
+            let removed = link.removed_event();
+            link.netns = None;
+            link.master = None;
+            link.up = false;
+            let added = link.added_event();
+            state.notify(&current, removed);
+            state.notify(&None, added);
+            Ok(())
+        })
+    }
+
+    async fn set_iface_up(&self, iface: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("set_iface_up", |state| {
+            let link = state.link_mut(&netns, &iface)?;
+            link.up = true;
+            let event = link.added_event();
+            state.notify(&netns, event);
+            Ok(())
+        })
+    }
+
+    async fn set_iface_down(&self, iface: String) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("set_iface_down", |state| {
+            let link = state.link_mut(&netns, &iface)?;
+            link.up = false;
+            let event = link.added_event();
+            state.notify(&netns, event);
+            Ok(())
+        })
+    }
+
+    async fn iface_exists(&self, iface: String) -> FResult<bool> {
+        let netns = self.netns.clone();
+        self.with_state("iface_exists", |state| {
+            Ok(state.position(&netns, &iface).is_some())
+        })
+    }
+
+    async fn dump_links(&self) -> FResult<Vec<String>> {
+        let netns = self.netns.clone();
+        self.with_state("dump_links", |state| {
+            Ok(state
+                .links
+                .iter()
+                .filter(|l| l.netns == netns)
+                .map(|l| l.name.clone())
+                .collect())
+        })
+    }
+
+    async fn append_fdb_entry(&self, iface: String, dst: IPAddress) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("append_fdb_entry", |state| {
+            let link = state.link_mut(&netns, &iface)?;
+            if !matches!(link.kind, FakeLinkKind::VXLAN { .. }) {
+                return Err(FError::WrongKind);
+            }
+            if link.fdb.contains(&dst) {
+                return Err(FError::AlreadyPresent);
+            }
+            link.fdb.push(dst);
+            Ok(())
+        })
+    }
+
+    async fn del_fdb_entry(&self, iface: String, dst: IPAddress) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("del_fdb_entry", |state| {
+            let link = state.link_mut(&netns, &iface)?;
+            let pos = link
+                .fdb
+                .iter()
+                .position(|d| *d == dst)
+                .ok_or(FError::NotFound)?;
+            link.fdb.remove(pos);
+            Ok(())
+        })
+    }
+
+    async fn subscribe(&self) -> FResult<BoxStream<'static, NetlinkEvent>> {
+        let netns = self.netns.clone();
+        self.with_state("subscribe", |state| {
+            let (tx, rx) = unbounded();
+            state.subscribers.push((netns, tx));
+            Ok(rx.boxed())
+        })
+    }
+
+    async fn add_nat_table(
+        &self,
+        table_name: String,
+        owner: Uuid,
+        nets: Vec<IpNetwork>,
+        iface: String,
+    ) -> FResult<()> {
+        let netns = self.netns.clone();
+        self.with_state("add_nat_table", |state| {
+            if state.nat_tables.contains_key(&table_name) {
+                return Err(FError::AlreadyPresent);
+            }
+            state.position(&netns, &iface).ok_or(FError::NotFound)?;
+            state
+                .nat_tables
+                .insert(table_name, FakeNATTable { owner, nets, iface });
+            Ok(())
+        })
+    }
+
+    async fn del_nat_table(&self, table_name: String) -> FResult<()> {
+        self.with_state("del_nat_table", |state| {
+            state
+                .nat_tables
+                .remove(&table_name)
+                .map(|_| ())
+                .ok_or(FError::NotFound)
+        })
+    }
+
+    async fn list_nat_tables(&self) -> FResult<Vec<String>> {
+        self.with_state("list_nat_tables", |state| {
+            Ok(state.nat_tables.keys().cloned().collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlink::{owner_tag, parse_owner_tag};
+
+    fn addr(a: &str) -> IPAddress {
+        a.parse().unwrap()
+    }
+
+    #[async_std::test]
+    async fn bridge_and_vxlan() {
+        let nl = FakeNetlinkBackend::new();
+        nl.create_bridge("br0".to_string()).await.unwrap();
+        assert!(matches!(
+            nl.create_mcast_vxlan("vxl0".into(), "eth0".into(), 10, addr("239.0.0.1"), 4789)
+                .await,
+            Err(FError::NotFound)
+        ));
+
+        nl.create_bridge("eth0".to_string()).await.unwrap();
+        nl.create_mcast_vxlan("vxl0".into(), "eth0".into(), 10, addr("239.0.0.1"), 4789)
+            .await
+            .unwrap();
+        nl.set_iface_master("vxl0".into(), "br0".into())
+            .await
+            .unwrap();
+        nl.set_iface_up("vxl0".into()).await.unwrap();
+
+        let vxl = nl.link("vxl0").unwrap();
+        assert_eq!(vxl.master, Some("br0".to_string()));
+        assert!(vxl.up);
+
+        nl.del_iface("br0".into()).await.unwrap();
+        assert_eq!(nl.link("vxl0").unwrap().master, None);
+        assert!(!nl.iface_exists("br0".into()).await.unwrap());
+    }
+
+    #[async_std::test]
+    async fn owner_aliases() {
+        let nl = FakeNetlinkBackend::new();
+        let owner = Uuid::new_v4();
+        nl.create_bridge("br0".to_string()).await.unwrap();
+        assert_eq!(nl.get_iface_alias("br0".into()).await.unwrap(), None);
+        nl.set_iface_alias("br0".into(), owner_tag(&owner))
+            .await
+            .unwrap();
+        let alias = nl.get_iface_alias("br0".into()).await.unwrap().unwrap();
+        assert_eq!(parse_owner_tag(&alias), Some(owner));
+        assert_eq!(parse_owner_tag("uplink"), None);
+    }
+
+    #[async_std::test]
+    async fn duplicated_names() {
+        let nl = FakeNetlinkBackend::new();
+        nl.create_bridge("br0".to_string()).await.unwrap();
+        assert!(matches!(
+            nl.create_bridge("br0".to_string()).await,
+            Err(FError::AlreadyPresent)
+        ));
+        assert!(matches!(
+            nl.create_veth("br0".into(), "veth0".into()).await,
+            Err(FError::AlreadyPresent)
+        ));
+        assert_eq!(nl.links().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn veth_across_namespaces() {
+        let nl = FakeNetlinkBackend::new();
+        nl.add_netns("ns-test".into()).await.unwrap();
+        nl.create_veth("veth-i".into(), "veth-e".into())
+            .await
+            .unwrap();
+        nl.set_iface_ns("veth-i".into(), "ns-test".into())
+            .await
+            .unwrap();
+
+        let ns = nl.in_netns("ns-test");
+        assert!(!nl.iface_exists("veth-i".into()).await.unwrap());
+        assert!(ns.iface_exists("veth-i".into()).await.unwrap());
+
+        ns.set_iface_default_ns("veth-i".into()).await.unwrap();
+        assert!(nl.iface_exists("veth-i".into()).await.unwrap());
+        nl.set_iface_ns("veth-i".into(), "ns-test".into())
+            .await
+            .unwrap();
+
+        // removing the namespace removes the links inside it and their peers
+        nl.del_netns("ns-test".into()).await.unwrap();
+        assert!(nl.links().is_empty());
+        assert!(nl.namespaces().is_empty());
+    }
+
+    #[async_std::test]
+    async fn addresses() {
+        let nl = FakeNetlinkBackend::new();
+        nl.create_bridge("br0".to_string()).await.unwrap();
+        nl.add_iface_address("br0".into(), addr("10.0.0.1"), 24)
+            .await
+            .unwrap();
+        nl.add_iface_address("br0".into(), addr("fd00::1"), 64)
+            .await
+            .unwrap();
+        assert_eq!(
+            nl.get_iface_addresses("br0".into()).await.unwrap(),
+            vec![addr("10.0.0.1"), addr("fd00::1")]
+        );
+        nl.del_iface_address("br0".into(), addr("10.0.0.1"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            nl.del_iface_address("br0".into(), addr("10.0.0.1")).await,
+            Err(FError::NotFound)
+        ));
+    }
+
+    #[async_std::test]
+    async fn injected_failures() {
+        let nl = FakeNetlinkBackend::new();
+        nl.inject_failure("create_bridge", 1);
+        nl.create_bridge("br0".to_string()).await.unwrap();
+        assert!(matches!(
+            nl.create_bridge("br1".to_string()).await,
+            Err(FError::NetworkingError(_))
+        ));
+        assert!(nl.link("br1").is_none());
+        // failures are one-shot
+        nl.create_bridge("br1".to_string()).await.unwrap();
+    }
+
+    #[async_std::test]
+    async fn nat_tables() {
+        let nl = FakeNetlinkBackend::new();
+        let net: IpNetwork = "10.240.0.0/16".parse().unwrap();
+        let owner = Uuid::new_v4();
+        assert!(matches!(
+            nl.add_nat_table("t0".into(), owner, vec![net], "eth0".into())
+                .await,
+            Err(FError::NotFound)
+        ));
+        nl.create_bridge("eth0".to_string()).await.unwrap();
+        nl.add_nat_table("t0".into(), owner, vec![net], "eth0".into())
+            .await
+            .unwrap();
+        assert_eq!(nl.nat_tables()["t0"].nets, vec![net]);
+        assert_eq!(nl.nat_tables()["t0"].owner, owner);
+        assert_eq!(
+            nl.list_nat_tables().await.unwrap(),
+            vec![String::from("t0")]
+        );
+        nl.del_nat_table("t0".into()).await.unwrap();
+        assert!(nl.nat_tables().is_empty());
+    }
+
+    #[async_std::test]
+    async fn events() {
+        let nl = FakeNetlinkBackend::new();
+        let mut events = nl.subscribe().await.unwrap();
+        nl.create_bridge("br0".to_string()).await.unwrap();
+        nl.add_iface_address("br0".into(), addr("10.0.0.1"), 24)
+            .await
+            .unwrap();
+        nl.set_iface_up("br0".into()).await.unwrap();
+        nl.del_iface("br0".into()).await.unwrap();
+
+        let index = nl.state.lock().unwrap().next_index;
+        assert_eq!(
+            events.next().await,
+            Some(NetlinkEvent::LinkAdded {
+                name: "br0".into(),
+                alias: None,
+                up: false
+            })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(NetlinkEvent::AddressChanged(index))
+        );
+        assert_eq!(
+            events.next().await,
+            Some(NetlinkEvent::LinkAdded {
+                name: "br0".into(),
+                alias: None,
+                up: true
+            })
+        );
+        assert_eq!(
+            events.next().await,
+            Some(NetlinkEvent::LinkRemoved {
+                name: "br0".into(),
+                alias: None
+            })
+        );
+    }
+}
@@ -14,16 +14,15 @@
 #![allow(clippy::too_many_arguments)]
 extern crate tera;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::convert::From;
 use std::error::Error;
 use std::ffi::{self, CString};
-use std::os::unix::io::IntoRawFd;
 use std::process::{Child, Command, Stdio};
 use std::time::Duration;
 
 use async_std::prelude::*;
-use async_std::sync::{Arc, RwLock};
+use async_std::sync::{Arc, Mutex, RwLock};
 use async_std::task;
 
 use log::{error, info, trace};
@@ -45,28 +44,35 @@ use fog05_sdk::types::{
 
 use uuid::Uuid;
 
-use futures::stream::TryStreamExt;
-
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
-
-use netlink_packet_route::rtnl::address::nlas::Nla;
-use rtnetlink::NetworkNamespace as NetlinkNetworkNamespace;
-use rtnetlink::{new_connection, Handle};
-
 use nix::sys::signal::{kill, Signal};
 use nix::unistd::Pid;
 
 use ipnetwork::IpNetwork;
 
-use nftnl::{nft_expr, nftnl_sys::libc, Batch, Chain, FinalizedBatch, ProtoFamily, Rule, Table};
-
 use tera::{Context, Result, Tera};
 
+use crate::dhcp::{
+    join_addresses, parse_leases, render_hosts, render_options, same_mac, validate_options,
+    validate_reservation,
+};
+use crate::errors::LinuxNetworkError;
+use crate::netlink::{
+    owner_table_name, owner_tag, parse_owner_table_name, parse_owner_tag, NetlinkBackend,
+    NetlinkEvent, RtNetlinkBackend,
+};
+use crate::services::{
+    DHCPLauncher, NSManagerLauncher, NSManagerOps, NetworkStore, ProcessDHCPLauncher,
+    ProcessNSManagerLauncher, ZConnectorStore,
+};
 use crate::types::{
-    deserialize_network_internals, serialize_network_internals, LinuxNetwork, LinuxNetworkConfig,
-    LinuxNetworkState, LinuxNetworkStateGuard, NamespaceManagerClient, VNetDHCP, VNetNetns,
-    VirtualNetworkInternals,
+    deserialize_network_internals, deserialize_network_settings, serialize_network_internals,
+    DHCPLease, DHCPOptions, DHCPProcess, DHCPReservation, GRETunnelKind, L2Transport, LinuxNetwork,
+    LinuxNetworkConfig, LinuxNetworkIntrospection, LinuxNetworkState, LinuxNetworkStateGuard,
+    MACVLANMode, NSManagerProcess, NamespaceManagerClient, Restarts, ShutdownPolicy,
+    SupervisionEvent, SupervisionEventKind, VNetDHCP, VNetDHCPSettings, VNetNetns, VNetTransport,
+    VirtualNetworkInternals, VirtualNetworkSettings, DEFAULT_DHCP_LEASE_TIME, DEFAULT_NAME_PREFIX,
+    DNSMASQ_STOP_TIMEOUT, MAX_NAME_PREFIX_LEN, MAX_SUPERVISION_EVENTS, NS_MANAGER_DEFAULT_TIMEOUT,
+    NS_MANAGER_READY, SUPERVISION_MAX_RESTARTS,
 };
 
 #[znserver]
@@ -89,297 +95,24 @@ impl NetworkingPlugin for LinuxNetwork {
             dhcp
         );
 
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let default_net_uuid = Uuid::nil();
-
-        let default_br_uuid = Uuid::nil();
-        let default_br_name = String::from("fosbr0");
-
-        let default_vxl_uuid = Uuid::new_v4();
-        let default_vxl_name = String::from("fosvxl0");
-
-        // let default_netns_uuid = Uuid::nil();
-        // let default_netns_name = String::from("fos-default");
-
-        // let default_veth_i_uuid = Uuid::new_v4();
-        // let default_veth_i_name = String::from("fveth-default-i");
-
-        // let default_veth_e_uuid = Uuid::new_v4();
-        // let default_veth_e_name = String::from("fveth-default-e");
-
-        let default_vni: u32 = 3845;
-        let default_mcast_addr = IPAddress::V4(std::net::Ipv4Addr::new(239, 15, 5, 0));
-        let default_port: u16 = 3845;
-
-        let dafault_ext_if_name = self.get_overlay_iface().await?;
-
-        let mut default_vnet = VirtualNetwork {
-            uuid: default_net_uuid,
-            id: String::from("fos-default"),
-            name: Some(String::from("Eclipse fog05 default virtual network")),
-            is_mgmt: false,
-            link_kind: LinkKind::L2(MCastVXLANInfo {
-                vni: default_vni,
-                mcast_addr: default_mcast_addr,
-                port: default_port,
-            }),
-            ip_version: IPVersion::IPV4,
-            ip_configuration: None,
-            connection_points: Vec::new(),
-            interfaces: vec![
-                default_br_uuid,
-                default_vxl_uuid,
-                // default_veth_i_uuid,
-                // default_veth_e_uuid,
-            ],
-            plugin_internals: None,
-        };
-
-        // let mut default_netns = NetworkNamespace {
-        //     uuid: default_netns_uuid,
-        //     ns_name: String::from("fos-default"),
-        //     interfaces: vec![default_veth_e_uuid],
-        // };
-
-        if dhcp {
-            let ip_conf = IPConfiguration {
-                subnet: Some((IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 0)), 16)),
-                gateway: Some(IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 1))),
-                dhcp_range: Some((
-                    IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 2)),
-                    IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 255, 254)),
-                )),
-                dns: Some(vec![
-                    IPAddress::V4(std::net::Ipv4Addr::new(208, 67, 222, 222)),
-                    // IPAddress::V4(std::net::Ipv4Addr::new(208, 67, 222, 220)),
-                ]),
-            };
-            default_vnet.ip_configuration = Some(ip_conf);
+        // held until the records are written, see LinuxNetwork::record_in_use
+        let lock = self.record_lock(Uuid::nil()).await;
+        let _guard = lock.lock().await;
+        let mut undo = Vec::new();
+        match self.default_vnet_create(dhcp, &mut undo).await {
+            Ok(default_vnet) => {
+                log::debug!(
+                    "leaving create_default_virtual_network with res: {:?}",
+                    default_vnet
+                );
+                Ok(default_vnet)
+            }
+            Err(e) => {
+                log::error!("Default virtual network creation failed: {}", e);
+                self.rollback(undo).await;
+                Err(e)
+            }
         }
-
-        let v_bridge = VirtualInterface {
-            uuid: default_br_uuid,
-            if_name: default_br_name.clone(),
-            net_ns: None,
-            parent: None,
-            kind: VirtualInterfaceKind::BRIDGE(BridgeKind {
-                childs: vec![default_vxl_uuid],
-            }),
-            addresses: Vec::new(),
-            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-        };
-
-        let res = self.create_bridge(default_br_name.clone()).await?;
-        log::trace!("Bridge creation res: {:?}", res);
-        self.set_iface_up(default_br_name.clone()).await?;
-
-        let v_vxl = VirtualInterface {
-            uuid: default_vxl_uuid,
-            if_name: default_vxl_name.clone(),
-            net_ns: None,
-            parent: Some(default_br_uuid),
-            kind: VirtualInterfaceKind::VXLAN(VXLANKind {
-                vni: default_vni,
-                mcast_addr: default_mcast_addr,
-                port: default_port,
-                dev: Interface {
-                    if_name: dafault_ext_if_name.clone(),
-                    kind: InterfaceKind::ETHERNET,
-                    addresses: Vec::new(),
-                    phy_address: None,
-                },
-            }),
-            addresses: Vec::new(),
-            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-        };
-
-        let res = self
-            .create_mcast_vxlan(
-                default_vxl_name.clone(),
-                dafault_ext_if_name.clone(),
-                default_vni,
-                default_mcast_addr,
-                default_port,
-            )
-            .await?;
-
-        log::trace!("VXLAN creation res: {:?}", res);
-        // Setting master for VXLAN interface and setting interface up
-        self.set_iface_master(default_vxl_name.clone(), default_br_name.clone())
-            .await?;
-        self.set_iface_up(default_vxl_name).await?;
-
-        // Adding address to bridge interface
-        self.add_iface_address(
-            default_br_name.clone(),
-            IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 1)),
-            16,
-        )
-        .await?;
-
-        // Creating dnsmasq config
-        let dhcp_internal = if dhcp {
-            let lease_file_path = self
-                .get_run_path()
-                .join("fosbr0.leases")
-                .to_str()
-                .ok_or(FError::EncodingError)?
-                .to_string();
-            let pid_file_path = self
-                .get_run_path()
-                .join("fosbr0.pid")
-                .to_str()
-                .ok_or(FError::EncodingError)?
-                .to_string();
-            let log_file_path = self
-                .get_run_path()
-                .join("fosbr0.log")
-                .to_str()
-                .ok_or(FError::EncodingError)?
-                .to_string();
-            let conf_file_path = self
-                .get_run_path()
-                .join("fosbr0.conf")
-                .to_str()
-                .ok_or(FError::EncodingError)?
-                .to_string();
-
-            let config = self
-                .create_dnsmasq_config(
-                    &default_br_name,
-                    &pid_file_path,
-                    &lease_file_path,
-                    &log_file_path,
-                    IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 2)),
-                    IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 255, 254)),
-                    IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 1)),
-                    IPAddress::V4(std::net::Ipv4Addr::new(208, 67, 222, 222)),
-                )
-                .await?;
-            log::trace!("dnsmasq config: {}", config);
-            self.os
-                .as_ref()
-                .unwrap()
-                .store_file(config.into_bytes(), conf_file_path.clone())
-                .await??;
-            let child = self.spawn_dnsmasq(conf_file_path.clone()).await?;
-            log::debug!("DHCP Process running PID: {}", child.id());
-            Some(VNetDHCP {
-                leases_file: lease_file_path,
-                pid_file: pid_file_path,
-                conf: conf_file_path,
-                log_file: log_file_path,
-            })
-        } else {
-            None
-        };
-
-        // let v_veth_i = VirtualInterface {
-        //     uuid: default_veth_i_uuid,
-        //     if_name: default_veth_i_name.clone(),
-        //     net_ns: None,
-        //     parent: Some(default_br_uuid),
-        //     kind: VirtualInterfaceKind::VETH(VETHKind {
-        //         pair: default_veth_e_uuid,
-        //         internal: true,
-        //     }),
-        //     addresses: Vec::new(),
-        //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-        // };
-
-        // let v_veth_e = VirtualInterface {
-        //     uuid: default_veth_e_uuid,
-        //     if_name: default_veth_e_name.clone(),
-        //     net_ns: Some(default_netns_uuid),
-        //     parent: None,
-        //     kind: VirtualInterfaceKind::VETH(VETHKind {
-        //         pair: default_veth_i_uuid,
-        //         internal: false,
-        //     }),
-        //     addresses: Vec::new(),
-        //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-        // };
-
-        // let res = self
-        //     .create_veth(default_veth_i_name.clone(), default_veth_e_name.clone())
-        //     .await?;
-        // log::trace!("VEth Pair creation res: {:?}", res);
-
-        // self.set_iface_master(default_veth_i_name.clone(), default_br_name.clone())
-        //     .await?;
-        // self.set_iface_up(default_veth_i_name).await?;
-
-        // let res = self.add_netns(default_netns_name.clone()).await?;
-        // log::trace!("Netns creation res: {:?}", res);
-
-        // Here we spawn the manager for the just created Namespace and
-        // we add it to the map of managers
-        // let mut guard = self.state.write().await;
-        // let child = Command::new("fos-net-linux-ns-manager")
-        //     .arg("--netns")
-        //     .arg(&default_netns_name)
-        //     .arg("--id")
-        //     .arg(format!("{}", default_netns_uuid))
-        //     .arg("--locator")
-        //     .arg("unixsock-stream//tmp/zenoh.sock")
-        //     .spawn()
-        //     .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-        // let ns_manager_client = NamespaceManagerClient::new(self.z.clone(), default_netns_uuid);
-        // guard
-        //     .ns_managers
-        //     .insert(default_netns_uuid, (child.id(), ns_manager_client));
-        // drop(guard);
-
-        // let res = self.set_iface_up(default_veth_e_name.clone()).await?;
-        // log::trace!("veth ext face up res: {:?}", res);
-        // let res = self
-        //     .set_iface_ns(default_veth_e_name.clone(), default_netns_name.clone())
-        //     .await?;
-        // log::trace!("veth ext netns set res: {:?}", res);
-
-        // Setting the firewall to NAT
-        // using nftables so add as dependencies:
-        // nftables libnftnl-dev libnfnetlink-dev libmnl-dev
-        // rule is similar to
-        // table ip nat { # handle 3
-        // 	chain postrouting { # handle 1
-        // 		type nat hook postrouting priority srcnat; policy accept;
-        // 		ip saddr 10.240.0.0/16 oif "eno0" masquerade # handle 4
-        // 	}
-        // }
-        let nat_table = self
-            .configure_nat(
-                IpNetwork::V4(
-                    ipnetwork::Ipv4Network::new(std::net::Ipv4Addr::new(10, 240, 0, 0), 16)
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))?,
-                ),
-                &self.get_overlay_face_from_config().await?.if_name,
-            )
-            .await?;
-
-        self.connector.local.add_interface(&v_bridge).await?;
-
-        self.connector.local.add_interface(&v_vxl).await?;
-
-        let internals = VirtualNetworkInternals {
-            // associated_netns_name: default_netns_name,
-            associated_netns: None,
-            dhcp: dhcp_internal,
-            associated_tables: vec![nat_table],
-        };
-
-        default_vnet.plugin_internals = Some(serialize_network_internals(&internals)?);
-
-        self.connector
-            .local
-            .add_virutal_network(&default_vnet)
-            .await?;
-
-        log::debug!(
-            "leaving create_default_virtual_network with res: {:?}",
-            default_vnet
-        );
-        Ok(default_vnet)
     }
 
     /// Creates the given virtual network in the current node.
@@ -431,17 +164,24 @@ impl NetworkingPlugin for LinuxNetwork {
     ///  +--------------------------------------+
     ///
     async fn create_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        match self.connector.global.get_virtual_network(vnet_uuid).await {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        match self.store.get_global_virtual_network(vnet_uuid).await {
             Ok(mut vnet) => {
-                if let Ok(net) = self.connector.local.get_virtual_network(vnet_uuid).await {
+                // held until the records are written, see LinuxNetwork::record_in_use
+                let lock = self.record_lock(vnet_uuid).await;
+                let _guard = lock.lock().await;
+                if let Ok(net) = self.store.get_virtual_network(vnet_uuid).await {
                     return Ok(net);
                 }
+                let settings = match &vnet.plugin_internals {
+                    Some(raw_settings) => deserialize_network_settings(raw_settings)?,
+                    None => VirtualNetworkSettings::default(),
+                };
                 match vnet.clone().link_kind {
                     LinkKind::L2(link_kind_info) => {
-                        //Multicast-based VxLAN
-                        let vnet = self.mcast_vxlan_create(vnet, link_kind_info).await?;
-                        self.connector.local.add_virutal_network(&vnet).await?;
+                        //VxLAN (multicast-based or unicast when the network has peers) or VLAN
+                        let transport = self.get_l2_transport(&link_kind_info, &settings).await?;
+                        let vnet = self.vnet_create(vnet, transport).await?;
                         Ok(vnet)
                     }
                     // Unimplemented for other virtual networks kinds
@@ -460,13 +200,13 @@ impl NetworkingPlugin for LinuxNetwork {
     }
 
     async fn get_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        self.connector.local.get_virtual_network(vnet_uuid).await
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        self.store.get_virtual_network(vnet_uuid).await
     }
 
     async fn delete_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        match self.connector.local.get_virtual_network(vnet_uuid).await {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        match self.store.get_virtual_network(vnet_uuid).await {
             Err(_) => Err(FError::NotFound),
             Ok(vnet) => {
                 // if !vnet.interfaces.is_empty() {
@@ -474,6 +214,12 @@ impl NetworkingPlugin for LinuxNetwork {
                 //         "Cannot remove virtual network that has attached interfaces".into(),
                 //     ));
                 // }
+                if !vnet.connection_points.is_empty() {
+                    return Err(FError::NetworkingError(
+                        "Cannot remove virtual network that has attached connection points".into(),
+                    ));
+                }
+
                 for i in &vnet.interfaces {
                     log::info!(
                         "Deleting virtual interface: {:?}",
@@ -481,313 +227,114 @@ impl NetworkingPlugin for LinuxNetwork {
                     );
                 }
 
-                if !vnet.connection_points.is_empty() {
-                    return Err(FError::NetworkingError(
-                        "Cannot remove virtual network that has attached connection points".into(),
-                    ));
-                }
-
                 if let Some(ref pl_net_info) = vnet.plugin_internals {
                     let net_info = deserialize_network_internals(pl_net_info)?;
+                    if let Some(dhcp_info) = net_info.dhcp {
+                        self.stop_vnet_dhcp(&dhcp_info).await?;
+                    }
                     if let Some(ns_info) = net_info.associated_netns {
                         self.delete_network_namespace(ns_info.ns_uuid).await?;
                     }
+                    for table in net_info.associated_tables {
+                        self.clean_nat(table).await?;
+                    }
                 }
 
-                self.connector
-                    .local
-                    .remove_virtual_network(vnet_uuid)
-                    .await?;
+                self.store.remove_virtual_network(vnet_uuid).await?;
                 Ok(vnet)
             }
         }
     }
 
+    /// Creates a connection point in the current node.
+    /// A connection point is made of:
+    /// 1 - A network namespace with its namespace manager
+    /// 2 - A bridge inside the namespace
+    /// 3 - A veth pair, the internal end is attached to the bridge
+    ///     inside the namespace, the external end stays in the default
+    ///     namespace and it is used to bind the connection point
+    ///     to a virtual network
     async fn create_connection_point(&self) -> FResult<ConnectionPoint> {
-        Err(FError::Unimplemented)
-        // let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        // let cp_uuid = Uuid::new_v4();
-        // match self
-        //     .connector
-        //     .local
-        //     .get_connection_point(cp_uuid)
-        //     .await
-        // {
-        //     Err(_) => {
-        //         let cp = ConnectionPoint {
-        //             uuid: cp_uuid,
-        //             net_ns: Uuid::new_v4(),
-        //             bridge: Uuid::new_v4(),
-        //             internal_veth: Uuid::new_v4(),
-        //             external_veth: Uuid::new_v4(),
-        //         };
-        //         self.connector
-        //             .local
-        //             .add_connection_point(&cp)
-        //             .await?;
-        //         Ok(cp)
-        //     }
-        //     Ok(_) => Err(FError::AlreadyPresent),
-        // }
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let mut undo = Vec::new();
+        match self.connection_point_create_steps(&mut undo).await {
+            Ok(cp) => {
+                log::debug!("Created connection point: {:?}", cp);
+                Ok(cp)
+            }
+            Err(e) => {
+                log::error!("Connection point creation failed: {}", e);
+                self.rollback(undo).await;
+                Err(e)
+            }
+        }
     }
 
     async fn get_connection_point(&self, cp_uuid: Uuid) -> FResult<ConnectionPoint> {
-        Err(FError::Unimplemented)
-        // let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        // self.connector
-        //     .local
-        //     .get_connection_point(cp_uuid)
-        //     .await
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        self.store.get_connection_point(cp_uuid).await
     }
 
     async fn delete_connection_point(&self, cp_uuid: Uuid) -> FResult<Uuid> {
-        Err(FError::Unimplemented)
-        // let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        // match self
-        //     .connector
-        //     .local
-        //     .get_connection_point(cp_uuid)
-        //     .await
-        // {
-        //     Err(_) => Err(FError::NotFound),
-        //     Ok(_) => {
-        //         self.connector
-        //             .local
-        //             .remove_connection_point(cp_uuid)
-        //             .await?;
-        //         Ok(cp_uuid)
-        //     }
-        // }
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        match self.store.get_connection_point(cp_uuid).await {
+            Err(_) => Err(FError::NotFound),
+            Ok(cp) => {
+                let external_veth = self.store.get_interface(cp.external_veth).await?;
+                if external_veth.parent.is_some() {
+                    return Err(FError::NetworkingError(
+                        "Cannot remove connection point bound to a virtual network".into(),
+                    ));
+                }
+
+                let netns = self.store.get_network_namespace(cp.net_ns).await?;
+                if netns
+                    .interfaces
+                    .iter()
+                    .any(|x| *x != cp.bridge && *x != cp.internal_veth)
+                {
+                    return Err(FError::NetworkingError(
+                        "Cannot remove connection point that has bound interfaces".into(),
+                    ));
+                }
+
+                // Removing the namespace destroys the bridge and the
+                // internal veth, the kernel removes the external end with it
+                self.delete_network_namespace(cp.net_ns).await?;
+
+                self.store.remove_interface(cp.bridge).await?;
+                self.store.remove_interface(cp.internal_veth).await?;
+                self.store.remove_interface(cp.external_veth).await?;
+                self.store.remove_connection_point(cp_uuid).await?;
+                Ok(cp_uuid)
+            }
+        }
     }
 
     async fn create_virtual_interface(
         &self,
         intf: VirtualInterfaceConfig,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        match intf.kind {
-            VirtualInterfaceConfigKind::VXLAN(conf) => {
-                let ext_face = self.get_overlay_face_from_config().await?;
-                let v_iface = VirtualInterface {
-                    uuid: Uuid::new_v4(),
-                    if_name: intf.if_name.clone(),
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::VXLAN(VXLANKind {
-                        vni: conf.vni,
-                        mcast_addr: conf.mcast_addr,
-                        port: conf.port,
-                        dev: ext_face.clone(),
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-
-                self.create_mcast_vxlan(
-                    intf.if_name,
-                    ext_face.if_name.clone(),
-                    conf.vni,
-                    conf.mcast_addr,
-                    conf.port,
-                )
-                .await?;
-
-                self.connector.local.add_interface(&v_iface).await?;
-                Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::BRIDGE => {
-                let v_iface = VirtualInterface {
-                    uuid: Uuid::new_v4(),
-                    if_name: intf.if_name.clone(),
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::BRIDGE(BridgeKind { childs: Vec::new() }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-
-                self.create_bridge(intf.if_name).await?;
-
-                self.connector.local.add_interface(&v_iface).await?;
-                Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::VETH => {
-                let external_face_name = self.generate_random_interface_name();
-                let internal_iface_uuid = Uuid::new_v4();
-                let external_iface_uuid = Uuid::new_v4();
-                let v_iface_internal = VirtualInterface {
-                    uuid: internal_iface_uuid,
-                    if_name: intf.if_name.clone(),
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::VETH(VETHKind {
-                        pair: external_iface_uuid,
-                        internal: true,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-                let v_iface_external = VirtualInterface {
-                    uuid: external_iface_uuid,
-                    if_name: external_face_name.clone(),
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::VETH(VETHKind {
-                        pair: internal_iface_uuid,
-                        internal: false,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-
-                self.create_veth(intf.if_name, external_face_name).await?;
-
-                self.connector
-                    .local
-                    .add_interface(&v_iface_internal)
-                    .await?;
-                self.connector
-                    .local
-                    .add_interface(&v_iface_external)
-                    .await?;
-                Ok(v_iface_internal)
-            }
-            VirtualInterfaceConfigKind::VLAN(conf) => {
-                let ext_face = self.get_dataplane_from_config().await?;
-                let v_iface = VirtualInterface {
-                    uuid: Uuid::new_v4(),
-                    if_name: intf.if_name.clone(),
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::VLAN(VLANKind {
-                        tag: conf.tag,
-                        dev: ext_face.clone(),
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-
-                self.create_vlan(intf.if_name, ext_face.if_name, conf.tag)
-                    .await?;
-
-                self.connector.local.add_interface(&v_iface).await?;
-                Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::MACVLAN => {
-                let v_iface = VirtualInterface {
-                    uuid: Uuid::new_v4(),
-                    if_name: intf.if_name,
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::MACVLAN(MACVLANKind {
-                        dev: self.get_dataplane_from_config().await?,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-                Err(FError::Unimplemented)
-                // self.connector
-                //.local
-                //.add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::GRE(conf) => {
-                let v_iface = VirtualInterface {
-                    uuid: Uuid::new_v4(),
-                    if_name: intf.if_name,
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::GRE(GREKind {
-                        local_addr: conf.local_addr,
-                        remote_addr: conf.remote_addr,
-                        ttl: conf.ttl,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-                Err(FError::Unimplemented)
-                // self.connector
-                //.local
-                //.add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::GRETAP(conf) => {
-                let v_iface = VirtualInterface {
-                    uuid: Uuid::new_v4(),
-                    if_name: intf.if_name,
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::GRETAP(GREKind {
-                        local_addr: conf.local_addr,
-                        remote_addr: conf.remote_addr,
-                        ttl: conf.ttl,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-                Err(FError::Unimplemented)
-                // self.connector
-                //.local
-                //.add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::IP6GRE(conf) => {
-                let v_iface = VirtualInterface {
-                    uuid: Uuid::new_v4(),
-                    if_name: intf.if_name,
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::IP6GRE(GREKind {
-                        local_addr: conf.local_addr,
-                        remote_addr: conf.remote_addr,
-                        ttl: conf.ttl,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-                Err(FError::Unimplemented)
-                // self.connector
-                //.local
-                //.add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::IP6GRETAP(conf) => {
-                let v_iface = VirtualInterface {
-                    uuid: Uuid::new_v4(),
-                    if_name: intf.if_name,
-                    net_ns: None,
-                    parent: None,
-                    kind: VirtualInterfaceKind::IP6GRETAP(GREKind {
-                        local_addr: conf.local_addr,
-                        remote_addr: conf.remote_addr,
-                        ttl: conf.ttl,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-                Err(FError::Unimplemented)
-                // self.connector
-                //.local
-                //.add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        self.check_interface_name(&intf.if_name).await?;
+        let mut ifaces = self.create_interface_links(intf, None).await?;
+        for iface in &ifaces {
+            self.store_new_interface(iface).await?;
         }
+        Ok(ifaces.remove(0))
     }
 
     async fn get_virtual_interface(&self, intf_uuid: Uuid) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        self.connector.local.get_interface(intf_uuid).await
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        self.store.get_interface(intf_uuid).await
     }
 
     async fn delete_virtual_interface(&self, intf_uuid: Uuid) -> FResult<VirtualInterface> {
         log::trace!("delete_virtual_interface({})", intf_uuid);
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        match self.connector.local.get_interface(intf_uuid).await {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        match self.store.get_interface(intf_uuid).await {
             Err(e) => {
                 log::error!("Unable to find interface {}, error: {}", intf_uuid, e);
                 Err(FError::NotFound)
@@ -796,7 +343,7 @@ impl NetworkingPlugin for LinuxNetwork {
                 log::error!("Delete Interface: {:?}", intf);
                 match intf.net_ns {
                     Some(ns_uuid) => {
-                        let netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                        let netns = self.store.get_network_namespace(ns_uuid).await?;
                         let ns_manager = self.get_ns_manager(&ns_uuid).await?;
                         let res = ns_manager.del_virtual_interface(intf.if_name.clone()).await;
                         log::info!(
@@ -804,7 +351,7 @@ impl NetworkingPlugin for LinuxNetwork {
                             intf.if_name.clone(),
                             res
                         );
-                        if let Err(e) = res? {
+                        if let Err(e) = res {
                             log::warn!(
                                 "Got error {} from namespace manager when removing {}",
                                 e,
@@ -813,7 +360,7 @@ impl NetworkingPlugin for LinuxNetwork {
                             if let VirtualInterfaceKind::VETH(VETHKind { pair, internal }) =
                                 intf.kind
                             {
-                                if let Err(e) = self.connector.local.get_interface(pair).await {
+                                if let Err(e) = self.store.get_interface(pair).await {
                                     log::warn!("Other end of veth pair was already removed: {}", e);
                                     return Ok(intf);
                                 }
@@ -824,23 +371,23 @@ impl NetworkingPlugin for LinuxNetwork {
                             }
                             return Err(e);
                         }
-                        self.connector.local.remove_interface(intf_uuid).await?;
+                        self.store.remove_interface(intf_uuid).await?;
                         Ok(intf)
                     }
                     None => {
                         if let VirtualInterfaceKind::VETH(ref info) = intf.kind {
-                            if let Ok(pair) = self.connector.local.get_interface(info.pair).await {
-                                self.del_iface(intf.if_name.clone()).await;
-                                self.del_iface(pair.if_name.clone()).await;
-                                self.connector.local.remove_interface(info.pair).await?;
+                            if let Ok(pair) = self.store.get_interface(info.pair).await {
+                                self.nl.del_iface(intf.if_name.clone()).await;
+                                self.nl.del_iface(pair.if_name.clone()).await;
+                                self.store.remove_interface(info.pair).await?;
                             } else {
                                 log::trace!("Peer was alredy removed...");
-                                self.del_iface(intf.if_name.clone()).await;
+                                self.nl.del_iface(intf.if_name.clone()).await;
                             }
                         } else {
-                            self.del_iface(intf.if_name.clone()).await?;
+                            self.nl.del_iface(intf.if_name.clone()).await?;
                         }
-                        self.connector.local.remove_interface(intf_uuid).await?;
+                        self.store.remove_interface(intf_uuid).await?;
                         Ok(intf)
                     }
                 }
@@ -849,7 +396,8 @@ impl NetworkingPlugin for LinuxNetwork {
     }
 
     async fn create_virtual_bridge(&self, br_name: String) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        self.check_interface_name(&br_name).await?;
         let v_iface = VirtualInterface {
             uuid: Uuid::new_v4(),
             if_name: br_name,
@@ -860,15 +408,15 @@ impl NetworkingPlugin for LinuxNetwork {
             phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
         };
 
-        self.create_bridge(v_iface.if_name.clone()).await?;
+        self.nl.create_bridge(v_iface.if_name.clone()).await?;
 
-        self.connector.local.add_interface(&v_iface).await?;
+        self.store_new_interface(&v_iface).await?;
         Ok(v_iface)
     }
 
     async fn get_virtual_bridge(&self, br_uuid: Uuid) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        match self.connector.local.get_interface(br_uuid).await {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        match self.store.get_interface(br_uuid).await {
             Err(err) => Err(err),
             Ok(i) => match i.kind {
                 VirtualInterfaceKind::BRIDGE(_) => Ok(i),
@@ -878,23 +426,23 @@ impl NetworkingPlugin for LinuxNetwork {
     }
 
     async fn delete_virtual_bridge(&self, br_uuid: Uuid) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        match self.connector.local.get_interface(br_uuid).await {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(br_uuid).await;
+        let _guard = lock.lock().await;
+        match self.store.get_interface(br_uuid).await {
             Err(err) => Err(err),
             Ok(i) => match i.net_ns {
                 Some(ns_uuid) => {
-                    let netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                    let netns = self.store.get_network_namespace(ns_uuid).await?;
                     let ns_manager = self.get_ns_manager(&ns_uuid).await?;
-                    ns_manager
-                        .del_virtual_interface(i.if_name.clone())
-                        .await??;
-                    self.connector.local.remove_interface(br_uuid).await?;
+                    ns_manager.del_virtual_interface(i.if_name.clone()).await?;
+                    self.store.remove_interface(br_uuid).await?;
                     Ok(i)
                 }
                 None => match i.kind {
                     VirtualInterfaceKind::BRIDGE(_) => {
-                        self.del_iface(i.if_name.clone()).await?;
-                        self.connector.local.remove_interface(br_uuid).await?;
+                        self.nl.del_iface(i.if_name.clone()).await?;
+                        self.store.remove_interface(br_uuid).await?;
                         Ok(i)
                     }
                     _ => Err(FError::WrongKind),
@@ -904,45 +452,44 @@ impl NetworkingPlugin for LinuxNetwork {
     }
 
     async fn create_network_namespace(&self) -> FResult<NetworkNamespace> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let ns_name = self.generate_random_netns_name();
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let ns_uuid = Uuid::new_v4();
+        let ns_name = self.generate_netns_name(&ns_uuid).await?;
         let netns = NetworkNamespace {
-            uuid: Uuid::new_v4(),
+            uuid: ns_uuid,
             ns_name: ns_name.clone(),
             interfaces: Vec::new(),
         };
-        self.add_netns(ns_name.clone()).await?;
+        self.nl.add_netns(ns_name.clone()).await?;
 
-        self.spawn_ns_manager(ns_name.clone(), netns.uuid).await?;
+        if let Err(e) = self.spawn_ns_manager(ns_name.clone(), netns.uuid).await {
+            let _ = self.nl.del_netns(ns_name).await;
+            return Err(e);
+        }
         let ns_manager = self.get_ns_manager(&netns.uuid).await?;
 
-        while !ns_manager.verify_server().await? {}
-
         ns_manager
             .set_virtual_interface_up("lo".to_string())
-            .await??;
+            .await?;
 
-        self.connector.local.add_network_namespace(&netns).await?;
+        self.store.add_network_namespace(&netns).await?;
         Ok(netns)
     }
 
     async fn get_network_namespace(&self, ns_uuid: Uuid) -> FResult<NetworkNamespace> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        self.connector.local.get_network_namespace(ns_uuid).await
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        self.store.get_network_namespace(ns_uuid).await
     }
 
     async fn delete_network_namespace(&self, ns_uuid: Uuid) -> FResult<NetworkNamespace> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        match self.connector.local.get_network_namespace(ns_uuid).await {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        match self.store.get_network_namespace(ns_uuid).await {
             Err(_) => Err(FError::NotFound),
             Ok(netns) => {
-                self.del_netns(netns.ns_name.clone()).await?;
+                self.nl.del_netns(netns.ns_name.clone()).await?;
                 log::trace!("Taking guard to remove ns-manager");
                 self.kill_ns_manager(&netns.uuid).await?;
-                self.connector
-                    .local
-                    .remove_network_namespace(ns_uuid)
-                    .await?;
+                self.store.remove_network_namespace(ns_uuid).await?;
                 Ok(netns)
             }
         }
@@ -953,17 +500,22 @@ impl NetworkingPlugin for LinuxNetwork {
         intf_uuid: Uuid,
         cp_uuid: Uuid,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let cp = self.connector.local.get_connection_point(cp_uuid).await?;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
-
-        Err(FError::Unimplemented)
-        // iface.net_ns = Some(cp.net_ns);
-        // self.connector
-        //     .local
-        //     .add_interface(&iface)
-        //     .await?;
-        // Ok(iface)
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let cp = self.store.get_connection_point(cp_uuid).await?;
+        let iface = self.store.get_interface(intf_uuid).await?;
+
+        if iface.net_ns == Some(cp.net_ns) {
+            return Err(FError::AlreadyPresent);
+        }
+
+        // The interface loses its master when moved to another namespace
+        if iface.parent.is_some() {
+            self.detach_interface_from_bridge(intf_uuid).await?;
+        }
+
+        self.move_interface_info_namespace(intf_uuid, cp.net_ns)
+            .await?;
+        self.attach_interface_to_bridge(intf_uuid, cp.bridge).await
     }
 
     async fn unbind_interface_from_connection_point(
@@ -971,26 +523,19 @@ impl NetworkingPlugin for LinuxNetwork {
         intf_uuid: Uuid,
         cp_uuid: Uuid,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let cp = self.connector.local.get_connection_point(cp_uuid).await?;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
-
-        Err(FError::Unimplemented)
-
-        // match iface.net_ns {
-        //     Some(ns) => {
-        //         if ns == cp.net_ns {
-        //             iface.net_ns = None;
-        //             self.connector
-        //                 .loccal
-        //                 .add_interface(&iface)
-        //                 .await?;
-        //             return Ok(iface);
-        //         }
-        //         Err(FError::NotConnected)
-        //     }
-        //     None => Err(FError::NotConnected),
-        // }
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let cp = self.store.get_connection_point(cp_uuid).await?;
+        let iface = self.store.get_interface(intf_uuid).await?;
+
+        match iface.net_ns {
+            Some(ns) if ns == cp.net_ns => {
+                if iface.parent == Some(cp.bridge) {
+                    self.detach_interface_from_bridge(intf_uuid).await?;
+                }
+                self.move_interface_into_default_namespace(intf_uuid).await
+            }
+            _ => Err(FError::NotConnected),
+        }
     }
 
     async fn bind_connection_point_to_virtual_network(
@@ -998,16 +543,23 @@ impl NetworkingPlugin for LinuxNetwork {
         cp_uuid: Uuid,
         vnet_uuid: Uuid,
     ) -> FResult<ConnectionPoint> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let cp = self.connector.local.get_connection_point(cp_uuid).await?;
-        let mut vnet = self.connector.local.get_virtual_network(vnet_uuid).await?;
-        Err(FError::Unimplemented)
-        // vnet.connection_points.push(cp.uuid);
-        // self.connector
-        //     .local
-        //     .add_virutal_network(&vnet)
-        //     .await?;
-        // Ok(cp)
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(vnet_uuid).await;
+        let _guard = lock.lock().await;
+        let cp = self.store.get_connection_point(cp_uuid).await?;
+        let mut vnet = self.store.get_virtual_network(vnet_uuid).await?;
+
+        if vnet.connection_points.contains(&cp.uuid) {
+            return Err(FError::AlreadyPresent);
+        }
+
+        let bridge = self.get_virtual_network_bridge(&vnet).await?;
+        self.attach_interface_to_bridge(cp.external_veth, bridge.uuid)
+            .await?;
+
+        vnet.connection_points.push(cp.uuid);
+        self.store.add_virtual_network(&vnet).await?;
+        Ok(cp)
     }
 
     async fn unbind_connection_point_from_virtual_network(
@@ -1015,26 +567,25 @@ impl NetworkingPlugin for LinuxNetwork {
         cp_uuid: Uuid,
         vnet_uuid: Uuid,
     ) -> FResult<ConnectionPoint> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let cp = self.connector.local.get_connection_point(cp_uuid).await?;
-        let mut vnet = self.connector.local.get_virtual_network(vnet_uuid).await?;
-        Err(FError::Unimplemented)
-        // match vnet.connection_points.iter().position(|&x| x == cp.uuid) {
-        //     Some(p) => {
-        //         vnet.connection_points.remove(p);
-        //         self.connector
-        //             .local
-        //             .add_virutal_network(&vnet)
-        //             .await?;
-        //         Ok(cp)
-        //     }
-        //     None => Err(FError::NotConnected),
-        // }
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(vnet_uuid).await;
+        let _guard = lock.lock().await;
+        let cp = self.store.get_connection_point(cp_uuid).await?;
+        let mut vnet = self.store.get_virtual_network(vnet_uuid).await?;
+        match vnet.connection_points.iter().position(|&x| x == cp.uuid) {
+            Some(p) => {
+                self.detach_interface_from_bridge(cp.external_veth).await?;
+                vnet.connection_points.remove(p);
+                self.store.add_virtual_network(&vnet).await?;
+                Ok(cp)
+            }
+            None => Err(FError::NotConnected),
+        }
     }
 
     async fn get_interface_addresses(&self, intf_uuid: Uuid) -> FResult<Vec<IPAddress>> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let iface = self.store.get_interface(intf_uuid).await?;
         Ok(iface.addresses)
     }
 
@@ -1046,15 +597,16 @@ impl NetworkingPlugin for LinuxNetwork {
     }
 
     async fn create_macvlan_interface(&self, master_intf: String) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let iface_uuid = Uuid::new_v4();
         let v_iface = VirtualInterface {
-            uuid: Uuid::new_v4(),
-            if_name: self.generate_random_interface_name(),
+            uuid: iface_uuid,
+            if_name: self.generate_interface_name(&iface_uuid).await?,
             net_ns: None,
             parent: None,
             kind: VirtualInterfaceKind::MACVLAN(MACVLANKind {
                 dev: Interface {
-                    if_name: master_intf,
+                    if_name: master_intf.clone(),
                     kind: InterfaceKind::ETHERNET,
                     addresses: Vec::new(),
                     phy_address: None,
@@ -1063,32 +615,37 @@ impl NetworkingPlugin for LinuxNetwork {
             addresses: Vec::new(),
             phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
         };
-        Err(FError::Unimplemented)
-        // self.connector
-        //     .local
-        //     .add_interface(&v_iface)
-        //     .await?;
-        // Ok(v_iface)
+
+        self.nl
+            .create_macvlan(
+                v_iface.if_name.clone(),
+                master_intf,
+                self.get_macvlan_mode(),
+            )
+            .await?;
+
+        self.store_new_interface(&v_iface).await?;
+        Ok(v_iface)
     }
 
     async fn delete_macvan_interface(&self, intf_uuid: Uuid) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        match self.connector.local.get_interface(intf_uuid).await {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        match self.store.get_interface(intf_uuid).await {
             Err(err) => Err(err),
             Ok(i) => match i.net_ns {
                 Some(ns_uuid) => {
-                    let netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                    let netns = self.store.get_network_namespace(ns_uuid).await?;
                     let ns_manager = self.get_ns_manager(&ns_uuid).await?;
-                    ns_manager
-                        .del_virtual_interface(i.if_name.clone())
-                        .await??;
-                    self.connector.local.remove_interface(intf_uuid).await?;
+                    ns_manager.del_virtual_interface(i.if_name.clone()).await?;
+                    self.store.remove_interface(intf_uuid).await?;
                     Ok(i)
                 }
                 None => match i.kind {
                     VirtualInterfaceKind::MACVLAN(_) => {
-                        self.del_iface(i.if_name.clone()).await?;
-                        self.connector.local.remove_interface(intf_uuid).await?;
+                        self.nl.del_iface(i.if_name.clone()).await?;
+                        self.store.remove_interface(intf_uuid).await?;
                         Ok(i)
                     }
                     _ => Err(FError::WrongKind),
@@ -1102,50 +659,52 @@ impl NetworkingPlugin for LinuxNetwork {
         intf_uuid: Uuid,
         ns_uuid: Uuid,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        let mut iface = self.store.get_interface(intf_uuid).await?;
 
         match iface.net_ns {
             Some(old_ns_uuid) => {
-                let mut netns = self
-                    .connector
-                    .local
-                    .get_network_namespace(old_ns_uuid)
-                    .await?;
-                let mut newns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                let mut netns = self.store.get_network_namespace(old_ns_uuid).await?;
+                let mut newns = self.store.get_network_namespace(ns_uuid).await?;
 
                 match netns.interfaces.iter().position(|&x| x == intf_uuid) {
                     Some(p) => {
-                        let ns_manager = self.get_ns_manager(&ns_uuid).await?;
+                        // the interface goes through the default namespace
+                        let ns_manager = self.get_ns_manager(&old_ns_uuid).await?;
                         ns_manager
                             .move_virtual_interface_into_default_ns(iface.if_name.clone())
-                            .await??;
+                            .await?;
                         netns.interfaces.remove(p);
 
-                        self.set_iface_ns(iface.if_name.clone(), newns.ns_name.clone())
+                        self.nl
+                            .set_iface_ns(iface.if_name.clone(), newns.ns_name.clone())
                             .await?;
 
                         iface.net_ns = Some(newns.uuid);
                         newns.interfaces.push(iface.uuid);
 
-                        self.connector.local.add_interface(&iface).await?;
-                        self.connector.local.add_network_namespace(&netns).await?;
+                        self.store.add_interface(&iface).await?;
+                        self.store.add_network_namespace(&netns).await?;
+                        self.store.add_network_namespace(&newns).await?;
                         Ok(iface)
                     }
                     None => Err(FError::NotConnected),
                 }
             }
             None => {
-                let mut netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                let mut netns = self.store.get_network_namespace(ns_uuid).await?;
 
-                self.set_iface_ns(iface.if_name.clone(), netns.ns_name.clone())
+                self.nl
+                    .set_iface_ns(iface.if_name.clone(), netns.ns_name.clone())
                     .await?;
 
                 iface.net_ns = Some(netns.uuid);
                 netns.interfaces.push(iface.uuid);
 
-                self.connector.local.add_interface(&iface).await?;
-                self.connector.local.add_network_namespace(&netns).await?;
+                self.store.add_interface(&iface).await?;
+                self.store.add_network_namespace(&netns).await?;
                 Ok(iface)
             }
         }
@@ -1155,25 +714,23 @@ impl NetworkingPlugin for LinuxNetwork {
         &self,
         intf_uuid: Uuid,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        let mut iface = self.store.get_interface(intf_uuid).await?;
         match iface.net_ns {
             Some(netns_uuid) => {
-                let mut netns = self
-                    .connector
-                    .local
-                    .get_network_namespace(netns_uuid)
-                    .await?;
+                let mut netns = self.store.get_network_namespace(netns_uuid).await?;
                 let ns_manager = self.get_ns_manager(&netns_uuid).await?;
                 ns_manager
                     .move_virtual_interface_into_default_ns(iface.if_name.clone())
-                    .await??;
+                    .await?;
                 iface.net_ns = None;
-                self.connector.local.add_interface(&iface).await?;
+                self.store.add_interface(&iface).await?;
                 match netns.interfaces.iter().position(|&x| x == iface.uuid) {
                     Some(p) => {
                         netns.interfaces.remove(p);
-                        self.connector.local.add_network_namespace(&netns).await?;
+                        self.store.add_network_namespace(&netns).await?;
                         Ok(iface)
                     }
                     None => Err(FError::NotConnected),
@@ -1188,24 +745,27 @@ impl NetworkingPlugin for LinuxNetwork {
         intf_uuid: Uuid,
         intf_name: String,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        let mut iface = self.store.get_interface(intf_uuid).await?;
         match iface.net_ns {
             Some(ns_uuid) => {
-                let netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                let netns = self.store.get_network_namespace(ns_uuid).await?;
                 let ns_manager = self.get_ns_manager(&ns_uuid).await?;
                 ns_manager
                     .set_virtual_interface_name(iface.if_name.clone(), intf_name.clone())
-                    .await??;
+                    .await?;
                 iface.if_name = intf_name;
-                self.connector.local.add_interface(&iface).await?;
+                self.store.add_interface(&iface).await?;
                 Ok(iface)
             }
             None => {
-                self.set_iface_name(iface.if_name.clone(), intf_name.clone())
+                self.nl
+                    .set_iface_name(iface.if_name.clone(), intf_name.clone())
                     .await?;
                 iface.if_name = intf_name;
-                self.connector.local.add_interface(&iface).await?;
+                self.store.add_interface(&iface).await?;
                 Ok(iface)
             }
         }
@@ -1216,47 +776,55 @@ impl NetworkingPlugin for LinuxNetwork {
         intf_uuid: Uuid,
         br_uuid: Uuid,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
-        let bridge = self.connector.local.get_interface(br_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        if intf_uuid == br_uuid {
+            return Err(FError::WrongKind);
+        }
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        let br_lock = self.record_lock(br_uuid).await;
+        let _br_guard = br_lock.lock().await;
+        let mut iface = self.store.get_interface(intf_uuid).await?;
+        let bridge = self.store.get_interface(br_uuid).await?;
         match bridge.kind {
             VirtualInterfaceKind::BRIDGE(mut info) => match (iface.net_ns, bridge.net_ns) {
                 (Some(ns_uuid), Some(_)) => {
-                    let netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                    let netns = self.store.get_network_namespace(ns_uuid).await?;
                     let ns_manager = self.get_ns_manager(&ns_uuid).await?;
                     ns_manager
                         .set_virtual_interface_master(iface.if_name.clone(), bridge.if_name.clone())
-                        .await??;
+                        .await?;
 
                     iface.parent = Some(bridge.uuid);
                     info.childs.push(iface.uuid);
 
                     ns_manager
                         .set_virtual_interface_up(iface.if_name.clone())
-                        .await??;
+                        .await?;
 
-                    let mut new_bridge = self.connector.local.get_interface(br_uuid).await?;
+                    let mut new_bridge = self.store.get_interface(br_uuid).await?;
                     new_bridge.kind = VirtualInterfaceKind::BRIDGE(info);
-                    self.connector.local.add_interface(&iface).await?;
-                    self.connector.local.add_interface(&new_bridge).await?;
+                    self.store.add_interface(&iface).await?;
+                    self.store.add_interface(&new_bridge).await?;
                     Ok(iface)
                 }
                 (Some(_), None) | (None, Some(_)) => Err(FError::NetworkingError(String::from(
                     "Interface in different namespaces",
                 ))),
                 (None, None) => {
-                    self.set_iface_master(iface.if_name.clone(), bridge.if_name.clone())
+                    self.nl
+                        .set_iface_master(iface.if_name.clone(), bridge.if_name.clone())
                         .await?;
 
                     iface.parent = Some(bridge.uuid);
                     info.childs.push(iface.uuid);
 
-                    self.set_iface_up(iface.if_name.clone()).await?;
+                    self.nl.set_iface_up(iface.if_name.clone()).await?;
 
-                    let mut new_bridge = self.connector.local.get_interface(br_uuid).await?;
+                    let mut new_bridge = self.store.get_interface(br_uuid).await?;
                     new_bridge.kind = VirtualInterfaceKind::BRIDGE(info);
-                    self.connector.local.add_interface(&iface).await?;
-                    self.connector.local.add_interface(&new_bridge).await?;
+                    self.store.add_interface(&iface).await?;
+                    self.store.add_interface(&new_bridge).await?;
                     Ok(iface)
                 }
             },
@@ -1265,12 +833,16 @@ impl NetworkingPlugin for LinuxNetwork {
     }
 
     async fn detach_interface_from_bridge(&self, intf_uuid: Uuid) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        let mut iface = self.store.get_interface(intf_uuid).await?;
         match iface.parent {
             None => Err(FError::NotConnected),
             Some(br_uuid) => {
-                let bridge = self.connector.local.get_interface(br_uuid).await?;
+                let br_lock = self.record_lock(br_uuid).await;
+                let _br_guard = br_lock.lock().await;
+                let bridge = self.store.get_interface(br_uuid).await?;
                 match bridge.kind {
                     VirtualInterfaceKind::BRIDGE(mut info) => match iface.net_ns {
                         Some(ns_uuid) => {
@@ -1281,14 +853,13 @@ impl NetworkingPlugin for LinuxNetwork {
                             match info.childs.iter().position(|&x| x == iface.uuid) {
                                 Some(p) => {
                                     info.childs.remove(p);
-                                    let mut new_bridge =
-                                        self.connector.local.get_interface(br_uuid).await?;
+                                    let mut new_bridge = self.store.get_interface(br_uuid).await?;
                                     ns_manager
                                         .set_virtual_interface_nomaster(iface.if_name.clone())
-                                        .await??;
+                                        .await?;
                                     new_bridge.kind = VirtualInterfaceKind::BRIDGE(info);
-                                    self.connector.local.add_interface(&new_bridge).await?;
-                                    self.connector.local.add_interface(&iface).await?;
+                                    self.store.add_interface(&new_bridge).await?;
+                                    self.store.add_interface(&iface).await?;
                                     return Ok(iface);
                                 }
                                 None => return Err(FError::NotConnected),
@@ -1297,12 +868,11 @@ impl NetworkingPlugin for LinuxNetwork {
                         None => match info.childs.iter().position(|&x| x == iface.uuid) {
                             Some(p) => {
                                 info.childs.remove(p);
-                                let mut new_bridge =
-                                    self.connector.local.get_interface(br_uuid).await?;
-                                self.del_iface_master(iface.if_name.clone()).await?;
+                                let mut new_bridge = self.store.get_interface(br_uuid).await?;
+                                self.nl.del_iface_master(iface.if_name.clone()).await?;
                                 new_bridge.kind = VirtualInterfaceKind::BRIDGE(info);
-                                self.connector.local.add_interface(&new_bridge).await?;
-                                self.connector.local.add_interface(&iface).await?;
+                                self.store.add_interface(&new_bridge).await?;
+                                self.store.add_interface(&iface).await?;
                                 return Ok(iface);
                             }
                             None => return Err(FError::NotConnected),
@@ -1330,7 +900,7 @@ impl NetworkingPlugin for LinuxNetwork {
         //                             .global
         //                             .get_node_interface(node_uuid, br_uuid)
         //                             .await?;
-        //                         self.del_iface_master(iface.if_name.clone()).await?;
+        //                         self.nl.del_iface_master(iface.if_name.clone()).await?;
         //                         new_bridge.kind = VirtualInterfaceKind::BRIDGE(info);
         //                         self.connector
         //                             .global
@@ -1349,268 +919,29 @@ impl NetworkingPlugin for LinuxNetwork {
         // }
     }
 
+    /// Creates the given virtual interface inside the given namespace,
+    /// see [`LinuxNetwork::create_interface_links`]
     async fn create_virtual_interface_in_namespace(
         &self,
         intf: VirtualInterfaceConfig,
         ns_uuid: Uuid,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut netns = self.connector.local.get_network_namespace(ns_uuid).await?;
-        //Err(FError::Unimplemented)
-        match intf.kind {
-            VirtualInterfaceConfigKind::VXLAN(conf) => {
-                // let v_iface = VirtualInterface {
-                //     uuid: Uuid::new_v4(),
-                //     if_name: intf.if_name,
-                //     net_ns: Some(netns.uuid),
-                //     parent: None,
-                //     kind: VirtualInterfaceKind::VXLAN(VXLANKind {
-                //         vni: conf.vni,
-                //         mcast_addr: conf.mcast_addr,
-                //         port: conf.port,
-                //         dev: self.get_overlay_face_from_config().await?,
-                //     }),
-                //     addresses: Vec::new(),
-                //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                // };
-                // netns.interfaces.push(v_iface.uuid);
-                // self.connector
-                //     .local
-                //     .add_network_namespace(&netns)
-                //     .await?;
-                // self.connector
-                //     .local
-                //     .add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-                Err(FError::Unimplemented)
-            }
-            VirtualInterfaceConfigKind::BRIDGE => {
-                // let v_iface = VirtualInterface {
-                //     uuid: Uuid::new_v4(),
-                //     if_name: intf.if_name,
-                //     net_ns: Some(netns.uuid),
-                //     parent: None,
-                //     kind: VirtualInterfaceKind::BRIDGE(BridgeKind { childs: Vec::new() }),
-                //     addresses: Vec::new(),
-                //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                // };
-                // netns.interfaces.push(v_iface.uuid);
-                // self.connector
-                //     .local
-                //     .add_network_namespace(&netns)
-                //     .await?;
-                // self.connector
-                //     .local
-                //     .add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-                Err(FError::Unimplemented)
-            }
-            VirtualInterfaceConfigKind::VETH => {
-                let external_face_name = self.generate_random_interface_name();
-                let internal_iface_uuid = Uuid::new_v4();
-                let external_iface_uuid = Uuid::new_v4();
-                let v_iface_internal = VirtualInterface {
-                    uuid: internal_iface_uuid,
-                    if_name: intf.if_name,
-                    net_ns: Some(netns.uuid),
-                    parent: None,
-                    kind: VirtualInterfaceKind::VETH(VETHKind {
-                        pair: external_iface_uuid,
-                        internal: true,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-                let v_iface_external = VirtualInterface {
-                    uuid: external_iface_uuid,
-                    if_name: external_face_name.clone(),
-                    net_ns: Some(netns.uuid),
-                    parent: None,
-                    kind: VirtualInterfaceKind::VETH(VETHKind {
-                        pair: internal_iface_uuid,
-                        internal: false,
-                    }),
-                    addresses: Vec::new(),
-                    phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                };
-                let ns_manager = self.get_ns_manager(&ns_uuid).await?;
-
-                ns_manager
-                    .add_virtual_interface_veth(
-                        v_iface_internal.if_name.clone(),
-                        external_face_name.clone(),
-                    )
-                    .await??;
-
-                netns.interfaces.push(internal_iface_uuid);
-                netns.interfaces.push(external_iface_uuid);
-                self.connector.local.add_network_namespace(&netns).await?;
-                self.connector
-                    .local
-                    .add_interface(&v_iface_internal)
-                    .await?;
-                self.connector
-                    .local
-                    .add_interface(&v_iface_external)
-                    .await?;
-                Ok(v_iface_internal)
-            }
-            VirtualInterfaceConfigKind::VLAN(conf) => {
-                // let v_iface = VirtualInterface {
-                //     uuid: Uuid::new_v4(),
-                //     if_name: intf.if_name,
-                //     net_ns: Some(netns.uuid),
-                //     parent: None,
-                //     kind: VirtualInterfaceKind::VLAN(VLANKind {
-                //         tag: conf.tag,
-                //         dev: self.get_dataplane_from_config().await?,
-                //     }),
-                //     addresses: Vec::new(),
-                //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                // };
-                // netns.interfaces.push(v_iface.uuid);
-                // self.connector
-                //     .local
-                //     .add_network_namespace(&netns)
-                //     .await?;
-                // self.connector
-                //     .local
-                //     .add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-                Err(FError::Unimplemented)
-            }
-            VirtualInterfaceConfigKind::MACVLAN => {
-                // let v_iface = VirtualInterface {
-                //     uuid: Uuid::new_v4(),
-                //     if_name: intf.if_name,
-                //     net_ns: Some(netns.uuid),
-                //     parent: None,
-                //     kind: VirtualInterfaceKind::MACVLAN(MACVLANKind {
-                //         dev: self.get_dataplane_from_config().await?,
-                //     }),
-                //     addresses: Vec::new(),
-                //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                // };
-                // netns.interfaces.push(v_iface.uuid);
-                // self.connector
-                //     .local
-                //     .add_network_namespace(&netns)
-                //     .await?;
-                // self.connector
-                //     .local
-                //     .add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-                Err(FError::Unimplemented)
-            }
-            VirtualInterfaceConfigKind::GRE(conf) => {
-                Err(FError::Unimplemented)
-                // let v_iface = VirtualInterface {
-                //     uuid: Uuid::new_v4(),
-                //     if_name: intf.if_name,
-                //     net_ns: Some(netns.uuid),
-                //     parent: None,
-                //     kind: VirtualInterfaceKind::GRE(GREKind {
-                //         local_addr: conf.local_addr,
-                //         remote_addr: conf.remote_addr,
-                //         ttl: conf.ttl,
-                //     }),
-                //     addresses: Vec::new(),
-                //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                // };
-                // netns.interfaces.push(v_iface.uuid);
-                // self.connector
-                //     .local
-                //     .add_network_namespace(&netns)
-                //     .await?;
-                // self.connector
-                //     .local
-                //     .add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::GRETAP(conf) => {
-                Err(FError::Unimplemented)
-                // let v_iface = VirtualInterface {
-                //     uuid: Uuid::new_v4(),
-                //     if_name: intf.if_name,
-                //     net_ns: Some(netns.uuid),
-                //     parent: None,
-                //     kind: VirtualInterfaceKind::GRETAP(GREKind {
-                //         local_addr: conf.local_addr,
-                //         remote_addr: conf.remote_addr,
-                //         ttl: conf.ttl,
-                //     }),
-                //     addresses: Vec::new(),
-                //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                // };
-                // netns.interfaces.push(v_iface.uuid);
-                // self.connector
-                //     .local
-                //     .add_network_namespace(&netns)
-                //     .await?;
-                // self.connector
-                //     .local
-                //     .add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::IP6GRE(conf) => {
-                Err(FError::Unimplemented)
-                // let v_iface = VirtualInterface {
-                //     uuid: Uuid::new_v4(),
-                //     if_name: intf.if_name,
-                //     net_ns: Some(netns.uuid),
-                //     parent: None,
-                //     kind: VirtualInterfaceKind::IP6GRE(GREKind {
-                //         local_addr: conf.local_addr,
-                //         remote_addr: conf.remote_addr,
-                //         ttl: conf.ttl,
-                //     }),
-                //     addresses: Vec::new(),
-                //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                // };
-                // netns.interfaces.push(v_iface.uuid);
-                // self.connector
-                //     .local
-                //     .add_network_namespace(&netns)
-                //     .await?;
-                // self.connector
-                //     .local
-                //     .add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
-            VirtualInterfaceConfigKind::IP6GRETAP(conf) => {
-                Err(FError::Unimplemented)
-                // let v_iface = VirtualInterface {
-                //     uuid: Uuid::new_v4(),
-                //     if_name: intf.if_name,
-                //     net_ns: Some(netns.uuid),
-                //     parent: None,
-                //     kind: VirtualInterfaceKind::IP6GRETAP(GREKind {
-                //         local_addr: conf.local_addr,
-                //         remote_addr: conf.remote_addr,
-                //         ttl: conf.ttl,
-                //     }),
-                //     addresses: Vec::new(),
-                //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-                // };
-                // netns.interfaces.push(v_iface.uuid);
-                // self.connector
-                //     .local
-                //     .add_network_namespace(&netns)
-                //     .await?;
-                // self.connector
-                //     .local
-                //     .add_interface(&v_iface)
-                //     .await?;
-                // Ok(v_iface)
-            }
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let mut netns = self.store.get_network_namespace(ns_uuid).await?;
+        validate_iface_name(&intf.if_name)?;
+        if self
+            .get_ns_manager(&ns_uuid)
+            .await?
+            .check_virtual_interface_exists(intf.if_name.clone())
+            .await?
+        {
+            return Err(LinuxNetworkError::NameCollision { name: intf.if_name }.into());
         }
+        let mut ifaces = self.create_interface_links(intf, Some(&netns)).await?;
+        for iface in &ifaces {
+            self.add_interface_to_netns(&mut netns, iface).await?;
+        }
+        Ok(ifaces.remove(0))
     }
 
     async fn delete_virtual_interface_in_namespace(
@@ -1618,9 +949,9 @@ impl NetworkingPlugin for LinuxNetwork {
         intf_uuid: Uuid,
         ns_uuid: Uuid,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut netns = self.connector.local.get_network_namespace(ns_uuid).await?;
-        let iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let mut netns = self.store.get_network_namespace(ns_uuid).await?;
+        let iface = self.store.get_interface(intf_uuid).await?;
         match iface.net_ns {
             None => Err(FError::NotConnected),
             Some(nid) => {
@@ -1628,16 +959,18 @@ impl NetworkingPlugin for LinuxNetwork {
                     let ns_manager = self.get_ns_manager(&ns_uuid).await?;
                     ns_manager
                         .del_virtual_interface(iface.if_name.clone())
-                        .await??;
+                        .await?;
 
                     match netns.interfaces.iter().position(|&x| x == iface.uuid) {
                         Some(p) => {
                             netns.interfaces.remove(p);
                             if let VirtualInterfaceKind::VETH(ref info) = iface.kind {
-                                self.connector.local.remove_interface(info.pair).await?;
+                                // the kernel removes the peer together with the interface
+                                netns.interfaces.retain(|&x| x != info.pair);
+                                self.store.remove_interface(info.pair).await?;
                             }
-                            self.connector.local.add_network_namespace(&netns).await?;
-                            self.connector.local.remove_interface(intf_uuid).await?;
+                            self.store.add_network_namespace(&netns).await?;
+                            self.store.remove_interface(intf_uuid).await?;
                             return Ok(iface);
                         }
                         None => return Err(FError::NotConnected),
@@ -1654,25 +987,28 @@ impl NetworkingPlugin for LinuxNetwork {
         address: Option<IpNetwork>,
     ) -> FResult<VirtualInterface> {
         log::trace!("assing_address_to_interface {} {:?}", intf_uuid, address);
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        let mut iface = self.store.get_interface(intf_uuid).await?;
         match iface.net_ns {
             Some(ns_uuid) => {
-                let netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                let netns = self.store.get_network_namespace(ns_uuid).await?;
                 let ns_manager = self.get_ns_manager(&ns_uuid).await?;
                 let addresses = ns_manager
                     .add_virtual_interface_address(iface.if_name.clone(), address)
-                    .await??;
+                    .await?;
                 iface.addresses = addresses;
-                self.connector.local.add_interface(&iface).await?;
+                self.store.add_interface(&iface).await?;
                 Ok(iface)
             }
             None => match address {
                 Some(address) => {
-                    self.add_iface_address(iface.if_name.clone(), address.ip(), address.prefix())
+                    self.nl
+                        .add_iface_address(iface.if_name.clone(), address.ip(), address.prefix())
                         .await?;
                     iface.addresses.push(address.ip());
-                    self.connector.local.add_interface(&iface).await?;
+                    self.store.add_interface(&iface).await?;
                     Ok(iface)
                 }
                 None => {
@@ -1686,9 +1022,9 @@ impl NetworkingPlugin for LinuxNetwork {
                     child
                         .wait()
                         .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-                    let addresses = self.get_iface_addresses(iface.if_name.clone()).await?;
+                    let addresses = self.nl.get_iface_addresses(iface.if_name.clone()).await?;
                     iface.addresses = addresses;
-                    self.connector.local.add_interface(&iface).await?;
+                    self.store.add_interface(&iface).await?;
                     Ok(iface)
                 }
             },
@@ -1700,28 +1036,31 @@ impl NetworkingPlugin for LinuxNetwork {
         intf_uuid: Uuid,
         address: IPAddress,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        let mut iface = self.store.get_interface(intf_uuid).await?;
         match iface.net_ns {
             Some(ns_uuid) => match iface.addresses.iter().position(|&x| x == address) {
                 Some(p) => {
-                    let netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                    let netns = self.store.get_network_namespace(ns_uuid).await?;
                     let ns_manager = self.get_ns_manager(&ns_uuid).await?;
                     let addresses = ns_manager
                         .del_virtual_interface_address(iface.if_name.clone(), address)
-                        .await??;
+                        .await?;
                     iface.addresses.remove(p);
-                    self.connector.local.add_interface(&iface).await?;
+                    self.store.add_interface(&iface).await?;
                     Ok(iface)
                 }
                 None => Err(FError::NotConnected),
             },
             None => match iface.addresses.iter().position(|&x| x == address) {
                 Some(p) => {
-                    self.del_iface_address(iface.if_name.clone(), address)
+                    self.nl
+                        .del_iface_address(iface.if_name.clone(), address)
                         .await?;
                     iface.addresses.remove(p);
-                    self.connector.local.add_interface(&iface).await?;
+                    self.store.add_interface(&iface).await?;
                     Ok(iface)
                 }
                 None => Err(FError::NotConnected),
@@ -1734,33 +1073,231 @@ impl NetworkingPlugin for LinuxNetwork {
         intf_uuid: Uuid,
         address: MACAddress,
     ) -> FResult<VirtualInterface> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let mut iface = self.connector.local.get_interface(intf_uuid).await?;
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let lock = self.record_lock(intf_uuid).await;
+        let _guard = lock.lock().await;
+        let mut iface = self.store.get_interface(intf_uuid).await?;
 
         let vec_addr = vec![
             address.0, address.1, address.2, address.3, address.4, address.5,
         ];
         match iface.net_ns {
             Some(ns_uuid) => {
-                let netns = self.connector.local.get_network_namespace(ns_uuid).await?;
+                let netns = self.store.get_network_namespace(ns_uuid).await?;
                 let ns_manager = self.get_ns_manager(&ns_uuid).await?;
                 ns_manager
                     .set_virtual_interface_mac(iface.if_name.clone(), vec_addr)
-                    .await??;
+                    .await?;
                 iface.phy_address = address;
-                self.connector.local.add_interface(&iface).await?;
+                self.store.add_interface(&iface).await?;
                 Ok(iface)
             }
             None => {
-                self.set_iface_mac(iface.if_name.clone(), vec_addr).await?;
+                self.nl
+                    .set_iface_mac(iface.if_name.clone(), vec_addr)
+                    .await?;
                 iface.phy_address = address;
-                self.connector.local.add_interface(&iface).await?;
+                self.store.add_interface(&iface).await?;
                 Ok(iface)
             }
         }
     }
 }
 
+#[znserver]
+impl LinuxNetworkIntrospection for LinuxNetwork {
+    async fn get_virtual_network_leases(&self, vnet_uuid: Uuid) -> FResult<Vec<DHCPLease>> {
+        let (vnet, internals) = self.get_virtual_network_internals(vnet_uuid).await?;
+        let dhcp = internals.dhcp.ok_or(FError::NotFound)?;
+        let content = match async_std::fs::read_to_string(async_std::path::Path::new(
+            &dhcp.leases_file,
+        ))
+        .await
+        {
+            Ok(content) => content,
+            // dnsmasq creates it with the first lease
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+            .as_secs();
+        let mut leases: Vec<DHCPLease> = parse_leases(&content)?
+            .into_iter()
+            .filter(|lease| lease.expiry == 0 || lease.expiry > now)
+            .collect();
+
+        for iface_uuid in &vnet.interfaces {
+            let iface = match self.store.get_interface(*iface_uuid).await {
+                Ok(iface) => iface,
+                Err(_) => continue,
+            };
+            for lease in leases.iter_mut() {
+                if let Some(mac) = &lease.mac {
+                    if same_mac(mac, &iface.phy_address) {
+                        lease.interface = Some(iface.uuid);
+                    }
+                }
+            }
+        }
+        Ok(leases)
+    }
+
+    async fn get_virtual_interface_lease(&self, intf_uuid: Uuid) -> FResult<Option<DHCPLease>> {
+        let iface = self.store.get_interface(intf_uuid).await?;
+        for vnet in self.store.get_all_virtual_networks().await? {
+            let leases = match self.get_virtual_network_leases(vnet.uuid).await {
+                Ok(leases) => leases,
+                Err(_) => continue,
+            };
+            if let Some(lease) = leases.into_iter().find(|lease| {
+                lease.interface == Some(intf_uuid)
+                    || lease
+                        .mac
+                        .as_ref()
+                        .map_or(false, |mac| same_mac(mac, &iface.phy_address))
+            }) {
+                return Ok(Some(lease));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn get_dhcp_reservations(&self, vnet_uuid: Uuid) -> FResult<Vec<DHCPReservation>> {
+        let (_, internals) = self.get_virtual_network_internals(vnet_uuid).await?;
+        Ok(internals.reservations)
+    }
+
+    async fn add_dhcp_reservation(
+        &self,
+        vnet_uuid: Uuid,
+        reservation: DHCPReservation,
+    ) -> FResult<Vec<DHCPReservation>> {
+        let lock = self.record_lock(vnet_uuid).await;
+        let _guard = lock.lock().await;
+        let (vnet, mut internals) = self.get_virtual_network_internals(vnet_uuid).await?;
+        validate_reservation(&reservation)?;
+        if let Some(IPConfiguration {
+            subnet: Some((subnet_addr, prefix)),
+            ..
+        }) = vnet.ip_configuration
+        {
+            let subnet = IpNetwork::new(subnet_addr, prefix)
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+            if !subnet.contains(reservation.ip) {
+                return Err(FError::NetworkingError(format!(
+                    "{} is not in {}",
+                    reservation.ip, subnet
+                )));
+            }
+        }
+        if internals
+            .reservations
+            .iter()
+            .any(|r| same_mac(&r.mac, &reservation.mac) || r.ip == reservation.ip)
+        {
+            return Err(FError::AlreadyPresent);
+        }
+        internals.reservations.push(reservation);
+        self.update_dhcp_reservations(vnet, internals).await
+    }
+
+    async fn get_dhcp_options(&self, vnet_uuid: Uuid) -> FResult<DHCPOptions> {
+        let (_, internals) = self.get_virtual_network_internals(vnet_uuid).await?;
+        internals
+            .dhcp
+            .and_then(|dhcp| dhcp.settings)
+            .map(|settings| settings.options)
+            .ok_or(FError::NotFound)
+    }
+
+    async fn set_dhcp_options(
+        &self,
+        vnet_uuid: Uuid,
+        options: DHCPOptions,
+    ) -> FResult<DHCPOptions> {
+        let lock = self.record_lock(vnet_uuid).await;
+        let _guard = lock.lock().await;
+        let (mut vnet, mut internals) = self.get_virtual_network_internals(vnet_uuid).await?;
+        validate_options(&options)?;
+        let netns = internals
+            .associated_netns
+            .as_ref()
+            .map(|ns| ns.ns_name.clone());
+        let dhcp = internals.dhcp.as_mut().ok_or(FError::NotFound)?;
+        let previous = dhcp.clone();
+        let settings = dhcp.settings.as_mut().ok_or_else(|| {
+            FError::NetworkingError("The DHCP server has no settings".to_string())
+        })?;
+        // the lease time is part of the dhcp-range, that is not reloaded on SIGHUP
+        let restart = settings.options.lease_time != options.lease_time;
+        settings.options = options.clone();
+
+        let mut res = self
+            .apply_dnsmasq_config(vnet_uuid, dhcp, netns.clone(), restart)
+            .await;
+        if res.is_ok() {
+            res = match serialize_network_internals(&internals) {
+                Ok(raw) => {
+                    vnet.plugin_internals = Some(raw);
+                    self.store.add_virtual_network(&vnet).await
+                }
+                Err(e) => Err(e),
+            };
+        }
+        if let Err(e) = res {
+            // dnsmasq goes back to the options still in the store
+            log::error!("Unable to set the DHCP options of {}: {}", vnet_uuid, e);
+            if let Err(re) = self
+                .apply_dnsmasq_config(vnet_uuid, &previous, netns, restart)
+                .await
+            {
+                log::error!(
+                    "Unable to restore the DHCP options of {}: {}",
+                    vnet_uuid,
+                    re
+                );
+            }
+            return Err(e);
+        }
+        Ok(options)
+    }
+
+    async fn remove_dhcp_reservation(
+        &self,
+        vnet_uuid: Uuid,
+        mac: MACAddress,
+    ) -> FResult<Vec<DHCPReservation>> {
+        let lock = self.record_lock(vnet_uuid).await;
+        let _guard = lock.lock().await;
+        let (vnet, mut internals) = self.get_virtual_network_internals(vnet_uuid).await?;
+        let len = internals.reservations.len();
+        internals.reservations.retain(|r| !same_mac(&r.mac, &mac));
+        if internals.reservations.len() == len {
+            return Err(FError::NotFound);
+        }
+        self.update_dhcp_reservations(vnet, internals).await
+    }
+
+    async fn get_supervision_events(&self) -> FResult<Vec<SupervisionEvent>> {
+        Ok(self
+            .state
+            .read()
+            .await
+            .supervision_events
+            .iter()
+            .cloned()
+            .collect())
+    }
+
+    async fn get_virtual_network_failures(&self, vnet_uuid: Uuid) -> FResult<Vec<String>> {
+        let (_, internals) = self.get_virtual_network_internals(vnet_uuid).await?;
+        Ok(internals.failures)
+    }
+}
+
 impl LinuxNetwork {
     pub async fn new(
         z: Arc<zenoh::net::Session>,
@@ -1768,42 +1305,93 @@ impl LinuxNetwork {
         pid: u32,
         config: LinuxNetworkConfig,
     ) -> FResult<Self> {
-        // this will be removed once netlink merges the async-std support
         let tokio_rt = tokio::runtime::Runtime::new()?;
-        let handle = tokio_rt
-            .spawn_blocking(|| {
-                let (connection, handle, _) = new_connection().unwrap();
-                tokio::spawn(connection);
-                handle
-            })
-            .await
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+        let nl = Arc::new(RtNetlinkBackend::new(&tokio_rt).await?);
+        let timeout = Duration::from_secs(
+            config
+                .ns_manager_timeout
+                .unwrap_or(NS_MANAGER_DEFAULT_TIMEOUT),
+        );
+        let ns_launcher = Arc::new(ProcessNSManagerLauncher::new(
+            z.clone(),
+            config.zfilelocator.clone(),
+            timeout,
+        ));
+        let store = Arc::new(ZConnectorStore::new(connector));
+        let mut plugin = Self::new_with_backend(pid, config, nl, store, ns_launcher)?;
+        plugin.z = Some(z);
+        plugin.state.write().await.tokio_rt = Some(tokio_rt);
+        Ok(plugin)
+    }
 
+    /// Creates the plugin on top of the given netlink backend, store
+    /// and namespace managers, e.g. the in-memory ones of [`crate::netlink::fake`]
+    /// and [`crate::services::fake`] in tests.
+    /// The Agent and the OS plugin are found by [`LinuxNetwork::start`],
+    /// DHCP servers are dnsmasq processes unless `dhcp_launcher` is replaced.
+    pub fn new_with_backend(
+        pid: u32,
+        config: LinuxNetworkConfig,
+        nl: Arc<dyn NetlinkBackend>,
+        store: Arc<dyn NetworkStore>,
+        ns_launcher: Arc<dyn NSManagerLauncher>,
+    ) -> FResult<Self> {
+        if let Some(prefix) = &config.name_prefix {
+            validate_iface_name(prefix)?;
+            if prefix.len() > MAX_NAME_PREFIX_LEN {
+                return Err(LinuxNetworkError::InvalidName {
+                    name: prefix.clone(),
+                    reason: format!("prefix longer than {} bytes", MAX_NAME_PREFIX_LEN),
+                }
+                .into());
+            }
+        }
+        // the monitoring loop would never sleep
+        if config.monitoring_interveal == 0 {
+            return Err(FError::NetworkingError(
+                "monitoring_interveal must be at least 1 second".to_string(),
+            ));
+        }
         let state = LinuxNetworkState {
             uuid: None,
-            tokio_rt,
-            nl_handler: handle,
+            tokio_rt: None,
             ns_managers: HashMap::new(),
+            dhcp_servers: HashMap::new(),
+            record_locks: HashMap::new(),
+            ns_manager_restarts: HashMap::new(),
+            pending_ns_managers: HashMap::new(),
+            failed_ns_managers: HashMap::new(),
+            supervision_events: VecDeque::new(),
+            dhcp_restarts: HashMap::new(),
+            pending_dhcp_servers: HashMap::new(),
         };
 
         Ok(Self {
-            z,
-            connector,
+            z: None,
+            store,
             pid,
             agent: None,
             os: None,
             config,
             state: Arc::new(RwLock::new(state)),
+            nl,
+            ns_launcher,
+            dhcp_launcher: Arc::new(ProcessDHCPLauncher),
         })
     }
 
     async fn run(&self, stop: async_std::channel::Receiver<()>) -> FResult<()> {
         info!("LinuxNetwork main loop starting...");
+        let z = self.z.clone().ok_or(FError::NotConnected)?;
+
+        // Restoring what was created before a restart,
+        // no request is served until it is done
+        if let Err(e) = self.reconcile().await {
+            log::error!("Startup reconciliation failed: {}", e);
+        }
 
         //starting the Agent-Plugin Server
-        let hv_server = self
-            .clone()
-            .get_networking_plugin_server(self.z.clone(), None);
+        let hv_server = self.clone().get_networking_plugin_server(z.clone(), None);
         let (stopper, _h) = hv_server.connect().await?;
         hv_server.initialize().await?;
 
@@ -1815,20 +1403,54 @@ impl LinuxNetwork {
             .clone()
             .unwrap()
             .register_plugin(hv_server.instance_uuid(), PluginKind::NETWORKING)
-            .await??;
+            .await?;
 
         hv_server.register().await?;
 
         let (shv, _hhv) = hv_server.start().await?;
 
+        // Introspection server, it uses the same UUID of the plugin
+        let introspection_server = self
+            .clone()
+            .get_linux_network_introspection_server(z, Some(hv_server.instance_uuid()));
+        let (introspection_stopper, _ih) = introspection_server.connect().await?;
+        introspection_server.initialize().await?;
+        introspection_server.register().await?;
+        let (sis, _his) = introspection_server.start().await?;
+
         let monitoring = async {
+            info!("Monitoring loop started");
             loop {
-                info!("Monitoring loop started");
-                task::sleep(Duration::from_secs(60)).await;
+                task::sleep(Duration::from_secs(self.config.monitoring_interveal)).await;
+                if let Err(e) = self.monitor().await {
+                    log::error!("Monitoring failed: {}", e);
+                }
             }
         };
 
-        match monitoring.race(stop.recv()).await {
+        let supervision = async {
+            loop {
+                task::sleep(Duration::from_secs(1)).await;
+                self.supervise_ns_managers().await;
+                self.supervise_dhcp_servers().await;
+            }
+        };
+
+        let events = async {
+            loop {
+                if let Err(e) = self.netlink_events().await {
+                    log::error!("Netlink events listener failed: {}", e);
+                }
+                task::sleep(Duration::from_secs(1)).await;
+            }
+        };
+
+        match monitoring
+            .race(supervision)
+            .race(events)
+            .race(stop.recv())
+            .await
+        {
             Ok(_) => trace!("Monitoring ending correct"),
             Err(e) => trace!("Monitoring ending got error: {}", e),
         }
@@ -1837,7 +1459,13 @@ impl LinuxNetwork {
             .clone()
             .unwrap()
             .unregister_plugin(hv_server.instance_uuid())
-            .await??;
+            .await?;
+
+        introspection_server.stop(sis).await?;
+        introspection_server.unregister().await?;
+        introspection_server
+            .disconnect(introspection_stopper)
+            .await?;
 
         hv_server.stop(shv).await?;
         hv_server.unregister().await?;
@@ -1853,13 +1481,17 @@ impl LinuxNetwork {
         async_std::channel::Sender<()>,
         async_std::task::JoinHandle<FResult<()>>,
     ) {
-        let local_os = OSClient::find_local_servers(self.z.clone()).await.unwrap();
+        let z = self
+            .z
+            .clone()
+            .expect("The plugin was created without a zenoh session");
+        let local_os = OSClient::find_local_servers(z.clone()).await.unwrap();
         if local_os.is_empty() {
             error!("Unable to find a local OS interface");
             panic!("No OS Server");
         }
 
-        let local_agent = AgentPluginInterfaceClient::find_local_servers(self.z.clone())
+        let local_agent = AgentPluginInterfaceClient::find_local_servers(z.clone())
             .await
             .unwrap();
         if local_agent.is_empty() {
@@ -1867,11 +1499,11 @@ impl LinuxNetwork {
             panic!("No Agent Server");
         }
 
-        let os = OSClient::new(self.z.clone(), local_os[0]);
-        let agent = AgentPluginInterfaceClient::new(self.z.clone(), local_agent[0]);
+        let os = OSClient::new(z.clone(), local_os[0]);
+        let agent = AgentPluginInterfaceClient::new(z, local_agent[0]);
 
-        self.agent = Some(agent);
-        self.os = Some(os);
+        self.agent = Some(Arc::new(agent));
+        self.os = Some(Arc::new(os));
 
         // Starting main loop in a task
         let (s, r) = async_std::channel::bounded::<()>(1);
@@ -1882,211 +1514,1284 @@ impl LinuxNetwork {
         (s, h)
     }
 
+    /// Stops the plugin, the managed networks are kept or removed
+    /// depending on the configured [`ShutdownPolicy`].
+    /// The teardown goes on after errors, they are returned together.
     pub async fn stop(&self, stop: async_std::channel::Sender<()>) -> FResult<()> {
         log::debug!("Linux Network Stopping");
         stop.send(()).await;
 
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-        let default_vnet = self
-            .connector
-            .local
-            .get_virtual_network(Uuid::nil())
-            .await?;
-
-        for iface_uuid in default_vnet.interfaces {
-            let iface = self.connector.local.get_interface(iface_uuid).await?;
-            match iface.net_ns {
-                None => {
-                    self.del_iface(iface.if_name.clone()).await?;
-                    self.connector.local.remove_interface(iface_uuid).await?;
+        let mut errors = Vec::new();
+        match self.config.shutdown_policy.unwrap_or_default() {
+            ShutdownPolicy::Keep => {
+                // the default network is created again by the agent
+                match self.store.get_virtual_network(Uuid::nil()).await {
+                    Ok(vnet) => self.teardown_virtual_network(vnet, &mut errors).await,
+                    Err(e) => errors.push(format!("default virtual network: {}", e)),
                 }
-                Some(_) => continue,
             }
+            ShutdownPolicy::Destroy => self.teardown_all(&mut errors).await,
         }
 
-        if let Some(internals) = default_vnet.plugin_internals {
-            let internals = deserialize_network_internals(internals.as_slice())?;
+        // Managers of the kept namespaces are respawned at the next start
+        let ns_uuids: Vec<Uuid> = self
+            .state
+            .read()
+            .await
+            .ns_managers
+            .keys()
+            .copied()
+            .collect();
+        for ns_uuid in ns_uuids {
+            if let Err(e) = self.kill_ns_manager(&ns_uuid).await {
+                errors.push(format!("namespace manager {}: {}", ns_uuid, e));
+            }
+        }
 
-            // Removing namespace if present
-            if let Some(ns_internals) = internals.associated_netns {
-                self.connector
-                    .local
-                    .get_network_namespace(ns_internals.ns_uuid)
-                    .await?;
+        match self.config.shutdown_policy.unwrap_or_default() {
+            // dnsmasq keeps serving the kept networks, it is found by its
+            // pid file and started again as a child at the next start
+            ShutdownPolicy::Keep => {
+                let mut guard = self.state.write().await;
+                guard.dhcp_servers.clear();
+                guard.pending_dhcp_servers.clear();
+                guard.dhcp_restarts.clear();
+            }
+            // left by the networks that could not be removed
+            ShutdownPolicy::Destroy => {
+                let dhcps: Vec<VNetDHCP> = self
+                    .state
+                    .read()
+                    .await
+                    .dhcp_servers
+                    .values()
+                    .map(|dhcp_server| dhcp_server.dhcp.clone())
+                    .collect();
+                for dhcp in dhcps {
+                    if let Err(e) = self.terminate_dnsmasq(&dhcp).await {
+                        errors.push(format!("dnsmasq {}: {}", dhcp.pid_file, e));
+                    }
+                }
+            }
+        }
 
-                self.del_netns(ns_internals.ns_name).await?;
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            for e in &errors {
+                log::error!("Teardown error: {}", e);
+            }
+            Err(LinuxNetworkError::TeardownFailed { errors }.into())
+        }
+    }
 
-                log::trace!("Taking guard to remove ns-manager");
-                self.kill_ns_manager(&ns_internals.ns_uuid).await?;
-                self.connector
-                    .local
-                    .remove_network_namespace(ns_internals.ns_uuid)
-                    .await?;
+    /// Removes every virtual network, connection point and network namespace in the store
+    async fn teardown_all(&self, errors: &mut Vec<String>) {
+        match self.store.get_all_virtual_networks().await {
+            Ok(vnets) => {
+                for vnet in vnets {
+                    self.teardown_virtual_network(vnet, errors).await;
+                }
             }
+            Err(e) => errors.push(format!("virtual networks: {}", e)),
+        }
 
-            // Killing dhcp if present
-            if let Some(dhcp_internal) = internals.dhcp {
-                let str_pid = String::from_utf8(
-                    self.os
-                        .as_ref()
-                        .unwrap()
-                        .read_file(dhcp_internal.pid_file.clone())
-                        .await??,
-                )
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-                let pid = str_pid
-                    .trim()
-                    .parse::<i32>()
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+        // Connection points left unbound, their namespaces are removed below
+        // with the bridge and the internal veth
+        match self.store.get_all_connection_points().await {
+            Ok(cps) => {
+                for cp in cps {
+                    match self.store.get_interface(cp.external_veth).await {
+                        Ok(veth) => match self.nl.del_iface(veth.if_name.clone()).await {
+                            Ok(_) | Err(FError::NotFound) => (),
+                            Err(e) => errors.push(format!("interface {}: {}", veth.if_name, e)),
+                        },
+                        Err(e) => errors.push(format!("interface {}: {}", cp.external_veth, e)),
+                    }
+                    if let Err(e) = self.store.remove_interface(cp.external_veth).await {
+                        errors.push(format!("interface {}: {}", cp.external_veth, e));
+                    }
+                    if let Err(e) = self.store.remove_connection_point(cp.uuid).await {
+                        errors.push(format!("connection point {}: {}", cp.uuid, e));
+                    }
+                }
+            }
+            Err(e) => errors.push(format!("connection points: {}", e)),
+        }
 
-                log::trace!("Killing dnsmasq {}", pid);
+        // Namespaces not associated to a network, e.g. of unbound connection points
+        match self.store.get_all_network_namespaces().await {
+            Ok(netnses) => {
+                for netns in netnses {
+                    for iface_uuid in &netns.interfaces {
+                        if let Err(e) = self.store.remove_interface(*iface_uuid).await {
+                            errors.push(format!("interface {}: {}", iface_uuid, e));
+                        }
+                    }
+                    if let Err(e) = self.delete_network_namespace(netns.uuid).await {
+                        errors.push(format!("netns {}: {}", netns.ns_name, e));
+                    }
+                }
+            }
+            Err(e) => errors.push(format!("network namespaces: {}", e)),
+        }
+    }
 
-                kill(Pid::from_raw(pid), Signal::SIGKILL)
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+    /// Removes a virtual network with its connection points, interfaces,
+    /// namespace, DHCP server and NAT tables, errors are collected
+    async fn teardown_virtual_network(&self, vnet: VirtualNetwork, errors: &mut Vec<String>) {
+        log::debug!("Tearing down virtual network {}", vnet.uuid);
+        for cp_uuid in &vnet.connection_points {
+            let res = match self
+                .unbind_connection_point_from_virtual_network(*cp_uuid, vnet.uuid)
+                .await
+            {
+                Ok(_) => self.delete_connection_point(*cp_uuid).await.map(|_| ()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                errors.push(format!("connection point {}: {}", cp_uuid, e));
+            }
+        }
+        // unbinding the connection points updated the network
+        let vnet = match self.store.get_virtual_network(vnet.uuid).await {
+            Ok(updated) => updated,
+            Err(_) => vnet,
+        };
 
-                async_std::fs::remove_file(async_std::path::Path::new(&dhcp_internal.pid_file))
-                    .await?;
-                async_std::fs::remove_file(async_std::path::Path::new(&dhcp_internal.leases_file))
-                    .await?;
-                async_std::fs::remove_file(async_std::path::Path::new(&dhcp_internal.conf)).await?;
-                async_std::fs::remove_file(async_std::path::Path::new(&dhcp_internal.log_file))
-                    .await?;
+        for iface_uuid in &vnet.interfaces {
+            let iface = match self.store.get_interface(*iface_uuid).await {
+                Ok(iface) => iface,
+                Err(e) => {
+                    errors.push(format!("interface {}: {}", iface_uuid, e));
+                    continue;
+                }
+            };
+            // interfaces inside a namespace are removed with it,
+            // a veth peer may already be gone with its other end
+            if iface.net_ns.is_none() {
+                match self.nl.del_iface(iface.if_name.clone()).await {
+                    Ok(_) | Err(FError::NotFound) => (),
+                    Err(e) => errors.push(format!("interface {}: {}", iface.if_name, e)),
+                }
+            }
+            if let Err(e) = self.store.remove_interface(*iface_uuid).await {
+                errors.push(format!("interface {}: {}", iface_uuid, e));
             }
+        }
 
-            for table in internals.associated_tables {
-                self.clean_nat(table).await?;
+        if let Some(internals) = &vnet.plugin_internals {
+            match deserialize_network_internals(internals) {
+                Ok(internals) => {
+                    if let Some(dhcp) = internals.dhcp {
+                        if let Err(e) = self.stop_vnet_dhcp(&dhcp).await {
+                            errors.push(format!("dnsmasq {}: {}", dhcp.pid_file, e));
+                        }
+                    }
+                    if let Some(ns_info) = internals.associated_netns {
+                        if let Err(e) = self.delete_network_namespace(ns_info.ns_uuid).await {
+                            errors.push(format!("netns {}: {}", ns_info.ns_name, e));
+                        }
+                    }
+                    for table in internals.associated_tables {
+                        if let Err(e) = self.clean_nat(table.clone()).await {
+                            errors.push(format!("nft table {}: {}", table, e));
+                        }
+                    }
+                }
+                Err(e) => errors.push(format!("virtual network {}: {}", vnet.uuid, e)),
             }
         }
 
-        self.connector
-            .local
-            .remove_virtual_network(Uuid::nil())
-            .await?;
+        if let Err(e) = self.store.remove_virtual_network(vnet.uuid).await {
+            errors.push(format!("virtual network {}: {}", vnet.uuid, e));
+        }
+    }
 
-        // Here we should remove and kill all the others ns-managers and clean-up
+    /// Compares the virtual networks and namespaces in the local store
+    /// with the kernel after a restart of the plugin.
+    /// Namespace managers are respawned for the namespaces, missing
+    /// objects are recreated when possible or marked as failed in the
+    /// network internals, orphans are reported, their links, NAT tables
+    /// and dnsmasq removed.
+    async fn reconcile(&self) -> FResult<()> {
+        log::info!("Reconciling store with the kernel");
+        let live_netns = self.nl.list_netns().await?;
+        let netnses = self.store.get_all_network_namespaces().await?;
+
+        let mut ns_failures = HashMap::new();
+        for netns in &netnses {
+            match self.reconcile_netns(netns, &live_netns).await {
+                Ok(failures) => {
+                    ns_failures.insert(netns.uuid, failures);
+                }
+                Err(e) => {
+                    log::error!("Unable to restore namespace {}: {}", netns.ns_name, e);
+                    ns_failures.insert(netns.uuid, vec![format!("netns {}: {}", netns.ns_name, e)]);
+                }
+            }
+        }
 
-        Ok(())
-    }
+        // dnsmasq already started by this instance, as the one of the default network
+        let mut dhcp_pid_files: Vec<String> = self
+            .state
+            .read()
+            .await
+            .dhcp_servers
+            .values()
+            .map(|p| p.dhcp.pid_file.clone())
+            .collect();
+        for vnet in self.store.get_all_virtual_networks().await? {
+            // the default network is recreated by the agent
+            if vnet.uuid == Uuid::nil() {
+                if let Some(ref pl_net_info) = vnet.plugin_internals {
+                    if let Some(dhcp) = deserialize_network_internals(pl_net_info)?.dhcp {
+                        dhcp_pid_files.push(dhcp.pid_file);
+                    }
+                }
+                continue;
+            }
+            let vnet_uuid = vnet.uuid;
+            match self.reconcile_virtual_network(vnet, &ns_failures).await {
+                Ok(Some(pid_file)) => dhcp_pid_files.push(pid_file),
+                Ok(None) => (),
+                Err(e) => log::error!("Unable to restore virtual network {}: {}", vnet_uuid, e),
+            }
+        }
 
-    /// Spawns and insert a new Namespace Manager into the Plugin state
-    async fn spawn_ns_manager(&self, ns_name: String, ns_uuid: Uuid) -> FResult<()> {
-        let mut guard = self.state.write().await;
-        let child = Command::new("fos-net-linux-ns-manager")
-            .arg("--netns")
-            .arg(&ns_name)
-            .arg("--id")
-            .arg(format!("{}", ns_uuid))
-            .arg("--locator")
-            .arg(self.config.zfilelocator.clone())
-            .spawn()
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-        let ns_manager_client = NamespaceManagerClient::new(self.z.clone(), ns_uuid);
-        guard
-            .ns_managers
-            .insert(ns_uuid, (child.id(), ns_manager_client));
-        drop(guard);
+        // Orphans
+        for ns_name in live_netns {
+            if ns_name.starts_with(&self.get_netns_prefix())
+                && !netnses.iter().any(|ns| ns.ns_name == ns_name)
+            {
+                log::warn!("Network namespace {} is not in the store", ns_name);
+            }
+        }
+        // only what the store reports as missing is removed,
+        // other errors of the store must not destroy live objects
+        for link in self.nl.dump_links().await? {
+            let owner = match self.nl.get_iface_alias(link.clone()).await {
+                Ok(Some(alias)) => parse_owner_tag(&alias),
+                _ => None,
+            };
+            if let Some(owner) = owner {
+                match self.store.get_interface(owner).await {
+                    Err(FError::NotFound) => {
+                        log::warn!("Removing {} owned by {} not in the store", link, owner);
+                        if let Err(e) = self.nl.del_iface(link.clone()).await {
+                            log::warn!("Unable to remove {}: {}", link, e);
+                        }
+                    }
+                    Err(e) => log::warn!("Unable to check the owner of {}: {}", link, e),
+                    Ok(_) => (),
+                }
+            }
+        }
+        for table in self.nl.list_nat_tables().await? {
+            if let Some(owner) = parse_owner_table_name(&table) {
+                // the network is being created, its record is written last
+                if self.record_in_use(&owner).await {
+                    continue;
+                }
+                match self.store.get_virtual_network(owner).await {
+                    Err(FError::NotFound) => {
+                        log::warn!(
+                            "Removing NAT table {} owned by {} not in the store",
+                            table,
+                            owner
+                        );
+                        if let Err(e) = self.clean_nat(table.clone()).await {
+                            log::warn!("Unable to remove {}: {}", table, e);
+                        }
+                    }
+                    Err(e) => log::warn!("Unable to check the owner of {}: {}", table, e),
+                    Ok(_) => (),
+                }
+            }
+        }
+        let mut entries =
+            async_std::fs::read_dir(async_std::path::Path::new(self.get_run_path().as_ref()))
+                .await?;
+        while let Some(entry) = entries.next().await {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("pid") {
+                continue;
+            }
+            let pid_file = path.to_str().ok_or(FError::EncodingError)?.to_string();
+            if dhcp_pid_files.contains(&pid_file) {
+                continue;
+            }
+            // only the files named as the plugin does, of a running dnsmasq
+            let name = path
+                .file_stem()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            if name != DEFAULT_VNET_DHCP_NAME && Uuid::parse_str(name).is_err() {
+                continue;
+            }
+            let pid = async_std::fs::read_to_string(path.as_path())
+                .await
+                .ok()
+                .and_then(|p| p.trim().parse::<i32>().ok());
+            match pid {
+                Some(pid) if is_dnsmasq(pid) => (),
+                _ => {
+                    log::debug!("Skipping {}, not the pid file of a dnsmasq", pid_file);
+                    continue;
+                }
+            }
+            log::warn!("Removing dnsmasq not in the store: {}", pid_file);
+            let orphan = VNetDHCP {
+                leases_file: path.with_extension("leases").to_string_lossy().to_string(),
+                pid_file,
+                conf: path.with_extension("conf").to_string_lossy().to_string(),
+                log_file: path.with_extension("log").to_string_lossy().to_string(),
+                hosts_file: Some(path.with_extension("hosts").to_string_lossy().to_string()),
+                opts_file: Some(path.with_extension("opts").to_string_lossy().to_string()),
+                settings: None,
+            };
+            if let Err(e) = self.stop_vnet_dhcp(&orphan).await {
+                log::warn!("Unable to remove dnsmasq {}: {}", orphan.pid_file, e);
+            }
+        }
         Ok(())
     }
 
-    async fn get_ns_manager(&self, ns_uuid: &Uuid) -> FResult<NamespaceManagerClient> {
-        let mut guard = self.state.read().await;
-        let (_, ns_manager) = guard
-            .ns_managers
-            .get(ns_uuid)
-            .ok_or_else(|| FError::NetworkingError("Manager not found".to_string()))?;
-        Ok(ns_manager.clone())
-    }
+    /// Respawns the namespace manager of a namespace in the store,
+    /// if the namespace is not present anymore it is recreated.
+    /// Missing bridges are recreated, other missing interfaces
+    /// are returned as failures.
+    async fn reconcile_netns(
+        &self,
+        netns: &NetworkNamespace,
+        live_netns: &[String],
+    ) -> FResult<Vec<String>> {
+        log::debug!("Reconciling network namespace {}", netns.ns_name);
+        // a manager left by the previous instance serves with the same uuid
+        for pid in find_ns_manager_pids(&netns.uuid) {
+            log::trace!("Killing stale namespace manager {}", pid);
+            let _ = kill(Pid::from_raw(pid), Signal::SIGTERM);
+        }
+        if !live_netns.contains(&netns.ns_name) {
+            log::warn!("Network namespace {} is missing, recreating", netns.ns_name);
+            self.nl.add_netns(netns.ns_name.clone()).await?;
+        }
+        self.spawn_ns_manager(netns.ns_name.clone(), netns.uuid)
+            .await?;
+        let ns_manager = self.get_ns_manager(&netns.uuid).await?;
+        ns_manager
+            .set_virtual_interface_up("lo".to_string())
+            .await?;
 
-    async fn remove_ns_manager(&self, ns_uuid: &Uuid) -> FResult<(u32, NamespaceManagerClient)> {
-        let mut guard = self.state.write().await;
-        let (pid, ns_manager) = guard
-            .ns_managers
-            .remove(&ns_uuid)
-            .ok_or_else(|| FError::NetworkingError("Manager not found".to_string()))?;
-        Ok((pid, ns_manager))
-    }
+        let mut failures = Vec::new();
+        let mut recreated_bridges = Vec::new();
+        for iface_uuid in &netns.interfaces {
+            let iface = match self.store.get_interface(*iface_uuid).await {
+                Ok(iface) => iface,
+                Err(_) => continue,
+            };
+            if iface.net_ns != Some(netns.uuid)
+                || ns_manager
+                    .check_virtual_interface_exists(iface.if_name.clone())
+                    .await?
+            {
+                continue;
+            }
+            match iface.kind {
+                VirtualInterfaceKind::BRIDGE(_) => {
+                    log::warn!("Recreating bridge {} in {}", iface.if_name, netns.ns_name);
+                    ns_manager
+                        .add_virtual_interface_bridge(iface.if_name.clone())
+                        .await?;
+                    ns_manager
+                        .set_virtual_interface_up(iface.if_name.clone())
+                        .await?;
+                    if let Err(e) = self.tag_interface(&iface).await {
+                        log::warn!("Unable to tag {} with its owner: {}", iface.if_name, e);
+                    }
+                    recreated_bridges.push(iface);
+                }
+                _ => {
+                    log::error!("Interface {} missing in {}", iface.if_name, netns.ns_name);
+                    failures.push(format!("{} missing in {}", iface.if_name, netns.ns_name));
+                }
+            }
+        }
 
-    /// Removes and kills a Namespaces Manager
-    async fn kill_ns_manager(&self, ns_uuid: &Uuid) -> FResult<()> {
-        let (pid, ns_manager) = self.remove_ns_manager(ns_uuid).await?;
-        kill(Pid::from_raw(pid as i32), Signal::SIGTERM)
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-        Ok(())
+        // the ports of a bridge are released when it is removed
+        for bridge in recreated_bridges {
+            for iface_uuid in &netns.interfaces {
+                let iface = match self.store.get_interface(*iface_uuid).await {
+                    Ok(iface) if iface.parent == Some(bridge.uuid) => iface,
+                    _ => continue,
+                };
+                if let Err(e) = ns_manager
+                    .set_virtual_interface_master(iface.if_name.clone(), bridge.if_name.clone())
+                    .await
+                {
+                    log::error!("Unable to attach {}: {}", iface.if_name, e);
+                    failures.push(format!("{}: {}", iface.if_name, e));
+                }
+            }
+        }
+        Ok(failures)
     }
 
-    async fn mcast_vxlan_create(
+    /// Recreates the missing objects of a virtual network in the default
+    /// namespace, restarts its dnsmasq and reapplies the NAT.
+    /// Returns the pid file of the dnsmasq of the network, if any.
+    async fn reconcile_virtual_network(
         &self,
         mut vnet: VirtualNetwork,
-        vxlan_info: MCastVXLANInfo,
-    ) -> FResult<VirtualNetwork> {
-        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await??;
-
-        // Generating Names
-
-        let br_uuid = Uuid::new_v4();
-        let br_name = self.generate_random_interface_name();
+        ns_failures: &HashMap<Uuid, Vec<String>>,
+    ) -> FResult<Option<String>> {
+        log::debug!("Reconciling virtual network {}", vnet.uuid);
+        let mut internals = match &vnet.plugin_internals {
+            Some(raw) => deserialize_network_internals(raw)?,
+            None => return Ok(None),
+        };
+        let mut failures = Vec::new();
+
+        // bridges first, the other interfaces are attached to them
+        let mut ifaces = Vec::new();
+        for iface_uuid in &vnet.interfaces {
+            let iface = self.store.get_interface(*iface_uuid).await?;
+            if iface.net_ns.is_none() && !self.nl.iface_exists(iface.if_name.clone()).await? {
+                ifaces.push(iface);
+            }
+        }
+        ifaces.sort_by_key(|iface| !matches!(iface.kind, VirtualInterfaceKind::BRIDGE(_)));
+
+        let prefix = match vnet.ip_configuration {
+            Some(IPConfiguration {
+                subnet: Some((_, prefix)),
+                ..
+            }) => Some(prefix),
+            _ => None,
+        };
+        let mut recreated_bridges = Vec::new();
+        for iface in ifaces {
+            log::warn!("Interface {} is missing, recreating", iface.if_name);
+            let res = match (&iface.kind, &internals.transport) {
+                (VirtualInterfaceKind::BRIDGE(_), _) => {
+                    self.nl.create_bridge(iface.if_name.clone()).await?;
+                    if let Some(prefix) = prefix {
+                        for addr in &iface.addresses {
+                            self.nl
+                                .add_iface_address(iface.if_name.clone(), *addr, prefix)
+                                .await?;
+                        }
+                    }
+                    Ok(())
+                }
+                (VirtualInterfaceKind::VLAN(_), Some(VNetTransport::VLAN { tag, dev })) => {
+                    self.nl
+                        .create_vlan(iface.if_name.clone(), dev.clone(), *tag)
+                        .await
+                }
+                (VirtualInterfaceKind::VXLAN(_), Some(transport)) => {
+                    let mut undo = Vec::new();
+                    let res = self
+                        .create_transport_vxlan(
+                            iface.if_name.clone(),
+                            self.get_overlay_iface().await?,
+                            transport,
+                            &mut undo,
+                        )
+                        .await;
+                    if res.is_err() {
+                        self.rollback(undo).await;
+                    }
+                    res
+                }
+                _ => Err(FError::Unimplemented),
+            };
+            match res {
+                Ok(_) => {
+                    if let Err(e) = self.tag_interface(&iface).await {
+                        log::warn!("Unable to tag {} with its owner: {}", iface.if_name, e);
+                    }
+                    if let Some(parent) = iface.parent {
+                        let parent = self.store.get_interface(parent).await?;
+                        self.nl
+                            .set_iface_master(iface.if_name.clone(), parent.if_name)
+                            .await?;
+                    }
+                    self.nl.set_iface_up(iface.if_name.clone()).await?;
+                    if let VirtualInterfaceKind::BRIDGE(_) = iface.kind {
+                        recreated_bridges.push(iface.uuid);
+                    }
+                }
+                Err(e) => {
+                    log::error!("Unable to recreate {}: {}", iface.if_name, e);
+                    failures.push(format!("{}: {}", iface.if_name, e));
+                }
+            }
+        }
 
-        let vxl_uuid = Uuid::new_v4();
-        let vxl_name = self.generate_random_interface_name();
+        // the ports of a bridge are released when it is removed
+        if !recreated_bridges.is_empty() {
+            for iface_uuid in &vnet.interfaces {
+                let iface = self.store.get_interface(*iface_uuid).await?;
+                let parent = match iface.parent {
+                    Some(parent)
+                        if iface.net_ns.is_none() && recreated_bridges.contains(&parent) =>
+                    {
+                        self.store.get_interface(parent).await?
+                    }
+                    _ => continue,
+                };
+                if !self.nl.iface_exists(iface.if_name.clone()).await? {
+                    continue;
+                }
+                if let Err(e) = self
+                    .nl
+                    .set_iface_master(iface.if_name.clone(), parent.if_name)
+                    .await
+                {
+                    log::error!("Unable to attach {}: {}", iface.if_name, e);
+                    failures.push(format!("{}: {}", iface.if_name, e));
+                }
+            }
+        }
 
-        let internal_br_uuid = Uuid::new_v4();
-        let internal_br_name = self.generate_random_interface_name();
+        if let Some(ns_info) = &internals.associated_netns {
+            if let Some(ns_failures) = ns_failures.get(&ns_info.ns_uuid) {
+                failures.extend(ns_failures.iter().cloned());
+            }
+        }
 
-        let internal_veth_uuid = Uuid::new_v4();
-        let internal_veth_name = self.generate_random_interface_name();
+        // the rules of the NAT tables are not read back, the tables
+        // are removed and created again
+        if !internals.associated_tables.is_empty() {
+            for table in internals.associated_tables.drain(..) {
+                let _ = self.clean_nat(table).await;
+            }
+            if let Some(IPConfiguration {
+                subnet: Some((subnet_addr, prefix)),
+                gateway: Some(_),
+                ..
+            }) = vnet.ip_configuration
+            {
+                let subnet = IpNetwork::new(subnet_addr, prefix)
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+                match self
+                    .configure_nat(
+                        &vnet.uuid,
+                        &[subnet],
+                        &self.get_overlay_face_from_config().await?.if_name,
+                    )
+                    .await
+                {
+                    Ok(table) => internals.associated_tables.push(table),
+                    Err(e) => failures.push(format!("NAT: {}", e)),
+                }
+            }
+        }
 
-        let external_veth_uuid = Uuid::new_v4();
-        let external_veth_name = self.generate_random_interface_name();
+        // networks created before the DHCP reservations have no hosts file
+        if let Some(dhcp) = internals.dhcp.as_mut() {
+            if dhcp.hosts_file.is_none() || dhcp.opts_file.is_none() {
+                if let Err(e) = self.add_dhcp_files(dhcp, &internals.reservations).await {
+                    failures.push(format!("dnsmasq: {}", e));
+                }
+            }
+        }
 
-        let mut associated_ns = NetworkNamespace {
-            uuid: vnet.uuid,
-            ns_name: self.generate_random_netns_name(),
-            interfaces: vec![
-                external_veth_uuid,
-                internal_veth_uuid,
-                internal_br_uuid,
-                vxl_uuid,
-                br_uuid,
-            ],
+        // dnsmasq is started again as a child of the plugin,
+        // it reloads the leases from its leases file
+        let pid_file = match &internals.dhcp {
+            Some(dhcp) => {
+                let ns_name = internals
+                    .associated_netns
+                    .as_ref()
+                    .map(|ns| ns.ns_name.clone());
+                if let Err(e) = self.restart_vnet_dhcp(vnet.uuid, dhcp, ns_name).await {
+                    failures.push(format!("dnsmasq: {}", e));
+                }
+                Some(dhcp.pid_file.clone())
+            }
+            None => None,
         };
 
-        // Generating Structs
+        internals.failures = failures;
+        vnet.plugin_internals = Some(serialize_network_internals(&internals)?);
+        self.store.add_virtual_network(&vnet).await?;
+        Ok(pid_file)
+    }
 
-        let v_bridge = VirtualInterface {
-            uuid: br_uuid,
-            if_name: br_name.clone(),
-            net_ns: None,
-            parent: None,
-            kind: VirtualInterfaceKind::BRIDGE(BridgeKind {
-                childs: vec![external_veth_uuid, vxl_uuid],
-            }),
-            addresses: Vec::new(),
-            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
-        };
+    /// Checks the managed namespaces, namespace managers, dnsmasq and
+    /// interfaces against the kernel. Interfaces addresses are updated
+    /// in the local store, anything else that drifted is logged.
+    async fn monitor(&self) -> FResult<()> {
+        log::trace!("Monitoring managed objects");
+        let live_netns = self.nl.list_netns().await?;
+        let mut ifaces = Vec::new();
+        for netns in self.store.get_all_network_namespaces().await? {
+            if !live_netns.contains(&netns.ns_name) {
+                log::warn!("Network namespace {} is missing", netns.ns_name);
+                continue;
+            }
+            match self.get_ns_manager(&netns.uuid).await {
+                Ok(ns_manager) => {
+                    if !ns_manager.verify_server().await.unwrap_or(false) {
+                        log::warn!("Namespace manager for {} is not serving", netns.ns_name);
+                    }
+                }
+                Err(_) => log::warn!("Network namespace {} has no manager", netns.ns_name),
+            }
+            ifaces.extend(netns.interfaces);
+        }
+        for vnet in self.store.get_all_virtual_networks().await? {
+            ifaces.extend(vnet.interfaces.iter().copied());
+            if let Some(raw) = &vnet.plugin_internals {
+                if let Some(dhcp) = deserialize_network_internals(raw)?.dhcp {
+                    if !self.is_dnsmasq_running(&dhcp).await {
+                        log::warn!("dnsmasq of virtual network {} is not running", vnet.uuid);
+                    }
+                }
+            }
+        }
+        ifaces.sort();
+        ifaces.dedup();
+        for iface_uuid in ifaces {
+            if let Err(e) = self.monitor_interface(iface_uuid).await {
+                log::warn!("Unable to check interface {}: {}", iface_uuid, e);
+            }
+        }
+        Ok(())
+    }
 
-        let v_internal_bridge = VirtualInterface {
-            uuid: internal_br_uuid,
-            if_name: internal_br_name.clone(),
-            net_ns: Some(associated_ns.uuid),
+    /// Checks that an interface exists and updates its addresses in the store
+    async fn monitor_interface(&self, iface_uuid: Uuid) -> FResult<()> {
+        let lock = self.record_lock(iface_uuid).await;
+        let _guard = lock.lock().await;
+        self.refresh_interface(iface_uuid).await
+    }
+
+    /// Body of [`LinuxNetwork::monitor_interface`],
+    /// the caller holds the lock of the interface
+    async fn refresh_interface(&self, iface_uuid: Uuid) -> FResult<()> {
+        let mut iface = self.store.get_interface(iface_uuid).await?;
+        let addresses = match iface.net_ns {
+            None => {
+                if !self.nl.iface_exists(iface.if_name.clone()).await? {
+                    log::warn!("Interface {} is missing", iface.if_name);
+                    return Ok(());
+                }
+                self.nl.get_iface_addresses(iface.if_name.clone()).await?
+            }
+            Some(ns_uuid) => {
+                let ns_manager = self.get_ns_manager(&ns_uuid).await?;
+                if !ns_manager
+                    .check_virtual_interface_exists(iface.if_name.clone())
+                    .await?
+                {
+                    log::warn!("Interface {} is missing in {}", iface.if_name, ns_uuid);
+                    return Ok(());
+                }
+                ns_manager
+                    .get_virtual_interface_addresses(iface.if_name.clone())
+                    .await?
+            }
+        };
+        let changed = addresses.len() != iface.addresses.len()
+            || addresses.iter().any(|a| !iface.addresses.contains(a));
+        if changed {
+            log::info!(
+                "Addresses of {} changed: {:?} -> {:?}",
+                iface.if_name,
+                iface.addresses,
+                addresses
+            );
+            iface.addresses = addresses;
+            self.store.add_interface(&iface).await?;
+        }
+        Ok(())
+    }
+
+    /// Listens for link and address notifications from the kernel,
+    /// managed interfaces in the default namespace are kept in sync
+    /// with changes made outside of the plugin
+    async fn netlink_events(&self) -> FResult<()> {
+        let mut events = self.nl.subscribe().await?;
+        log::info!("Listening for netlink events");
+        while let Some(event) = events.next().await {
+            if let Err(e) = self.handle_netlink_event(None, event).await {
+                log::warn!("Unable to handle netlink event: {}", e);
+            }
+        }
+        Err(FError::NetworkingError(
+            "Netlink events stream closed".to_string(),
+        ))
+    }
+
+    /// Polls the netlink events of a namespace from its manager,
+    /// ends once the manager is killed or replaced after a restart,
+    /// the restarted manager gets its own listener
+    async fn ns_netlink_events(&self, ns_uuid: Uuid, ns_manager: Arc<dyn NSManagerOps>) {
+        log::info!("Listening for netlink events in {}", ns_uuid);
+        loop {
+            let events = ns_manager.next_netlink_events().await;
+            // the events of a replaced manager are covered by reconcile
+            match self.get_ns_manager(&ns_uuid).await {
+                Ok(current) if same_ns_manager(&current, &ns_manager) => (),
+                _ => break,
+            }
+            match events {
+                Ok(events) => {
+                    for event in events {
+                        if let Err(e) = self.handle_netlink_event(Some(ns_uuid), event).await {
+                            log::warn!("Unable to handle netlink event in {}: {}", ns_uuid, e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    log::warn!("Unable to get netlink events of {}: {}", ns_uuid, e);
+                    task::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+        log::info!("Stopped listening for netlink events in {}", ns_uuid);
+    }
+
+    /// Only links tagged with the owner tag are handled, the tag
+    /// gives the UUID of their record without looking through the store.
+    /// `netns` is the namespace the event comes from, None for the default one.
+    async fn handle_netlink_event(&self, netns: Option<Uuid>, event: NetlinkEvent) -> FResult<()> {
+        let owner = |alias: &Option<String>| alias.as_deref().and_then(parse_owner_tag);
+        match (event, netns) {
+            (NetlinkEvent::LinkAdded { name, alias, up }, _) => match owner(&alias) {
+                Some(iface_uuid) => self.sync_interface(iface_uuid, netns, name, Some(up)).await,
+                None => Ok(()),
+            },
+            (NetlinkEvent::LinkRemoved { name, alias }, _) => match owner(&alias) {
+                Some(iface_uuid) => self.sync_interface(iface_uuid, netns, name, None).await,
+                None => Ok(()),
+            },
+            // the index is only meaningful inside the namespace,
+            // the interfaces of the namespace are refreshed instead
+            (NetlinkEvent::AddressChanged(_), Some(ns_uuid)) => {
+                let ns = match self.store.get_network_namespace(ns_uuid).await {
+                    Ok(ns) => ns,
+                    Err(FError::NotFound) => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                for iface_uuid in ns.interfaces {
+                    match self.monitor_interface(iface_uuid).await {
+                        // removed in the meantime
+                        Ok(_) | Err(FError::NotFound) => (),
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok(())
+            }
+            (NetlinkEvent::AddressChanged(index), None) => {
+                let alias = match self.nl.get_iface_name(index).await {
+                    Ok(iface) => self.nl.get_iface_alias(iface).await?,
+                    // the link was removed in the meantime
+                    Err(FError::NotFound) => return Ok(()),
+                    Err(e) => return Err(e),
+                };
+                match owner(&alias) {
+                    Some(iface_uuid) => self.monitor_interface(iface_uuid).await,
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Updates the store after a change of a managed link in the namespace
+    /// `netns` (None for the default one), `up` is None once the link is removed.
+    /// Links removed or set down outside of the plugin are recorded in the
+    /// failures of their virtual network and cleared when they come back.
+    async fn sync_interface(
+        &self,
+        iface_uuid: Uuid,
+        netns: Option<Uuid>,
+        iface: String,
+        up: Option<bool>,
+    ) -> FResult<()> {
+        {
+            let lock = self.record_lock(iface_uuid).await;
+            let _guard = lock.lock().await;
+            match self.store.get_interface(iface_uuid).await {
+                Ok(v_iface) if v_iface.net_ns == netns => (),
+                // deleted or moved to another namespace by the plugin
+                _ => return Ok(()),
+            }
+            match up {
+                Some(_) => self.refresh_interface(iface_uuid).await?,
+                None => log::warn!("Interface {} was removed outside of the plugin", iface),
+            }
+        }
+
+        let vnet_uuid = match self
+            .store
+            .get_all_virtual_networks()
+            .await?
+            .into_iter()
+            .find(|vnet| vnet.interfaces.contains(&iface_uuid))
+        {
+            Some(vnet) => vnet.uuid,
+            None => return Ok(()),
+        };
+        let removed = format!("{} removed", iface);
+        let down = format!("{} down", iface);
+        self.update_vnet_failures(vnet_uuid, |failures| {
+            failures.retain(|f| *f != removed && *f != down);
+            match up {
+                None => failures.push(removed),
+                Some(false) => failures.push(down),
+                Some(true) => (),
+            }
+        })
+        .await
+    }
+
+    /// Updates the failures of a virtual network under its lock,
+    /// the record is written only if they changed
+    async fn update_vnet_failures<F>(&self, vnet_uuid: Uuid, update: F) -> FResult<()>
+    where
+        F: FnOnce(&mut Vec<String>),
+    {
+        let lock = self.record_lock(vnet_uuid).await;
+        let _guard = lock.lock().await;
+        // the network may be under deletion
+        let mut vnet = match self.store.get_virtual_network(vnet_uuid).await {
+            Ok(vnet) => vnet,
+            Err(_) => return Ok(()),
+        };
+        let mut internals = match &vnet.plugin_internals {
+            Some(raw) => deserialize_network_internals(raw)?,
+            None => return Ok(()),
+        };
+        let mut failures = internals.failures.clone();
+        update(&mut failures);
+        if failures != internals.failures {
+            internals.failures = failures;
+            vnet.plugin_internals = Some(serialize_network_internals(&internals)?);
+            self.store.add_virtual_network(&vnet).await?;
+        }
+        Ok(())
+    }
+
+    /// Keeps a supervision event for the introspection
+    async fn push_supervision_event(&self, kind: SupervisionEventKind) {
+        let mut guard = self.state.write().await;
+        if guard.supervision_events.len() >= MAX_SUPERVISION_EVENTS {
+            guard.supervision_events.pop_front();
+        }
+        guard
+            .supervision_events
+            .push_back(SupervisionEvent::new(kind));
+    }
+
+    /// Checks if the dnsmasq of a virtual network is alive
+    async fn is_dnsmasq_running(&self, dhcp_internal: &VNetDHCP) -> bool {
+        match async_std::fs::read_to_string(async_std::path::Path::new(&dhcp_internal.pid_file))
+            .await
+        {
+            Ok(str_pid) => match str_pid.trim().parse::<i32>() {
+                Ok(pid) => kill(Pid::from_raw(pid), None).is_ok(),
+                Err(_) => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// Spawns and insert a new Namespace Manager into the Plugin state.
+    /// Returns once the manager reports it is serving, see [`NSManagerLauncher`].
+    async fn spawn_ns_manager(&self, ns_name: String, ns_uuid: Uuid) -> FResult<()> {
+        let ns_manager = self.ns_launcher.launch(ns_name, ns_uuid).await?;
+        let client = ns_manager.client.clone();
+        let mut guard = self.state.write().await;
+        guard.ns_managers.insert(ns_uuid, ns_manager);
+        guard.failed_ns_managers.remove(&ns_uuid);
+        drop(guard);
+        let plugin = self.clone();
+        task::spawn(async move { plugin.ns_netlink_events(ns_uuid, client).await });
+        Ok(())
+    }
+
+    /// Stores the record of a newly created interface and stamps its kernel
+    /// link with the UUID of the record, see [`owner_tag`].
+    /// The record is written first, a tagged link without a record
+    /// is an orphan for [`LinuxNetwork::reconcile`].
+    /// The alias survives renames and namespace moves, so later updates
+    /// of the record go straight to the store.
+    /// Links inside a namespace are tagged through its manager.
+    async fn store_new_interface(&self, iface: &VirtualInterface) -> FResult<()> {
+        self.store.add_interface(iface).await?;
+        if let Err(e) = self.tag_interface(iface).await {
+            log::warn!("Unable to tag {} with its owner: {}", iface.if_name, e);
+        }
+        Ok(())
+    }
+
+    async fn tag_interface(&self, iface: &VirtualInterface) -> FResult<()> {
+        let alias = owner_tag(&iface.uuid);
+        match iface.net_ns {
+            None => self.nl.set_iface_alias(iface.if_name.clone(), alias).await,
+            Some(ns_uuid) => {
+                self.get_ns_manager(&ns_uuid)
+                    .await?
+                    .set_virtual_interface_alias(iface.if_name.clone(), alias)
+                    .await
+            }
+        }
+    }
+
+    /// Returns the lock serializing the read-modify-write of the record
+    /// with the given UUID, the locks nobody holds are dropped
+    async fn record_lock(&self, uuid: Uuid) -> Arc<Mutex<()>> {
+        let mut guard = self.state.write().await;
+        guard
+            .record_locks
+            .retain(|_, lock| Arc::strong_count(lock) > 1);
+        guard.record_locks.entry(uuid).or_default().clone()
+    }
+
+    /// Tells if a request holds the lock of the record with the given UUID,
+    /// virtual networks are locked while they are created, before
+    /// their record exists
+    async fn record_in_use(&self, uuid: &Uuid) -> bool {
+        self.state
+            .read()
+            .await
+            .record_locks
+            .get(uuid)
+            .map_or(false, |lock| Arc::strong_count(lock) > 1)
+    }
+
+    async fn get_ns_manager(&self, ns_uuid: &Uuid) -> FResult<Arc<dyn NSManagerOps>> {
+        let mut guard = self.state.read().await;
+        if let Some(reason) = guard.failed_ns_managers.get(ns_uuid) {
+            return Err(FError::NetworkingError(format!(
+                "Manager of {} failed: {}",
+                ns_uuid, reason
+            )));
+        }
+        let ns_manager = guard
+            .ns_managers
+            .get(ns_uuid)
+            .ok_or_else(|| FError::NetworkingError("Manager not found".to_string()))?;
+        Ok(ns_manager.client.clone())
+    }
+
+    async fn remove_ns_manager(&self, ns_uuid: &Uuid) -> FResult<NSManagerProcess> {
+        let mut guard = self.state.write().await;
+        guard
+            .ns_managers
+            .remove(&ns_uuid)
+            .ok_or_else(|| FError::NetworkingError("Manager not found".to_string()))
+    }
+
+    /// Removes and kills a Namespaces Manager, the process is reaped
+    /// in background once it exits.
+    /// Managers waiting for a restart or failed are only forgotten.
+    async fn kill_ns_manager(&self, ns_uuid: &Uuid) -> FResult<()> {
+        let mut guard = self.state.write().await;
+        guard.ns_manager_restarts.remove(ns_uuid);
+        let pending = guard.pending_ns_managers.remove(ns_uuid).is_some();
+        let failed = guard.failed_ns_managers.remove(ns_uuid).is_some();
+        drop(guard);
+        let ns_manager = match self.remove_ns_manager(ns_uuid).await {
+            Ok(ns_manager) => ns_manager,
+            Err(_) if pending || failed => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if let Some(mut child) = ns_manager.child {
+            kill(Pid::from_raw(child.id() as i32), Signal::SIGTERM)
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+            task::spawn_blocking(move || child.wait());
+        }
+        Ok(())
+    }
+
+    /// Checks if any Namespace Manager exited, exited managers are reaped
+    /// and spawned again for the same namespace and uuid.
+    /// Restarts wait SUPERVISION_BACKOFF, doubled at each attempt, after
+    /// SUPERVISION_MAX_RESTARTS the namespace is marked failed.
+    async fn supervise_ns_managers(&self) {
+        let mut gave_up = Vec::new();
+        let mut due = Vec::new();
+        let mut guard = self.state.write().await;
+        let state = &mut *guard;
+        let mut exited = Vec::new();
+        for (ns_uuid, ns_manager) in state.ns_managers.iter_mut() {
+            // managers without a process run inside the plugin
+            let child = match ns_manager.child.as_mut() {
+                Some(child) => child,
+                None => continue,
+            };
+            match child.try_wait() {
+                Ok(Some(status)) => exited.push((*ns_uuid, status)),
+                Ok(None) => (),
+                Err(e) => log::error!("Unable to check namespace manager {}: {}", ns_uuid, e),
+            }
+        }
+        for (ns_uuid, status) in exited {
+            let ns_name = match state.ns_managers.remove(&ns_uuid) {
+                Some(ns_manager) => ns_manager.ns_name,
+                None => continue,
+            };
+            log::warn!(
+                "Namespace manager for {} ({}) exited with {}",
+                ns_name,
+                ns_uuid,
+                status
+            );
+            let restarts = state.ns_manager_restarts.entry(ns_uuid).or_default();
+            if restarts.exited() {
+                state.pending_ns_managers.insert(ns_uuid, ns_name);
+            } else {
+                gave_up.push((ns_uuid, ns_name, format!("exited with {}", status)));
+            }
+        }
+        for (ns_uuid, ns_name) in state.pending_ns_managers.iter() {
+            if state
+                .ns_manager_restarts
+                .get(ns_uuid)
+                .map_or(true, Restarts::is_due)
+            {
+                due.push((*ns_uuid, ns_name.clone()));
+            }
+        }
+        for (ns_uuid, _) in &due {
+            state.pending_ns_managers.remove(ns_uuid);
+        }
+        drop(guard);
+
+        for (ns_uuid, ns_name) in due {
+            if !self
+                .nl
+                .list_netns()
+                .await
+                .map_or(false, |ns| ns.contains(&ns_name))
+            {
+                log::error!(
+                    "Network namespace {} is gone, not restarting its manager",
+                    ns_name
+                );
+                self.state
+                    .write()
+                    .await
+                    .ns_manager_restarts
+                    .remove(&ns_uuid);
+                continue;
+            }
+            match self.spawn_ns_manager(ns_name.clone(), ns_uuid).await {
+                Ok(_) => {
+                    let mut guard = self.state.write().await;
+                    let attempt = guard.ns_manager_restarts.get_mut(&ns_uuid).map_or(0, |r| {
+                        r.restarted();
+                        r.attempts
+                    });
+                    drop(guard);
+                    log::info!("Namespace manager for {} restarted", ns_name);
+                    self.push_supervision_event(SupervisionEventKind::NSManagerRestarted {
+                        ns_uuid,
+                        attempt,
+                    })
+                    .await;
+                }
+                Err(e) => {
+                    log::error!("Unable to restart namespace manager for {}: {}", ns_name, e);
+                    let mut guard = self.state.write().await;
+                    let state = &mut *guard;
+                    if state
+                        .ns_manager_restarts
+                        .entry(ns_uuid)
+                        .or_default()
+                        .failed()
+                    {
+                        state.pending_ns_managers.insert(ns_uuid, ns_name);
+                    } else {
+                        gave_up.push((ns_uuid, ns_name, format!("{}", e)));
+                    }
+                }
+            }
+        }
+
+        for (ns_uuid, ns_name, reason) in gave_up {
+            self.ns_manager_failed(ns_uuid, ns_name, reason).await;
+        }
+    }
+
+    /// Marks the namespace failed once its manager is no longer restarted,
+    /// the failure is added to the virtual networks using the namespace
+    async fn ns_manager_failed(&self, ns_uuid: Uuid, ns_name: String, reason: String) {
+        log::error!(
+            "Namespace manager for {} failed after {} restarts, not restarting it: {}",
+            ns_name,
+            SUPERVISION_MAX_RESTARTS,
+            reason
+        );
+        let mut guard = self.state.write().await;
+        guard.ns_manager_restarts.remove(&ns_uuid);
+        guard.failed_ns_managers.insert(ns_uuid, reason.clone());
+        drop(guard);
+
+        let failure = format!("netns {}: manager failed, {}", ns_name, reason);
+        match self.store.get_all_virtual_networks().await {
+            Ok(vnets) => {
+                for vnet in vnets {
+                    let uses_ns = match &vnet.plugin_internals {
+                        Some(raw) => deserialize_network_internals(raw)
+                            .ok()
+                            .and_then(|internals| internals.associated_netns)
+                            .map_or(false, |ns_info| ns_info.ns_uuid == ns_uuid),
+                        None => false,
+                    };
+                    if !uses_ns {
+                        continue;
+                    }
+                    let failure = failure.clone();
+                    if let Err(e) = self
+                        .update_vnet_failures(vnet.uuid, |failures| {
+                            if !failures.contains(&failure) {
+                                failures.push(failure)
+                            }
+                        })
+                        .await
+                    {
+                        log::warn!("Unable to record the failure of {}: {}", ns_name, e);
+                    }
+                }
+            }
+            Err(e) => log::warn!("Unable to record the failure of {}: {}", ns_name, e),
+        }
+        self.push_supervision_event(SupervisionEventKind::NSManagerFailed { ns_uuid, reason })
+            .await;
+    }
+
+    /// Creates a L2 virtual network, made of:
+    /// 1 - A bridge in the default namespace
+    /// 2 - The uplink interface attached to the bridge, VXLAN or VLAN
+    ///     depending on the transport
+    /// 3 - A network namespace with an internal bridge
+    /// 4 - A veth pair connecting the two bridges
+    /// If a step fails the completed ones are undone in reverse order.
+    async fn vnet_create(
+        &self,
+        vnet: VirtualNetwork,
+        transport: VNetTransport,
+    ) -> FResult<VirtualNetwork> {
+        let mut undo = Vec::new();
+        match self.vnet_create_steps(vnet, transport, &mut undo).await {
+            Ok(vnet) => Ok(vnet),
+            Err(e) => {
+                log::error!("Virtual network creation failed: {}", e);
+                self.rollback(undo).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Creates the default virtual network, every completed step
+    /// is pushed into `undo`
+    async fn default_vnet_create(
+        &self,
+        dhcp: bool,
+        undo: &mut Vec<UndoStep>,
+    ) -> FResult<VirtualNetwork> {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+        let default_net_uuid = Uuid::nil();
+
+        let default_br_uuid = Uuid::nil();
+        let default_br_name = String::from("fosbr0");
+
+        let default_vxl_uuid = Uuid::new_v4();
+        let default_vxl_name = String::from("fosvxl0");
+
+        // let default_netns_uuid = Uuid::nil();
+        // let default_netns_name = String::from("fos-default");
+
+        // let default_veth_i_uuid = Uuid::new_v4();
+        // let default_veth_i_name = String::from("fveth-default-i");
+
+        // let default_veth_e_uuid = Uuid::new_v4();
+        // let default_veth_e_name = String::from("fveth-default-e");
+
+        let default_vni: u32 = 3845;
+        let default_mcast_addr = IPAddress::V4(std::net::Ipv4Addr::new(239, 15, 5, 0));
+        let default_port: u16 = 3845;
+
+        let dafault_ext_if_name = self.get_overlay_iface().await?;
+
+        let mut default_vnet = VirtualNetwork {
+            uuid: default_net_uuid,
+            id: String::from("fos-default"),
+            name: Some(String::from("Eclipse fog05 default virtual network")),
+            is_mgmt: false,
+            link_kind: LinkKind::L2(MCastVXLANInfo {
+                vni: default_vni,
+                mcast_addr: default_mcast_addr,
+                port: default_port,
+            }),
+            ip_version: IPVersion::IPV4,
+            ip_configuration: None,
+            connection_points: Vec::new(),
+            interfaces: vec![
+                default_br_uuid,
+                default_vxl_uuid,
+                // default_veth_i_uuid,
+                // default_veth_e_uuid,
+            ],
+            plugin_internals: None,
+        };
+
+        // let mut default_netns = NetworkNamespace {
+        //     uuid: default_netns_uuid,
+        //     ns_name: String::from("fos-default"),
+        //     interfaces: vec![default_veth_e_uuid],
+        // };
+
+        if dhcp {
+            let ip_conf = IPConfiguration {
+                subnet: Some((IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 0)), 16)),
+                gateway: Some(IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 1))),
+                dhcp_range: Some((
+                    IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 2)),
+                    IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 255, 254)),
+                )),
+                dns: Some(vec![
+                    IPAddress::V4(std::net::Ipv4Addr::new(208, 67, 222, 222)),
+                    // IPAddress::V4(std::net::Ipv4Addr::new(208, 67, 222, 220)),
+                ]),
+            };
+            default_vnet.ip_configuration = Some(ip_conf);
+        }
+
+        let v_bridge = VirtualInterface {
+            uuid: default_br_uuid,
+            if_name: default_br_name.clone(),
+            net_ns: None,
             parent: None,
             kind: VirtualInterfaceKind::BRIDGE(BridgeKind {
-                childs: vec![internal_veth_uuid],
+                childs: vec![default_vxl_uuid],
             }),
             addresses: Vec::new(),
             phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
         };
 
-        let vxl_iface = VirtualInterface {
-            uuid: vxl_uuid,
-            if_name: vxl_name.clone(),
+        let res = self.nl.create_bridge(default_br_name.clone()).await?;
+        undo.push(UndoStep::Iface(default_br_name.clone()));
+        log::trace!("Bridge creation res: {:?}", res);
+        self.nl.set_iface_up(default_br_name.clone()).await?;
+
+        let v_vxl = VirtualInterface {
+            uuid: default_vxl_uuid,
+            if_name: default_vxl_name.clone(),
             net_ns: None,
-            parent: Some(br_uuid),
+            parent: Some(default_br_uuid),
             kind: VirtualInterfaceKind::VXLAN(VXLANKind {
-                vni: vxlan_info.vni,
-                port: vxlan_info.port,
-                mcast_addr: vxlan_info.mcast_addr,
+                vni: default_vni,
+                mcast_addr: default_mcast_addr,
+                port: default_port,
                 dev: Interface {
-                    if_name: self.get_overlay_iface().await?,
+                    if_name: dafault_ext_if_name.clone(),
                     kind: InterfaceKind::ETHERNET,
                     addresses: Vec::new(),
                     phy_address: None,
@@ -2096,9 +2801,267 @@ impl LinuxNetwork {
             phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
         };
 
-        let v_veth_i = VirtualInterface {
-            uuid: internal_veth_uuid,
-            if_name: internal_veth_name.clone(),
+        let transport = VNetTransport::MCastVXLAN {
+            vni: default_vni,
+            mcast_addr: default_mcast_addr,
+            port: default_port,
+        };
+
+        let res = self
+            .create_transport_vxlan(
+                default_vxl_name.clone(),
+                dafault_ext_if_name.clone(),
+                &transport,
+                undo,
+            )
+            .await?;
+
+        log::trace!("VXLAN creation res: {:?}", res);
+        // Setting master for VXLAN interface and setting interface up
+        self.nl
+            .set_iface_master(default_vxl_name.clone(), default_br_name.clone())
+            .await?;
+        self.nl.set_iface_up(default_vxl_name).await?;
+
+        // Adding address to bridge interface
+        self.nl
+            .add_iface_address(
+                default_br_name.clone(),
+                IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 1)),
+                16,
+            )
+            .await?;
+
+        // Creating dnsmasq config
+        let dhcp_internal = if dhcp {
+            let dhcp_internal = self
+                .start_vnet_dhcp(
+                    default_net_uuid,
+                    DEFAULT_VNET_DHCP_NAME,
+                    None,
+                    VNetDHCPSettings {
+                        iface: default_br_name.clone(),
+                        dhcp_start: IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 2)),
+                        dhcp_end: IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 255, 254)),
+                        gateway: Some(IPAddress::V4(std::net::Ipv4Addr::new(10, 240, 0, 1))),
+                        dns: vec![IPAddress::V4(std::net::Ipv4Addr::new(208, 67, 222, 222))],
+                        options: self.config.dhcp_options.clone().unwrap_or_default(),
+                    },
+                )
+                .await?;
+            undo.push(UndoStep::DHCP(dhcp_internal.clone()));
+            Some(dhcp_internal)
+        } else {
+            None
+        };
+
+        // let v_veth_i = VirtualInterface {
+        //     uuid: default_veth_i_uuid,
+        //     if_name: default_veth_i_name.clone(),
+        //     net_ns: None,
+        //     parent: Some(default_br_uuid),
+        //     kind: VirtualInterfaceKind::VETH(VETHKind {
+        //         pair: default_veth_e_uuid,
+        //         internal: true,
+        //     }),
+        //     addresses: Vec::new(),
+        //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        // };
+
+        // let v_veth_e = VirtualInterface {
+        //     uuid: default_veth_e_uuid,
+        //     if_name: default_veth_e_name.clone(),
+        //     net_ns: Some(default_netns_uuid),
+        //     parent: None,
+        //     kind: VirtualInterfaceKind::VETH(VETHKind {
+        //         pair: default_veth_i_uuid,
+        //         internal: false,
+        //     }),
+        //     addresses: Vec::new(),
+        //     phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        // };
+
+        // let res = self
+        //     .create_veth(default_veth_i_name.clone(), default_veth_e_name.clone())
+        //     .await?;
+        // log::trace!("VEth Pair creation res: {:?}", res);
+
+        // self.nl.set_iface_master(default_veth_i_name.clone(), default_br_name.clone())
+        //     .await?;
+        // self.nl.set_iface_up(default_veth_i_name).await?;
+
+        // let res = self.nl.add_netns(default_netns_name.clone()).await?;
+        // log::trace!("Netns creation res: {:?}", res);
+
+        // Here we spawn the manager for the just created Namespace and
+        // we add it to the map of managers
+        // let mut guard = self.state.write().await;
+        // let child = Command::new("fos-net-linux-ns-manager")
+        //     .arg("--netns")
+        //     .arg(&default_netns_name)
+        //     .arg("--id")
+        //     .arg(format!("{}", default_netns_uuid))
+        //     .arg("--locator")
+        //     .arg("unixsock-stream//tmp/zenoh.sock")
+        //     .spawn()
+        //     .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+        // let ns_manager_client = NamespaceManagerClient::new(self.z.clone(), default_netns_uuid);
+        // guard
+        //     .ns_managers
+        //     .insert(default_netns_uuid, (child.id(), ns_manager_client));
+        // drop(guard);
+
+        // let res = self.nl.set_iface_up(default_veth_e_name.clone()).await?;
+        // log::trace!("veth ext face up res: {:?}", res);
+        // let res = self
+        //     .set_iface_ns(default_veth_e_name.clone(), default_netns_name.clone())
+        //     .await?;
+        // log::trace!("veth ext netns set res: {:?}", res);
+
+        // Setting the firewall to NAT
+        // using nftables so add as dependencies:
+        // nftables libnftnl-dev libnfnetlink-dev libmnl-dev
+        // rule is similar to
+        // table ip nat { # handle 3
+        // 	chain postrouting { # handle 1
+        // 		type nat hook postrouting priority srcnat; policy accept;
+        // 		ip saddr 10.240.0.0/16 oif "eno0" masquerade # handle 4
+        // 	}
+        // }
+        let nat_table = self
+            .configure_nat(
+                &default_net_uuid,
+                &[IpNetwork::V4(
+                    ipnetwork::Ipv4Network::new(std::net::Ipv4Addr::new(10, 240, 0, 0), 16)
+                        .map_err(|e| FError::NetworkingError(format!("{}", e)))?,
+                )],
+                &self.get_overlay_face_from_config().await?.if_name,
+            )
+            .await?;
+        undo.push(UndoStep::NAT(nat_table.clone()));
+
+        self.store_new_interface(&v_bridge).await?;
+        undo.push(UndoStep::StoreInterface(default_br_uuid));
+
+        self.store_new_interface(&v_vxl).await?;
+        undo.push(UndoStep::StoreInterface(default_vxl_uuid));
+
+        let internals = VirtualNetworkInternals {
+            // associated_netns_name: default_netns_name,
+            associated_netns: None,
+            dhcp: dhcp_internal,
+            associated_tables: vec![nat_table],
+            transport: Some(transport),
+            failures: Vec::new(),
+            reservations: Vec::new(),
+        };
+
+        default_vnet.plugin_internals = Some(serialize_network_internals(&internals)?);
+
+        self.store.add_virtual_network(&default_vnet).await?;
+        Ok(default_vnet)
+    }
+
+    async fn vnet_create_steps(
+        &self,
+        mut vnet: VirtualNetwork,
+        transport: VNetTransport,
+        undo: &mut Vec<UndoStep>,
+    ) -> FResult<VirtualNetwork> {
+        let node_uuid = self.agent.as_ref().unwrap().get_node_uuid().await?;
+
+        // Generating Names
+
+        let br_uuid = Uuid::new_v4();
+        let br_name = self.generate_interface_name(&br_uuid).await?;
+
+        let uplink_uuid = Uuid::new_v4();
+        let uplink_name = self.generate_interface_name(&uplink_uuid).await?;
+
+        let internal_br_uuid = Uuid::new_v4();
+        let internal_br_name = self.generate_interface_name(&internal_br_uuid).await?;
+
+        let internal_veth_uuid = Uuid::new_v4();
+        let internal_veth_name = self.generate_interface_name(&internal_veth_uuid).await?;
+
+        let external_veth_uuid = Uuid::new_v4();
+        let external_veth_name = self.generate_interface_name(&external_veth_uuid).await?;
+
+        let mut associated_ns = NetworkNamespace {
+            uuid: vnet.uuid,
+            ns_name: self.generate_netns_name(&vnet.uuid).await?,
+            interfaces: vec![
+                external_veth_uuid,
+                internal_veth_uuid,
+                internal_br_uuid,
+                uplink_uuid,
+                br_uuid,
+            ],
+        };
+
+        // Generating Structs
+
+        let mut v_bridge = VirtualInterface {
+            uuid: br_uuid,
+            if_name: br_name.clone(),
+            net_ns: None,
+            parent: None,
+            kind: VirtualInterfaceKind::BRIDGE(BridgeKind {
+                childs: vec![external_veth_uuid, uplink_uuid],
+            }),
+            addresses: Vec::new(),
+            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        };
+
+        let mut v_internal_bridge = VirtualInterface {
+            uuid: internal_br_uuid,
+            if_name: internal_br_name.clone(),
+            net_ns: Some(associated_ns.uuid),
+            parent: None,
+            kind: VirtualInterfaceKind::BRIDGE(BridgeKind {
+                childs: vec![internal_veth_uuid],
+            }),
+            addresses: Vec::new(),
+            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        };
+
+        let uplink_kind = match &transport {
+            VNetTransport::MCastVXLAN {
+                vni,
+                mcast_addr,
+                port,
+            } => VirtualInterfaceKind::VXLAN(VXLANKind {
+                vni: *vni,
+                port: *port,
+                mcast_addr: *mcast_addr,
+                dev: self.get_overlay_face_from_config().await?,
+            }),
+            // the peers are kept in the transport of the internals
+            VNetTransport::UCastVXLAN { vni, port, .. } => VirtualInterfaceKind::VXLAN(VXLANKind {
+                vni: *vni,
+                port: *port,
+                mcast_addr: IPAddress::V4(std::net::Ipv4Addr::UNSPECIFIED),
+                dev: self.get_overlay_face_from_config().await?,
+            }),
+            VNetTransport::VLAN { tag, .. } => VirtualInterfaceKind::VLAN(VLANKind {
+                tag: *tag,
+                dev: self.get_dataplane_from_config().await?,
+            }),
+        };
+
+        let uplink_iface = VirtualInterface {
+            uuid: uplink_uuid,
+            if_name: uplink_name.clone(),
+            net_ns: None,
+            parent: Some(br_uuid),
+            kind: uplink_kind,
+            addresses: Vec::new(),
+            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        };
+
+        let v_veth_i = VirtualInterface {
+            uuid: internal_veth_uuid,
+            if_name: internal_veth_name.clone(),
             net_ns: Some(associated_ns.uuid),
             parent: Some(internal_br_uuid),
             kind: VirtualInterfaceKind::VETH(VETHKind {
@@ -2122,1062 +3085,2351 @@ impl LinuxNetwork {
             phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
         };
 
-        // Creating Virtual network bridge
+        // Creating Virtual network bridge
+
+        self.nl.create_bridge(br_name.clone()).await?;
+        undo.push(UndoStep::Iface(br_name.clone()));
+        self.store_new_interface(&v_bridge).await?;
+        undo.push(UndoStep::StoreInterface(br_uuid));
+
+        vnet.interfaces.push(br_uuid);
+
+        self.nl.set_iface_up(br_name.clone()).await?;
+
+        // Creating the uplink Interface
+
+        match &transport {
+            VNetTransport::VLAN { tag, dev } => {
+                self.nl
+                    .create_vlan(uplink_name.clone(), dev.clone(), *tag)
+                    .await?;
+                undo.push(UndoStep::Iface(uplink_name.clone()));
+            }
+            _ => {
+                self.create_transport_vxlan(
+                    uplink_name.clone(),
+                    self.get_overlay_iface().await?,
+                    &transport,
+                    undo,
+                )
+                .await?
+            }
+        }
+        self.store_new_interface(&uplink_iface).await?;
+        undo.push(UndoStep::StoreInterface(uplink_uuid));
+
+        vnet.interfaces.push(uplink_uuid);
+
+        self.nl
+            .set_iface_master(uplink_name.clone(), br_name.clone())
+            .await?;
+        self.nl.set_iface_up(uplink_name).await?;
+
+        // Creating netns and spawing the namespace manager
+        self.nl.add_netns(associated_ns.ns_name.clone()).await?;
+        undo.push(UndoStep::Netns(associated_ns.ns_name.clone()));
+        self.spawn_ns_manager(associated_ns.ns_name.clone(), associated_ns.uuid)
+            .await?;
+        undo.push(UndoStep::NsManager(associated_ns.uuid));
+
+        self.store.add_network_namespace(&associated_ns).await?;
+        undo.push(UndoStep::StoreNetns(associated_ns.uuid));
+
+        // Creating veth pair
+        self.nl
+            .create_veth(external_veth_name.clone(), internal_veth_name.clone())
+            .await?;
+        // removing the external end removes the pair
+        undo.push(UndoStep::Iface(external_veth_name.clone()));
+
+        self.store_new_interface(&v_veth_e).await?;
+        undo.push(UndoStep::StoreInterface(external_veth_uuid));
+
+        vnet.interfaces.push(internal_veth_uuid);
+
+        self.store_new_interface(&v_veth_i).await?;
+        undo.push(UndoStep::StoreInterface(internal_veth_uuid));
+
+        vnet.interfaces.push(external_veth_uuid);
+
+        self.nl
+            .set_iface_master(external_veth_name.clone(), br_name.clone())
+            .await?;
+        self.nl.set_iface_up(external_veth_name).await?;
+
+        self.nl
+            .set_iface_ns(
+                internal_veth_name.clone(),
+                associated_ns.ns_name.clone().clone(),
+            )
+            .await?;
+
+        // create internal bridge
+        let ns_manager = self.get_ns_manager(&associated_ns.uuid).await?;
+
+        ns_manager
+            .set_virtual_interface_up("lo".to_string())
+            .await?;
+
+        ns_manager
+            .add_virtual_interface_bridge(internal_br_name.clone())
+            .await?;
+
+        ns_manager
+            .set_virtual_interface_up(internal_br_name.clone())
+            .await?;
+
+        vnet.interfaces.push(internal_br_uuid);
+
+        self.store_new_interface(&v_internal_bridge).await?;
+        undo.push(UndoStep::StoreInterface(internal_br_uuid));
+
+        ns_manager
+            .set_virtual_interface_master(internal_veth_name.clone(), internal_br_name.clone())
+            .await?;
+
+        ns_manager
+            .set_virtual_interface_up(internal_veth_name.clone())
+            .await?;
+
+        // Gateway and NAT configuration
+        // the gateway address is assigned to the virtual network bridge
+        // and traffic from the network is masqueraded toward the uplink
+        let mut associated_tables = Vec::new();
+        if let Some(IPConfiguration {
+            subnet: Some((subnet_addr, prefix)),
+            gateway: Some(gateway),
+            ..
+        }) = vnet.ip_configuration
+        {
+            self.nl
+                .add_iface_address(br_name.clone(), gateway, prefix)
+                .await?;
+            v_bridge.addresses.push(gateway);
+            self.store.add_interface(&v_bridge).await?;
+
+            let subnet = IpNetwork::new(subnet_addr, prefix)
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+            let nat_table = self
+                .configure_nat(
+                    &vnet.uuid,
+                    &[subnet],
+                    &self.get_overlay_face_from_config().await?.if_name,
+                )
+                .await?;
+            undo.push(UndoStep::NAT(nat_table.clone()));
+            associated_tables.push(nat_table);
+        }
+
+        // DHCP configuration and spawn
+
+        let dhcp_internal = match &vnet.ip_configuration {
+            Some(IPConfiguration {
+                subnet: Some((_, prefix)),
+                gateway,
+                dhcp_range: Some((range_start, range_end)),
+                dns,
+            }) => {
+                // The DHCP server takes the first address of the range
+                // and it is assigned to the internal bridge
+                let server_addr = IpNetwork::new(*range_start, *prefix)
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+                let addresses = ns_manager
+                    .add_virtual_interface_address(internal_br_name.clone(), Some(server_addr))
+                    .await?;
+                v_internal_bridge.addresses = addresses;
+                self.store.add_interface(&v_internal_bridge).await?;
+
+                let dhcp = self
+                    .start_vnet_dhcp(
+                        vnet.uuid,
+                        &format!("{}", vnet.uuid),
+                        Some(&associated_ns.ns_name),
+                        VNetDHCPSettings {
+                            iface: internal_br_name.clone(),
+                            dhcp_start: next_address(*range_start),
+                            dhcp_end: *range_end,
+                            gateway: *gateway,
+                            dns: dns.clone().unwrap_or_default(),
+                            options: self.config.dhcp_options.clone().unwrap_or_default(),
+                        },
+                    )
+                    .await?;
+                undo.push(UndoStep::DHCP(dhcp.clone()));
+                Some(dhcp)
+            }
+            Some(IPConfiguration {
+                dhcp_range: Some(_),
+                ..
+            }) => {
+                return Err(FError::NetworkingError(
+                    "DHCP range requires a subnet".to_string(),
+                ))
+            }
+            _ => None,
+        };
+
+        let ns_info = Some(VNetNetns {
+            ns_name: associated_ns.ns_name.clone(),
+            ns_uuid: associated_ns.uuid,
+        });
+
+        let internals = VirtualNetworkInternals {
+            associated_netns: ns_info,
+            dhcp: dhcp_internal,
+            associated_tables,
+            transport: Some(transport),
+            failures: Vec::new(),
+            reservations: Vec::new(),
+        };
+        vnet.plugin_internals = Some(serialize_network_internals(&internals)?);
+        self.store.add_virtual_network(&vnet).await?;
+        Ok(vnet)
+    }
+
+    /// Creates a connection point, every completed step is pushed into `undo`
+    async fn connection_point_create_steps(
+        &self,
+        undo: &mut Vec<UndoStep>,
+    ) -> FResult<ConnectionPoint> {
+        let cp_uuid = Uuid::new_v4();
+
+        let br_uuid = Uuid::new_v4();
+        let br_name = self.generate_interface_name(&br_uuid).await?;
+
+        let internal_veth_uuid = Uuid::new_v4();
+        let internal_veth_name = self.generate_interface_name(&internal_veth_uuid).await?;
+
+        let external_veth_uuid = Uuid::new_v4();
+        let external_veth_name = self.generate_interface_name(&external_veth_uuid).await?;
+
+        // Creating the namespace, this also spawns the namespace manager
+        let mut netns = self.create_network_namespace().await?;
+        undo.push(UndoStep::Netns(netns.ns_name.clone()));
+        undo.push(UndoStep::NsManager(netns.uuid));
+        undo.push(UndoStep::StoreNetns(netns.uuid));
+        let ns_manager = self.get_ns_manager(&netns.uuid).await?;
+
+        let mut v_bridge = VirtualInterface {
+            uuid: br_uuid,
+            if_name: br_name.clone(),
+            net_ns: Some(netns.uuid),
+            parent: None,
+            kind: VirtualInterfaceKind::BRIDGE(BridgeKind {
+                childs: vec![internal_veth_uuid],
+            }),
+            addresses: Vec::new(),
+            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        };
+
+        let v_veth_i = VirtualInterface {
+            uuid: internal_veth_uuid,
+            if_name: internal_veth_name.clone(),
+            net_ns: Some(netns.uuid),
+            parent: Some(br_uuid),
+            kind: VirtualInterfaceKind::VETH(VETHKind {
+                pair: external_veth_uuid,
+                internal: true,
+            }),
+            addresses: Vec::new(),
+            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        };
+
+        let v_veth_e = VirtualInterface {
+            uuid: external_veth_uuid,
+            if_name: external_veth_name.clone(),
+            net_ns: None,
+            parent: None,
+            kind: VirtualInterfaceKind::VETH(VETHKind {
+                pair: internal_veth_uuid,
+                internal: false,
+            }),
+            addresses: Vec::new(),
+            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        };
+
+        // Creating the bridge inside the namespace
+        ns_manager
+            .add_virtual_interface_bridge(br_name.clone())
+            .await?;
+        self.store_new_interface(&v_bridge).await?;
+        undo.push(UndoStep::StoreInterface(br_uuid));
+        netns.interfaces.push(br_uuid);
+
+        // Creating the veth pair and moving the internal end into the namespace
+        self.nl
+            .create_veth(internal_veth_name.clone(), external_veth_name.clone())
+            .await?;
+        // removing the external end removes the pair
+        undo.push(UndoStep::Iface(external_veth_name.clone()));
+        self.nl.set_iface_up(external_veth_name.clone()).await?;
+        self.nl
+            .set_iface_ns(internal_veth_name.clone(), netns.ns_name.clone())
+            .await?;
+
+        ns_manager
+            .set_virtual_interface_master(internal_veth_name.clone(), br_name.clone())
+            .await?;
+        ns_manager
+            .set_virtual_interface_up(internal_veth_name.clone())
+            .await?;
+
+        self.store_new_interface(&v_veth_i).await?;
+        undo.push(UndoStep::StoreInterface(internal_veth_uuid));
+        self.store_new_interface(&v_veth_e).await?;
+        undo.push(UndoStep::StoreInterface(external_veth_uuid));
+        netns.interfaces.push(internal_veth_uuid);
+        self.store.add_network_namespace(&netns).await?;
+
+        let cp = ConnectionPoint {
+            uuid: cp_uuid,
+            net_ns: netns.uuid,
+            bridge: br_uuid,
+            internal_veth: internal_veth_uuid,
+            external_veth: external_veth_uuid,
+        };
+        self.store.add_connection_point(&cp).await?;
+        Ok(cp)
+    }
+
+    /// Undoes the given steps in reverse order, errors are only logged
+    /// as the remaining steps have to be undone anyway
+    async fn rollback(&self, undo: Vec<UndoStep>) {
+        for step in undo.into_iter().rev() {
+            log::debug!("Rolling back {:?}", step);
+            let res = match &step {
+                UndoStep::Iface(iface) => self.nl.del_iface(iface.clone()).await,
+                UndoStep::StoreInterface(uuid) => self.store.remove_interface(*uuid).await,
+                UndoStep::Netns(ns_name) => self.nl.del_netns(ns_name.clone()).await,
+                UndoStep::NsManager(ns_uuid) => self.kill_ns_manager(ns_uuid).await,
+                UndoStep::StoreNetns(ns_uuid) => {
+                    self.store.remove_network_namespace(*ns_uuid).await
+                }
+                UndoStep::DHCP(dhcp) => self.stop_vnet_dhcp(dhcp).await,
+                UndoStep::NAT(table) => self.clean_nat(table.clone()).await,
+                UndoStep::FDB(iface, dst) => self.nl.del_fdb_entry(iface.clone(), *dst).await,
+            };
+            if let Err(e) = res {
+                log::warn!("Unable to roll back {:?}: {}", step, e);
+            }
+        }
+    }
+
+    /// Returns the transport of a L2 virtual network according to its
+    /// settings, with VLAN the tag of the settings is used on the
+    /// dataplane interface
+    async fn get_l2_transport(
+        &self,
+        info: &MCastVXLANInfo,
+        settings: &VirtualNetworkSettings,
+    ) -> FResult<VNetTransport> {
+        match settings.l2_transport {
+            L2Transport::VXLAN => Ok(self.get_vxlan_transport(info, &settings.vxlan_peers)),
+            L2Transport::VLAN => match settings.vlan_tag {
+                Some(tag) if tag > 0 && tag < 4095 => Ok(VNetTransport::VLAN {
+                    tag,
+                    dev: self.get_dataplane_from_config().await?.if_name,
+                }),
+                Some(tag) => Err(FError::NetworkingError(format!(
+                    "{} is not a valid VLAN tag",
+                    tag
+                ))),
+                None => Err(FError::NetworkingError(
+                    "VLAN transport requested without a VLAN tag".to_string(),
+                )),
+            },
+        }
+    }
+
+    /// Returns the VXLAN transport of a L2 virtual network, when the
+    /// network has peers unicast is used instead of the multicast group
+    fn get_vxlan_transport(
+        &self,
+        vxlan_info: &MCastVXLANInfo,
+        peers: &[IPAddress],
+    ) -> VNetTransport {
+        match peers {
+            [] => VNetTransport::MCastVXLAN {
+                vni: vxlan_info.vni,
+                mcast_addr: vxlan_info.mcast_addr,
+                port: vxlan_info.port,
+            },
+            _ => VNetTransport::UCastVXLAN {
+                vni: vxlan_info.vni,
+                port: vxlan_info.port,
+                peers: peers.to_vec(),
+            },
+        }
+    }
+
+    /// Creates the VXLAN interface for the given transport, every
+    /// completed step is pushed into `undo`.
+    /// With unicast the interface is created toward the first peer
+    /// and an all-zeros FDB entry is appended for each other peer,
+    /// so BUM traffic is replicated to all of them.
+    async fn create_transport_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        transport: &VNetTransport,
+        undo: &mut Vec<UndoStep>,
+    ) -> FResult<()> {
+        match transport {
+            VNetTransport::MCastVXLAN {
+                vni,
+                mcast_addr,
+                port,
+            } => {
+                self.nl
+                    .create_mcast_vxlan(iface.clone(), dev, *vni, *mcast_addr, *port)
+                    .await?;
+                undo.push(UndoStep::Iface(iface));
+                Ok(())
+            }
+            VNetTransport::UCastVXLAN { vni, port, peers } => {
+                let local_addresses = self.nl.get_iface_addresses(dev.clone()).await?;
+                // the same peer list can be shared by all nodes
+                let peers: Vec<IPAddress> = peers
+                    .iter()
+                    .filter(|p| !local_addresses.contains(p))
+                    .copied()
+                    .collect();
+                let (first, others) = peers.split_first().ok_or_else(|| {
+                    FError::NetworkingError("No remote VXLAN peers configured".to_string())
+                })?;
+                let local_addr = local_addresses
+                    .iter()
+                    .find(|a| a.is_ipv4() == first.is_ipv4())
+                    .copied()
+                    .ok_or_else(|| {
+                        FError::NetworkingError(format!(
+                            "{} has no address of the same family of {}",
+                            dev, first
+                        ))
+                    })?;
+                self.nl
+                    .create_ptp_vxlan(iface.clone(), dev, *vni, local_addr, *first, *port)
+                    .await?;
+                undo.push(UndoStep::Iface(iface.clone()));
+                for peer in others {
+                    self.nl.append_fdb_entry(iface.clone(), *peer).await?;
+                    undo.push(UndoStep::FDB(iface.clone(), *peer));
+                }
+                Ok(())
+            }
+            VNetTransport::VLAN { .. } => Err(FError::WrongKind),
+        }
+    }
+
+    /// Creates the links of a virtual interface and returns its records,
+    /// the interface first and then the other end for veth pairs.
+    /// In a namespace bridges and veth pairs are created by its manager,
+    /// interfaces that depend on a device of the default namespace
+    /// (VXLAN, VLAN, MACVLAN and GRE tunnels) are created in the default
+    /// namespace, where their lower device or underlay lives,
+    /// and then moved into the namespace.
+    async fn create_interface_links(
+        &self,
+        intf: VirtualInterfaceConfig,
+        netns: Option<&NetworkNamespace>,
+    ) -> FResult<Vec<VirtualInterface>> {
+        let net_ns = netns.map(|ns| ns.uuid);
+        let if_name = intf.if_name;
+        let new_iface = |kind: VirtualInterfaceKind| VirtualInterface {
+            uuid: Uuid::new_v4(),
+            if_name: if_name.clone(),
+            net_ns,
+            parent: None,
+            kind,
+            addresses: Vec::new(),
+            phy_address: MACAddress::new(0, 0, 0, 0, 0, 0),
+        };
+        let iface = match intf.kind {
+            VirtualInterfaceConfigKind::BRIDGE => {
+                match netns {
+                    Some(netns) => {
+                        self.get_ns_manager(&netns.uuid)
+                            .await?
+                            .add_virtual_interface_bridge(if_name.clone())
+                            .await?
+                    }
+                    None => self.nl.create_bridge(if_name.clone()).await?,
+                }
+                // created where it belongs, nothing to move
+                return Ok(vec![new_iface(VirtualInterfaceKind::BRIDGE(BridgeKind {
+                    childs: Vec::new(),
+                }))]);
+            }
+            VirtualInterfaceConfigKind::VETH => {
+                let internal_iface_uuid = Uuid::new_v4();
+                let external_iface_uuid = Uuid::new_v4();
+                let external_face_name = self.generate_interface_name(&external_iface_uuid).await?;
+                match netns {
+                    Some(netns) => {
+                        self.get_ns_manager(&netns.uuid)
+                            .await?
+                            .add_virtual_interface_veth(if_name.clone(), external_face_name.clone())
+                            .await?
+                    }
+                    None => {
+                        self.nl
+                            .create_veth(if_name.clone(), external_face_name.clone())
+                            .await?
+                    }
+                }
+                let v_iface_internal = VirtualInterface {
+                    uuid: internal_iface_uuid,
+                    ..new_iface(VirtualInterfaceKind::VETH(VETHKind {
+                        pair: external_iface_uuid,
+                        internal: true,
+                    }))
+                };
+                let v_iface_external = VirtualInterface {
+                    uuid: external_iface_uuid,
+                    if_name: external_face_name,
+                    kind: VirtualInterfaceKind::VETH(VETHKind {
+                        pair: internal_iface_uuid,
+                        internal: false,
+                    }),
+                    ..v_iface_internal.clone()
+                };
+                return Ok(vec![v_iface_internal, v_iface_external]);
+            }
+            VirtualInterfaceConfigKind::VXLAN(conf) => {
+                let ext_face = self.get_overlay_face_from_config().await?;
+                self.nl
+                    .create_mcast_vxlan(
+                        if_name.clone(),
+                        ext_face.if_name.clone(),
+                        conf.vni,
+                        conf.mcast_addr,
+                        conf.port,
+                    )
+                    .await?;
+                new_iface(VirtualInterfaceKind::VXLAN(VXLANKind {
+                    vni: conf.vni,
+                    mcast_addr: conf.mcast_addr,
+                    port: conf.port,
+                    dev: ext_face,
+                }))
+            }
+            VirtualInterfaceConfigKind::VLAN(conf) => {
+                let ext_face = self.get_dataplane_from_config().await?;
+                self.nl
+                    .create_vlan(if_name.clone(), ext_face.if_name.clone(), conf.tag)
+                    .await?;
+                new_iface(VirtualInterfaceKind::VLAN(VLANKind {
+                    tag: conf.tag,
+                    dev: ext_face,
+                }))
+            }
+            VirtualInterfaceConfigKind::MACVLAN => {
+                let ext_face = self.get_dataplane_from_config().await?;
+                self.nl
+                    .create_macvlan(
+                        if_name.clone(),
+                        ext_face.if_name.clone(),
+                        self.get_macvlan_mode(),
+                    )
+                    .await?;
+                new_iface(VirtualInterfaceKind::MACVLAN(MACVLANKind { dev: ext_face }))
+            }
+            VirtualInterfaceConfigKind::GRE(conf) => {
+                self.nl
+                    .create_gre(
+                        if_name.clone(),
+                        GRETunnelKind::GRE,
+                        conf.local_addr,
+                        conf.remote_addr,
+                        conf.ttl,
+                    )
+                    .await?;
+                new_iface(VirtualInterfaceKind::GRE(GREKind {
+                    local_addr: conf.local_addr,
+                    remote_addr: conf.remote_addr,
+                    ttl: conf.ttl,
+                }))
+            }
+            VirtualInterfaceConfigKind::GRETAP(conf) => {
+                self.nl
+                    .create_gre(
+                        if_name.clone(),
+                        GRETunnelKind::GRETAP,
+                        conf.local_addr,
+                        conf.remote_addr,
+                        conf.ttl,
+                    )
+                    .await?;
+                new_iface(VirtualInterfaceKind::GRETAP(GREKind {
+                    local_addr: conf.local_addr,
+                    remote_addr: conf.remote_addr,
+                    ttl: conf.ttl,
+                }))
+            }
+            VirtualInterfaceConfigKind::IP6GRE(conf) => {
+                self.nl
+                    .create_gre(
+                        if_name.clone(),
+                        GRETunnelKind::IP6GRE,
+                        conf.local_addr,
+                        conf.remote_addr,
+                        conf.ttl,
+                    )
+                    .await?;
+                new_iface(VirtualInterfaceKind::IP6GRE(GREKind {
+                    local_addr: conf.local_addr,
+                    remote_addr: conf.remote_addr,
+                    ttl: conf.ttl,
+                }))
+            }
+            VirtualInterfaceConfigKind::IP6GRETAP(conf) => {
+                self.nl
+                    .create_gre(
+                        if_name.clone(),
+                        GRETunnelKind::IP6GRETAP,
+                        conf.local_addr,
+                        conf.remote_addr,
+                        conf.ttl,
+                    )
+                    .await?;
+                new_iface(VirtualInterfaceKind::IP6GRETAP(GREKind {
+                    local_addr: conf.local_addr,
+                    remote_addr: conf.remote_addr,
+                    ttl: conf.ttl,
+                }))
+            }
+        };
+        if let Some(netns) = netns {
+            self.move_iface_into_netns(if_name, netns).await?;
+        }
+        Ok(vec![iface])
+    }
+
+    /// Moves an interface from the default namespace into the given one
+    /// and sets it up, the interface is removed if it cannot be moved.
+    async fn move_iface_into_netns(&self, iface: String, netns: &NetworkNamespace) -> FResult<()> {
+        if let Err(e) = self
+            .nl
+            .set_iface_ns(iface.clone(), netns.ns_name.clone())
+            .await
+        {
+            log::error!("Unable to move {} into {}: {}", iface, netns.ns_name, e);
+            self.nl.del_iface(iface).await?;
+            return Err(e);
+        }
+        let ns_manager = self.get_ns_manager(&netns.uuid).await?;
+        ns_manager.set_virtual_interface_up(iface).await
+    }
+
+    /// Stores the interface and adds it to the namespace interfaces
+    async fn add_interface_to_netns(
+        &self,
+        netns: &mut NetworkNamespace,
+        iface: &VirtualInterface,
+    ) -> FResult<()> {
+        netns.interfaces.push(iface.uuid);
+        self.store.add_network_namespace(netns).await?;
+        self.store_new_interface(iface).await
+    }
+
+    async fn get_overlay_face_from_config(&self) -> FResult<Interface> {
+        let iface = self.config.overlay_iface.as_ref().ok_or(FError::NotFound)?;
+        let addresses = self.nl.get_iface_addresses(iface.clone()).await?;
+        Ok(Interface {
+            if_name: iface.to_string(),
+            kind: InterfaceKind::ETHERNET,
+            addresses,
+            phy_address: None,
+        })
+    }
+
+    async fn get_dataplane_from_config(&self) -> FResult<Interface> {
+        let iface = self
+            .config
+            .dataplane_iface
+            .as_ref()
+            .ok_or(FError::NotFound)?;
+        let addresses = self.nl.get_iface_addresses(iface.clone()).await?;
+        Ok(Interface {
+            if_name: iface.to_string(),
+            kind: InterfaceKind::ETHERNET,
+            addresses,
+            phy_address: None,
+        })
+    }
+
+    /// Returns the bridge of the given virtual network that lives
+    /// in the default network namespace
+    async fn get_virtual_network_bridge(&self, vnet: &VirtualNetwork) -> FResult<VirtualInterface> {
+        for iface_uuid in &vnet.interfaces {
+            let iface = self.store.get_interface(*iface_uuid).await?;
+            if let (VirtualInterfaceKind::BRIDGE(_), None) = (&iface.kind, iface.net_ns) {
+                return Ok(iface);
+            }
+        }
+        Err(FError::NotFound)
+    }
+
+    fn get_macvlan_mode(&self) -> MACVLANMode {
+        self.config.macvlan_mode.unwrap_or_default()
+    }
+
+    fn get_domain_socket_locator(&self) -> String {
+        self.config.zfilelocator.clone()
+    }
+
+    fn get_path(&self) -> Box<std::path::Path> {
+        self.config.path.clone()
+    }
+
+    fn get_run_path(&self) -> Box<std::path::Path> {
+        self.config.run_path.clone()
+    }
+
+    fn get_name_prefix(&self) -> String {
+        self.config
+            .name_prefix
+            .clone()
+            .unwrap_or_else(|| DEFAULT_NAME_PREFIX.to_string())
+    }
+
+    /// Returns the name of the interface with the given UUID:
+    /// the prefix followed by the UUID digits that fit in IFNAMSIZ.
+    /// If the name is used in the default namespace the following
+    /// digits of the UUID are tried.
+    async fn generate_interface_name(&self, uuid: &Uuid) -> FResult<String> {
+        let prefix = self.get_name_prefix();
+        let digits = format!("{}", uuid.to_simple());
+        let len = IFNAMSIZ - 1 - prefix.len();
+        for start in 0..=(digits.len() - len) {
+            let name = format!("{}{}", prefix, &digits[start..start + len]);
+            if !self.nl.iface_exists(name.clone()).await? {
+                return Ok(name);
+            }
+        }
+        Err(LinuxNetworkError::NameCollision {
+            name: format!("{}{}", prefix, digits),
+        }
+        .into())
+    }
+
+    /// Returns the name of the network namespace with the given UUID,
+    /// fails if it is already present
+    async fn generate_netns_name(&self, uuid: &Uuid) -> FResult<String> {
+        let name = format!("{}{}", self.get_netns_prefix(), uuid.to_simple());
+        if self.nl.list_netns().await?.contains(&name) {
+            return Err(LinuxNetworkError::NameCollision { name }.into());
+        }
+        Ok(name)
+    }
+
+    fn get_netns_prefix(&self) -> String {
+        format!("{}-ns-", self.get_name_prefix())
+    }
+
+    /// Checks a user supplied interface name and that it is not used
+    /// in the default namespace
+    async fn check_interface_name(&self, name: &str) -> FResult<()> {
+        validate_iface_name(name)?;
+        if self.nl.iface_exists(name.to_string()).await? {
+            return Err(LinuxNetworkError::NameCollision {
+                name: name.to_string(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Spawns the dnsmasq of a virtual network and adds it
+    /// to the supervised DHCP servers
+    async fn spawn_vnet_dhcp(
+        &self,
+        vnet_uuid: Uuid,
+        dhcp: &VNetDHCP,
+        netns: Option<String>,
+    ) -> FResult<()> {
+        let child = self
+            .dhcp_launcher
+            .launch(dhcp.conf.clone(), netns.clone())
+            .await?;
+        log::debug!("DHCP Process for {} running PID: {}", vnet_uuid, child.id());
+        let mut guard = self.state.write().await;
+        guard.dhcp_servers.insert(
+            vnet_uuid,
+            DHCPProcess {
+                child,
+                dhcp: dhcp.clone(),
+                netns,
+            },
+        );
+        Ok(())
+    }
+
+    /// Creates the dnsmasq configuration for a virtual network
+    /// and starts the DHCP server with the given settings.
+    /// Files are named after `name` inside the plugin run path.
+    async fn start_vnet_dhcp(
+        &self,
+        vnet_uuid: Uuid,
+        name: &str,
+        netns: Option<&str>,
+        settings: VNetDHCPSettings,
+    ) -> FResult<VNetDHCP> {
+        validate_options(&settings.options)?;
+        let run_path = self.get_run_path();
+        let file_path = |ext: &str| -> FResult<String> {
+            Ok(run_path
+                .join(format!("{}.{}", name, ext))
+                .to_str()
+                .ok_or(FError::EncodingError)?
+                .to_string())
+        };
+        let dhcp = VNetDHCP {
+            leases_file: file_path("leases")?,
+            pid_file: file_path("pid")?,
+            conf: file_path("conf")?,
+            log_file: file_path("log")?,
+            hosts_file: Some(file_path("hosts")?),
+            opts_file: Some(file_path("opts")?),
+            settings: Some(settings),
+        };
+
+        self.write_dnsmasq_config(&dhcp).await?;
+        if let Some(hosts_file) = &dhcp.hosts_file {
+            self.os
+                .as_ref()
+                .unwrap()
+                .store_file(Vec::new(), hosts_file.clone())
+                .await?;
+        }
+        self.spawn_vnet_dhcp(vnet_uuid, &dhcp, netns.map(String::from))
+            .await?;
+        Ok(dhcp)
+    }
+
+    /// Stops the dnsmasq of a virtual network if running and starts
+    /// it again, the leases are kept in the leases file
+    async fn restart_vnet_dhcp(
+        &self,
+        vnet_uuid: Uuid,
+        dhcp: &VNetDHCP,
+        netns: Option<String>,
+    ) -> FResult<()> {
+        self.terminate_dnsmasq(dhcp).await?;
+        self.spawn_vnet_dhcp(vnet_uuid, dhcp, netns).await
+    }
+
+    /// Writes the configuration of the dnsmasq of a virtual network,
+    /// then restarts it or, when `restart` is false, makes it reload its files
+    async fn apply_dnsmasq_config(
+        &self,
+        vnet_uuid: Uuid,
+        dhcp: &VNetDHCP,
+        netns: Option<String>,
+        restart: bool,
+    ) -> FResult<()> {
+        self.write_dnsmasq_config(dhcp).await?;
+        if restart {
+            self.restart_vnet_dhcp(vnet_uuid, dhcp, netns).await
+        } else {
+            self.reload_vnet_dhcp(&vnet_uuid).await
+        }
+    }
+
+    /// Makes the dnsmasq of a virtual network reload its
+    /// hosts and options files
+    async fn reload_vnet_dhcp(&self, vnet_uuid: &Uuid) -> FResult<()> {
+        let guard = self.state.read().await;
+        // it reads them when restarted
+        if guard.pending_dhcp_servers.contains_key(vnet_uuid) {
+            return Ok(());
+        }
+        let dhcp_server = guard
+            .dhcp_servers
+            .get(vnet_uuid)
+            .ok_or_else(|| FError::NetworkingError("DHCP server not found".to_string()))?;
+        log::trace!("Reloading dnsmasq {}", dhcp_server.child.id());
+        kill(Pid::from_raw(dhcp_server.child.id() as i32), Signal::SIGHUP)
+            .map_err(|e| FError::NetworkingError(format!("{}", e)))
+    }
+
+    /// Restarts the supervised dnsmasq instances that exited, with the
+    /// same backoff and limit of [`LinuxNetwork::supervise_ns_managers`].
+    /// Servers no longer restarted are recorded in the failures of their network.
+    async fn supervise_dhcp_servers(&self) {
+        let mut gave_up = Vec::new();
+        let mut due = Vec::new();
+        let mut guard = self.state.write().await;
+        let state = &mut *guard;
+        let mut exited = Vec::new();
+        for (vnet_uuid, dhcp_server) in state.dhcp_servers.iter_mut() {
+            match dhcp_server.child.try_wait() {
+                Ok(Some(status)) => exited.push((*vnet_uuid, status)),
+                Ok(None) => (),
+                Err(e) => log::error!("Unable to check dnsmasq of {}: {}", vnet_uuid, e),
+            }
+        }
+        for (vnet_uuid, status) in exited {
+            let dhcp_server = match state.dhcp_servers.remove(&vnet_uuid) {
+                Some(dhcp_server) => dhcp_server,
+                None => continue,
+            };
+            log::warn!(
+                "dnsmasq of virtual network {} exited with {}",
+                vnet_uuid,
+                status
+            );
+            let restarts = state.dhcp_restarts.entry(vnet_uuid).or_default();
+            if restarts.exited() {
+                state
+                    .pending_dhcp_servers
+                    .insert(vnet_uuid, (dhcp_server.dhcp, dhcp_server.netns));
+            } else {
+                gave_up.push((vnet_uuid, format!("exited with {}", status)));
+            }
+        }
+        for vnet_uuid in state.pending_dhcp_servers.keys() {
+            if state
+                .dhcp_restarts
+                .get(vnet_uuid)
+                .map_or(true, Restarts::is_due)
+            {
+                due.push(*vnet_uuid);
+            }
+        }
+        let due: Vec<(Uuid, VNetDHCP, Option<String>)> = due
+            .into_iter()
+            .filter_map(|vnet_uuid| {
+                state
+                    .pending_dhcp_servers
+                    .remove(&vnet_uuid)
+                    .map(|(dhcp, netns)| (vnet_uuid, dhcp, netns))
+            })
+            .collect();
+        drop(guard);
+
+        for (vnet_uuid, dhcp, netns) in due {
+            match self.spawn_vnet_dhcp(vnet_uuid, &dhcp, netns.clone()).await {
+                Ok(_) => {
+                    let mut guard = self.state.write().await;
+                    let attempt = guard.dhcp_restarts.get_mut(&vnet_uuid).map_or(0, |r| {
+                        r.restarted();
+                        r.attempts
+                    });
+                    drop(guard);
+                    log::info!("dnsmasq of virtual network {} restarted", vnet_uuid);
+                    self.push_supervision_event(SupervisionEventKind::DHCPRestarted {
+                        vnet_uuid,
+                        attempt,
+                    })
+                    .await;
+                }
+                Err(e) => {
+                    log::error!("Unable to restart dnsmasq of {}: {}", vnet_uuid, e);
+                    let mut guard = self.state.write().await;
+                    let state = &mut *guard;
+                    if state.dhcp_restarts.entry(vnet_uuid).or_default().failed() {
+                        state.pending_dhcp_servers.insert(vnet_uuid, (dhcp, netns));
+                    } else {
+                        gave_up.push((vnet_uuid, format!("{}", e)));
+                    }
+                }
+            }
+        }
+
+        for (vnet_uuid, reason) in gave_up {
+            self.dhcp_server_failed(vnet_uuid, reason).await;
+        }
+    }
+
+    /// Records in the failures of the virtual network that
+    /// its dnsmasq is no longer restarted
+    async fn dhcp_server_failed(&self, vnet_uuid: Uuid, reason: String) {
+        log::error!(
+            "dnsmasq of virtual network {} failed after {} restarts, not restarting it: {}",
+            vnet_uuid,
+            SUPERVISION_MAX_RESTARTS,
+            reason
+        );
+        self.state.write().await.dhcp_restarts.remove(&vnet_uuid);
+        let failure = format!("dnsmasq: failed, {}", reason);
+        if let Err(e) = self
+            .update_vnet_failures(vnet_uuid, |failures| {
+                if !failures.contains(&failure) {
+                    failures.push(failure)
+                }
+            })
+            .await
+        {
+            log::warn!(
+                "Unable to record the failure of dnsmasq of {}: {}",
+                vnet_uuid,
+                e
+            );
+        }
+        self.push_supervision_event(SupervisionEventKind::DHCPFailed { vnet_uuid, reason })
+            .await;
+    }
+
+    /// Terminates a dnsmasq with SIGTERM, it is killed if it is still
+    /// running after DNSMASQ_STOP_TIMEOUT. Supervised instances are
+    /// found by their pid file, others by the pid in the file.
+    async fn terminate_dnsmasq(&self, dhcp_internal: &VNetDHCP) -> FResult<()> {
+        let mut guard = self.state.write().await;
+        // no longer restarted by the supervision
+        let pending = guard
+            .pending_dhcp_servers
+            .iter()
+            .find(|(_, (dhcp, _))| dhcp.pid_file == dhcp_internal.pid_file)
+            .map(|(vnet_uuid, _)| *vnet_uuid);
+        if let Some(vnet_uuid) = pending {
+            guard.pending_dhcp_servers.remove(&vnet_uuid);
+            guard.dhcp_restarts.remove(&vnet_uuid);
+        }
+        let vnet_uuid = guard
+            .dhcp_servers
+            .iter()
+            .find(|(_, p)| p.dhcp.pid_file == dhcp_internal.pid_file)
+            .map(|(vnet_uuid, _)| *vnet_uuid);
+        if let Some(vnet_uuid) = vnet_uuid {
+            guard.dhcp_restarts.remove(&vnet_uuid);
+        }
+        let mut child = vnet_uuid.and_then(|vnet_uuid| guard.dhcp_servers.remove(&vnet_uuid));
+        drop(guard);
+
+        let pid = match &child {
+            Some(dhcp_server) => dhcp_server.child.id() as i32,
+            None => {
+                match async_std::fs::read_to_string(async_std::path::Path::new(
+                    &dhcp_internal.pid_file,
+                ))
+                .await
+                {
+                    Ok(str_pid) => str_pid
+                        .trim()
+                        .parse::<i32>()
+                        .map_err(|e| FError::NetworkingError(format!("{}", e)))?,
+                    // never started or already stopped
+                    Err(_) => return Ok(()),
+                }
+            }
+        };
+        // pid files can outlive their process
+        if kill(Pid::from_raw(pid), None).is_err() {
+            return Ok(());
+        }
+
+        log::trace!("Terminating dnsmasq {}", pid);
+        kill(Pid::from_raw(pid), Signal::SIGTERM)
+            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
 
-        self.create_bridge(br_name.clone()).await?;
-        self.connector.local.add_interface(&v_bridge).await?;
+        let deadline = std::time::Instant::now() + Duration::from_secs(DNSMASQ_STOP_TIMEOUT);
+        loop {
+            let exited = match &mut child {
+                Some(dhcp_server) => !matches!(dhcp_server.child.try_wait(), Ok(None)),
+                None => kill(Pid::from_raw(pid), None).is_err(),
+            };
+            if exited {
+                break;
+            }
+            if std::time::Instant::now() > deadline {
+                log::warn!("dnsmasq {} did not exit, killing it", pid);
+                kill(Pid::from_raw(pid), Signal::SIGKILL)
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+                if let Some(mut dhcp_server) = child {
+                    task::spawn_blocking(move || dhcp_server.child.wait()).await?;
+                }
+                break;
+            }
+            task::sleep(Duration::from_millis(100)).await;
+        }
+        Ok(())
+    }
 
-        vnet.interfaces.push(br_uuid);
+    /// Returns a virtual network in the local store with its internals
+    async fn get_virtual_network_internals(
+        &self,
+        vnet_uuid: Uuid,
+    ) -> FResult<(VirtualNetwork, VirtualNetworkInternals)> {
+        let vnet = self.store.get_virtual_network(vnet_uuid).await?;
+        let internals = match &vnet.plugin_internals {
+            Some(raw) => deserialize_network_internals(raw)?,
+            None => return Err(FError::NotFound),
+        };
+        Ok((vnet, internals))
+    }
 
-        self.set_iface_up(br_name.clone()).await?;
+    /// Writes the reservations in the hosts file of the dnsmasq
+    /// of the network, saves them and reloads dnsmasq.
+    /// The caller holds the lock of the network.
+    async fn update_dhcp_reservations(
+        &self,
+        mut vnet: VirtualNetwork,
+        internals: VirtualNetworkInternals,
+    ) -> FResult<Vec<DHCPReservation>> {
+        let dhcp = internals.dhcp.as_ref().ok_or(FError::NotFound)?;
+        let hosts_file = dhcp.hosts_file.clone().ok_or_else(|| {
+            FError::NetworkingError("The DHCP server has no hosts file".to_string())
+        })?;
+        self.os
+            .as_ref()
+            .unwrap()
+            .store_file(
+                render_hosts(&internals.reservations).into_bytes(),
+                hosts_file,
+            )
+            .await?;
 
-        // Creating VXLAN Interface
+        vnet.plugin_internals = Some(serialize_network_internals(&internals)?);
+        self.store.add_virtual_network(&vnet).await?;
 
-        self.create_mcast_vxlan(
-            vxl_name.clone(),
-            self.get_overlay_iface().await?,
-            vxlan_info.vni,
-            vxlan_info.mcast_addr,
-            vxlan_info.port,
-        )
-        .await?;
-        self.connector.local.add_interface(&vxl_iface).await?;
+        self.reload_vnet_dhcp(&vnet.uuid).await?;
+        Ok(internals.reservations)
+    }
 
-        vnet.interfaces.push(vxl_uuid);
+    /// Adds the missing hosts and options files to the dnsmasq of a network,
+    /// they are named after its pid file, and writes its configuration again
+    async fn add_dhcp_files(
+        &self,
+        dhcp: &mut VNetDHCP,
+        reservations: &[DHCPReservation],
+    ) -> FResult<()> {
+        let pid_file = std::path::Path::new(&dhcp.pid_file);
+        let file_path = |ext: &str| -> FResult<String> {
+            Ok(pid_file
+                .with_extension(ext)
+                .to_str()
+                .ok_or(FError::EncodingError)?
+                .to_string())
+        };
+        let hosts_file = file_path("hosts")?;
+        let opts_file = file_path("opts")?;
+        let hosts_file = dhcp.hosts_file.get_or_insert(hosts_file).clone();
+        dhcp.opts_file.get_or_insert(opts_file);
 
-        self.set_iface_master(vxl_name.clone(), br_name.clone())
+        self.os
+            .as_ref()
+            .unwrap()
+            .store_file(render_hosts(reservations).into_bytes(), hosts_file)
             .await?;
-        self.set_iface_up(vxl_name).await?;
+        self.write_dnsmasq_config(dhcp).await
+    }
 
-        // Creating netns and spawing the namespace manager
-        self.add_netns(associated_ns.ns_name.clone()).await?;
-        self.spawn_ns_manager(associated_ns.ns_name.clone(), associated_ns.uuid)
-            .await?;
+    /// Stops the dnsmasq of a virtual network and removes its files
+    async fn stop_vnet_dhcp(&self, dhcp_internal: &VNetDHCP) -> FResult<()> {
+        self.terminate_dnsmasq(dhcp_internal).await?;
 
-        self.connector
-            .local
-            .add_network_namespace(&associated_ns)
-            .await?;
+        let mut files = vec![
+            &dhcp_internal.pid_file,
+            &dhcp_internal.leases_file,
+            &dhcp_internal.conf,
+            &dhcp_internal.log_file,
+        ];
+        files.extend(dhcp_internal.hosts_file.iter());
+        files.extend(dhcp_internal.opts_file.iter());
+        for file in files {
+            match async_std::fs::remove_file(async_std::path::Path::new(file)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => (),
+            }
+        }
+        Ok(())
+    }
 
-        // Creating veth pair
-        self.create_veth(external_veth_name.clone(), internal_veth_name.clone())
+    /// Writes the configuration and the options file of a dnsmasq
+    async fn write_dnsmasq_config(&self, dhcp: &VNetDHCP) -> FResult<()> {
+        let settings = dhcp.settings.as_ref().ok_or_else(|| {
+            FError::NetworkingError("The DHCP server has no settings".to_string())
+        })?;
+        let config = self.create_dnsmasq_config(dhcp, settings).await?;
+        log::trace!("dnsmasq config: {}", config);
+        let os = self.os.as_ref().unwrap();
+        os.store_file(config.into_bytes(), dhcp.conf.clone())
             .await?;
-
-        self.connector.local.add_interface(&v_veth_e).await?;
-
-        vnet.interfaces.push(internal_veth_uuid);
-
-        self.connector.local.add_interface(&v_veth_i).await?;
-
-        vnet.interfaces.push(external_veth_uuid);
-
-        self.set_iface_master(external_veth_name.clone(), br_name.clone())
+        if let Some(opts_file) = &dhcp.opts_file {
+            os.store_file(
+                render_options(&settings.options).into_bytes(),
+                opts_file.clone(),
+            )
             .await?;
-        self.set_iface_up(external_veth_name).await?;
-
-        self.set_iface_ns(
-            internal_veth_name.clone(),
-            associated_ns.ns_name.clone().clone(),
-        )
-        .await?;
+        }
+        Ok(())
+    }
 
-        // create internal bridge
-        let ns_manager = self.get_ns_manager(&associated_ns.uuid).await?;
+    async fn create_dnsmasq_config(
+        &self,
+        dhcp: &VNetDHCP,
+        settings: &VNetDHCPSettings,
+    ) -> FResult<String> {
+        log::trace!("create_dnsmasq_config {:?} {:?}", dhcp, settings);
+        let mut context = Context::new();
+        let template_path = self
+            .get_path()
+            .join("*.conf")
+            .to_str()
+            .ok_or(FError::EncodingError)?
+            .to_string();
+        let templates =
+            Tera::new(&template_path).map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+        context.insert("dhcp_interface", &settings.iface);
+        context.insert("lease_file", &dhcp.leases_file);
+        context.insert("dhcp_pid", &dhcp.pid_file);
+        context.insert("dhcp_log", &dhcp.log_file);
+        if let Some(hosts_file) = &dhcp.hosts_file {
+            context.insert("hosts_file", hosts_file);
+        }
+        if let Some(opts_file) = &dhcp.opts_file {
+            context.insert("opts_file", opts_file);
+        }
+        context.insert("dhcp_start", &format!("{}", settings.dhcp_start));
+        context.insert("dhcp_end", &format!("{}", settings.dhcp_end));
+        context.insert(
+            "lease_time",
+            &settings
+                .options
+                .lease_time
+                .unwrap_or(DEFAULT_DHCP_LEASE_TIME),
+        );
+        if let Some(gateway) = settings.gateway {
+            context.insert("default_gw", &format!("{}", gateway));
+        }
+        let (dns4, dns6): (Vec<_>, Vec<_>) = settings
+            .dns
+            .iter()
+            .partition(|ip| matches!(ip, IPAddress::V4(_)));
+        if !dns4.is_empty() {
+            context.insert("dns_servers", &join_addresses(&dns4));
+        }
+        if !dns6.is_empty() {
+            context.insert("dns6_servers", &join_addresses(&dns6));
+        }
 
-        // This is used to wait that the namespace manager is ready to serve
-        while !ns_manager.verify_server().await? {}
+        match templates.render("dnsmasq.conf", &context) {
+            Ok(t) => Ok(t),
+            Err(e) => {
+                log::error!("Parsing error(s): {} {}", e, e.source().unwrap());
+                Err(FError::NetworkingError(format!(
+                    "{} {}",
+                    e,
+                    e.source().unwrap()
+                )))
+            }
+        }
+    }
 
-        ns_manager
-            .set_virtual_interface_up("lo".to_string())
-            .await??;
+    /// Creates a nftables table masquerading the given networks
+    /// toward the given interface, IPv4 and IPv6 networks can be mixed.
+    /// The table is named after its owner virtual network.
+    /// Returns the name of the table.
+    async fn configure_nat(
+        &self,
+        owner: &Uuid,
+        nets: &[IpNetwork],
+        iface: &str,
+    ) -> FResult<String> {
+        let table_name = owner_table_name(owner);
+        self.nl
+            .add_nat_table(table_name.clone(), *owner, nets.to_vec(), iface.to_string())
+            .await?;
+        Ok(table_name)
+    }
 
-        ns_manager
-            .add_virtual_interface_bridge(internal_br_name.clone())
-            .await??;
+    async fn clean_nat(&self, table_name: String) -> FResult<()> {
+        self.nl.del_nat_table(table_name).await
+    }
+}
 
-        ns_manager
-            .set_virtual_interface_up(internal_br_name.clone())
-            .await??;
+/// A completed step of the creation of a virtual network,
+/// used to undo it if a later step fails
+#[derive(Debug)]
+enum UndoStep {
+    /// Link in the default namespace
+    Iface(String),
+    /// Interface record in the local store
+    StoreInterface(Uuid),
+    Netns(String),
+    NsManager(Uuid),
+    /// Network namespace record in the local store
+    StoreNetns(Uuid),
+    DHCP(VNetDHCP),
+    NAT(String),
+    /// All-zeros FDB entry of a VXLAN link toward a peer
+    FDB(String, IPAddress),
+}
 
-        vnet.interfaces.push(internal_br_uuid);
+/// Name of the dnsmasq files of the default virtual network,
+/// the files of the other networks are named after their UUID
+const DEFAULT_VNET_DHCP_NAME: &str = "fosbr0";
 
-        self.connector
-            .local
-            .add_interface(&v_internal_bridge)
-            .await?;
+/// Maximum length of interface names, including the terminating NUL
+const IFNAMSIZ: usize = 16;
 
-        ns_manager
-            .set_virtual_interface_master(internal_veth_name.clone(), internal_br_name.clone())
-            .await??;
+/// Checks that the name is a valid Linux interface name
+pub fn validate_iface_name(name: &str) -> std::result::Result<(), LinuxNetworkError> {
+    let invalid = |reason: &str| {
+        Err(LinuxNetworkError::InvalidName {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        })
+    };
+    if name.is_empty() {
+        return invalid("empty name");
+    }
+    if name.len() >= IFNAMSIZ {
+        return invalid(&format!("longer than {} bytes", IFNAMSIZ - 1));
+    }
+    if name == "." || name == ".." {
+        return invalid("reserved name");
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| *c == '/' || *c == ':' || c.is_whitespace() || c.is_control())
+    {
+        return invalid(&format!("illegal character {:?}", c));
+    }
+    Ok(())
+}
 
-        ns_manager
-            .set_virtual_interface_up(internal_veth_name.clone())
-            .await??;
-
-        // NAT configuration, skip it for the time being...
-        // let nat_table = self
-        //     .configure_nat(
-        //         IpNetwork::V4(
-        //             ipnetwork::Ipv4Network::new(
-        //                 std::net::Ipv4Addr::new(10, 240, 0, 0),
-        //                 16,
-        //             )
-        //             .map_err(|e| FError::NetworkingError(format!("{}", e)))?,
-        //         ),
-        //         &self.get_overlay_face_from_config().await?.if_name,
-        //     )
-        //     .await?;
+/// Returns the address following the given one
+fn next_address(addr: IPAddress) -> IPAddress {
+    match addr {
+        IPAddress::V4(a) => IPAddress::V4(std::net::Ipv4Addr::from(u32::from(a).wrapping_add(1))),
+        IPAddress::V6(a) => IPAddress::V6(std::net::Ipv6Addr::from(u128::from(a).wrapping_add(1))),
+    }
+}
 
-        // DHCP configuration and spawn
+/// Checks if two handles point to the same Namespace Manager
+fn same_ns_manager(a: &Arc<dyn NSManagerOps>, b: &Arc<dyn NSManagerOps>) -> bool {
+    Arc::as_ptr(a) as *const u8 == Arc::as_ptr(b) as *const u8
+}
 
-        let dhcp_internal = match &vnet.ip_configuration {
-            Some(conf) => None,
-            None => None,
-        };
+/// Finds the pids of the namespace managers serving the given namespace
+fn find_ns_manager_pids(ns_uuid: &Uuid) -> Vec<i32> {
+    let id = format!("{}", ns_uuid);
+    let mut pids = Vec::new();
+    if let Ok(entries) = std::fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let pid = match entry.file_name().to_string_lossy().parse::<i32>() {
+                Ok(pid) => pid,
+                Err(_) => continue,
+            };
+            if let Ok(cmdline) = std::fs::read(entry.path().join("cmdline")) {
+                let args: Vec<&[u8]> = cmdline.split(|b| *b == 0).collect();
+                if args
+                    .first()
+                    .map(|a| a.ends_with(b"fos-net-linux-ns-manager"))
+                    .unwrap_or(false)
+                    && args.iter().any(|a| *a == id.as_bytes())
+                {
+                    pids.push(pid);
+                }
+            }
+        }
+    }
+    pids
+}
 
-        let ns_info = Some(VNetNetns {
-            ns_name: associated_ns.ns_name.clone(),
-            ns_uuid: associated_ns.uuid,
-        });
+/// Checks that the process with the given pid is a dnsmasq,
+/// pid files can outlive their process and the pid be reused
+fn is_dnsmasq(pid: i32) -> bool {
+    std::fs::read_to_string(format!("/proc/{}/comm", pid))
+        .map(|comm| comm.trim() == "dnsmasq")
+        .unwrap_or(false)
+}
 
-        let internals = VirtualNetworkInternals {
-            associated_netns: ns_info,
-            dhcp: dhcp_internal,
-            associated_tables: vec![],
-        };
-        vnet.plugin_internals = Some(serialize_network_internals(&internals)?);
-        Ok(vnet)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::netlink::fake::{FakeLinkKind, FakeNetlinkBackend};
+    use crate::services::fake::{
+        FakeAgent, FakeDHCPLauncher, FakeNSManagerLauncher, FakeOS, FakeStore,
+    };
+
+    fn test_config() -> LinuxNetworkConfig {
+        LinuxNetworkConfig {
+            pid_file: std::path::Path::new("/tmp/fos-net-linux-test.pid").into(),
+            zlocator: String::new(),
+            zfilelocator: String::new(),
+            // dnsmasq template
+            path: std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("etc")
+                .into_boxed_path(),
+            run_path: std::env::temp_dir().into_boxed_path(),
+            monitoring_interveal: 10,
+            overlay_iface: Some("eth0".to_string()),
+            dataplane_iface: Some("eth0".to_string()),
+            macvlan_mode: None,
+            ns_manager_timeout: None,
+            dhcp_options: None,
+            name_prefix: None,
+            shutdown_policy: None,
+        }
     }
 
-    async fn get_overlay_face_from_config(&self) -> FResult<Interface> {
-        let iface = self.config.overlay_iface.as_ref().ok_or(FError::NotFound)?;
-        let addresses = self.get_iface_addresses(iface.clone()).await?;
-        Ok(Interface {
-            if_name: iface.to_string(),
-            kind: InterfaceKind::ETHERNET,
-            addresses,
-            phy_address: None,
-        })
+    /// Plugin running on the in-memory backend and services,
+    /// eth0 stands for the physical interface of the node
+    async fn test_plugin() -> (LinuxNetwork, FakeNetlinkBackend, Arc<FakeStore>) {
+        let nl = FakeNetlinkBackend::new();
+        nl.create_bridge("eth0".to_string()).await.unwrap();
+        nl.add_iface_address("eth0".to_string(), "192.168.1.10".parse().unwrap(), 24)
+            .await
+            .unwrap();
+        let store = Arc::new(FakeStore::new());
+        let plugin = restarted_plugin(&nl, &store);
+        (plugin, nl, store)
     }
 
-    async fn get_dataplane_from_config(&self) -> FResult<Interface> {
-        let iface = self
-            .config
-            .dataplane_iface
-            .as_ref()
-            .ok_or(FError::NotFound)?;
-        let addresses = self.get_iface_addresses(iface.clone()).await?;
-        Ok(Interface {
-            if_name: iface.to_string(),
-            kind: InterfaceKind::ETHERNET,
-            addresses,
-            phy_address: None,
-        })
+    /// A new instance of the plugin on the kernel and store left by another
+    fn restarted_plugin(nl: &FakeNetlinkBackend, store: &Arc<FakeStore>) -> LinuxNetwork {
+        let mut plugin = LinuxNetwork::new_with_backend(
+            std::process::id(),
+            test_config(),
+            Arc::new(nl.clone()),
+            store.clone(),
+            Arc::new(FakeNSManagerLauncher::new(nl.clone())),
+        )
+        .unwrap();
+        plugin.agent = Some(Arc::new(FakeAgent::new(Uuid::new_v4())));
+        plugin.os = Some(Arc::new(FakeOS::new()));
+        plugin
     }
 
-    fn get_domain_socket_locator(&self) -> String {
-        self.config.zfilelocator.clone()
+    fn test_vnet(ip_configuration: Option<IPConfiguration>) -> VirtualNetwork {
+        VirtualNetwork {
+            uuid: Uuid::new_v4(),
+            id: "test-net".to_string(),
+            name: None,
+            is_mgmt: false,
+            link_kind: LinkKind::L2(MCastVXLANInfo {
+                vni: 42,
+                mcast_addr: "239.0.0.42".parse().unwrap(),
+                port: 4789,
+            }),
+            ip_version: IPVersion::IPV4,
+            ip_configuration,
+            connection_points: Vec::new(),
+            interfaces: Vec::new(),
+            plugin_internals: None,
+        }
     }
 
-    fn get_path(&self) -> Box<std::path::Path> {
-        self.config.path.clone()
+    /// Gateway and NAT, without DHCP so no dnsmasq is spawned
+    fn test_ip_configuration() -> IPConfiguration {
+        IPConfiguration {
+            subnet: Some(("10.10.0.0".parse().unwrap(), 24)),
+            gateway: Some("10.10.0.1".parse().unwrap()),
+            dhcp_range: None,
+            dns: None,
+        }
     }
 
-    fn get_run_path(&self) -> Box<std::path::Path> {
-        self.config.run_path.clone()
+    /// Gateway, NAT and a DHCP server, tests using it replace the
+    /// launcher of the plugin with a [`FakeDHCPLauncher`]
+    fn test_dhcp_configuration() -> IPConfiguration {
+        IPConfiguration {
+            dhcp_range: Some((
+                "10.10.0.100".parse().unwrap(),
+                "10.10.0.200".parse().unwrap(),
+            )),
+            ..test_ip_configuration()
+        }
     }
 
-    fn generate_random_interface_name(&self) -> String {
-        let iface: String = thread_rng().sample_iter(&Alphanumeric).take(8).collect();
-        iface
+    /// Checks that nothing but eth0 is left in the kernel and in the store
+    async fn assert_clean(plugin: &LinuxNetwork, nl: &FakeNetlinkBackend, store: &FakeStore) {
+        assert_eq!(nl.dump_links().await.unwrap(), vec!["eth0".to_string()]);
+        assert!(nl.namespaces().is_empty());
+        assert!(nl.nat_tables().is_empty());
+        assert!(store.virtual_networks().is_empty());
+        assert!(store.interfaces().is_empty());
+        assert!(store.network_namespaces().is_empty());
+        assert!(store.connection_points().is_empty());
+        assert!(plugin.state.read().await.ns_managers.is_empty());
     }
 
-    fn generate_random_netns_name(&self) -> String {
-        let ns: String = thread_rng().sample_iter(&Alphanumeric).take(8).collect();
-        format!("ns-{}", ns)
+    async fn vnet_failures(store: &FakeStore, vnet_uuid: Uuid) -> Vec<String> {
+        let vnet = store.get_virtual_network(vnet_uuid).await.unwrap();
+        deserialize_network_internals(vnet.plugin_internals.as_ref().unwrap())
+            .unwrap()
+            .failures
     }
 
-    fn generate_random_nft_table_name(&self) -> String {
-        let ns: String = thread_rng().sample_iter(&Alphanumeric).take(10).collect();
-        format!("table{}", ns)
-    }
+    #[async_std::test]
+    async fn vnet_create_and_delete() {
+        let (plugin, nl, store) = test_plugin().await;
+        let vnet = test_vnet(Some(test_ip_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+
+        let created = plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        assert_eq!(created.interfaces.len(), 5);
+        assert_eq!(store.interfaces().len(), 5);
+        let internals =
+            deserialize_network_internals(created.plugin_internals.as_ref().unwrap()).unwrap();
+        let ns_name = internals.associated_netns.unwrap().ns_name;
+        assert_eq!(nl.namespaces(), vec![ns_name.clone()]);
+
+        let bridge = plugin.get_virtual_network_bridge(&created).await.unwrap();
+        let br_link = nl.link(&bridge.if_name).unwrap();
+        assert!(br_link.up);
+        assert_eq!(br_link.addresses, vec![("10.10.0.1".parse().unwrap(), 24)]);
+        let vxlan = nl
+            .links()
+            .into_iter()
+            .find(|l| matches!(l.kind, FakeLinkKind::VXLAN { vni: 42, .. }))
+            .unwrap();
+        assert_eq!(vxlan.master, Some(bridge.if_name.clone()));
+        // loopback, internal bridge and internal veth
+        assert_eq!(nl.in_netns(&ns_name).links().len(), 3);
+        assert_eq!(nl.nat_tables().len(), 1);
 
-    async fn add_netns(&self, ns_name: String) -> FResult<()> {
-        log::trace!("add_netns {}", ns_name);
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async { NetlinkNetworkNamespace::add(ns_name).await })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
-    }
+        // creating it again returns the existing network
+        let again = plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        assert_eq!(again.interfaces, created.interfaces);
 
-    async fn del_netns(&self, ns_name: String) -> FResult<()> {
-        log::trace!("del_netns {}", ns_name);
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async { NetlinkNetworkNamespace::del(ns_name).await })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
     }
 
-    async fn create_bridge(&self, br_name: String) -> FResult<()> {
-        log::trace!("create_bridge {}", br_name);
-        // let mut state = self.state.write().await;
-        // state
-        //     .tokio_rt
-        //     .block_on(async {
-        //         let (connection, handle, _) = new_connection().unwrap();
-        //         tokio::spawn(connection);
-        //         handle.link().add().bridge(br_name).execute().await
-        //     })
-        //     .map_err(|e| FError::NetworkingError(format!("{}", e)))
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                state
-                    .nl_handler
-                    .link()
-                    .add()
-                    .bridge(br_name)
-                    .execute()
-                    .await
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
+    #[async_std::test]
+    async fn vnet_create_rollback() {
+        let failures = [
+            ("create_bridge", 0),
+            ("create_mcast_vxlan", 0),
+            ("add_netns", 0),
+            ("create_veth", 0),
+            // the second bridge is created by the namespace manager
+            ("create_bridge", 1),
+            ("set_iface_master", 2),
+            ("add_nat_table", 0),
+        ];
+        for (op, skip) in failures.iter() {
+            let (plugin, nl, store) = test_plugin().await;
+            let vnet = test_vnet(Some(test_ip_configuration()));
+            store.add_global_virtual_network(vnet.clone());
+
+            nl.inject_failure(op, *skip);
+            assert!(
+                plugin.create_virtual_network(vnet.uuid).await.is_err(),
+                "{} did not fail",
+                op
+            );
+            assert_clean(&plugin, &nl, &store).await;
+        }
     }
 
-    async fn create_veth(&self, iface_i: String, iface_e: String) -> FResult<()> {
-        log::trace!("create_veth {} {}", iface_i, iface_e);
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                state
-                    .nl_handler
-                    .link()
-                    .add()
-                    .veth(iface_i, iface_e)
-                    .execute()
-                    .await
+    #[async_std::test]
+    async fn vnet_unicast_vxlan() {
+        let (plugin, nl, store) = test_plugin().await;
+        let mut vnet = test_vnet(None);
+        let settings = VirtualNetworkSettings {
+            // the local address is skipped
+            vxlan_peers: vec![
+                "192.168.1.10".parse().unwrap(),
+                "192.168.1.11".parse().unwrap(),
+                "192.168.1.12".parse().unwrap(),
+            ],
+            ..Default::default()
+        };
+        vnet.plugin_internals = Some(serde_json::to_vec(&settings).unwrap());
+        store.add_global_virtual_network(vnet.clone());
+
+        let created = plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let internals =
+            deserialize_network_internals(created.plugin_internals.as_ref().unwrap()).unwrap();
+        assert_eq!(
+            internals.transport,
+            Some(VNetTransport::UCastVXLAN {
+                vni: 42,
+                port: 4789,
+                peers: settings.vxlan_peers.clone(),
             })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
-    }
+        );
+        let vxlan = nl
+            .links()
+            .into_iter()
+            .find(|l| matches!(l.kind, FakeLinkKind::VXLAN { vni: 42, .. }))
+            .unwrap();
+        assert_eq!(vxlan.fdb, vec!["192.168.1.12".parse().unwrap()]);
 
-    async fn create_vlan(&self, iface: String, dev: String, tag: u16) -> FResult<()> {
-        let mut state = self.state.write().await;
-        log::trace!("create_vlan {} {} {}", iface, dev, tag);
-        state
-            .tokio_rt
-            .block_on(async {
-                let mut links = state.nl_handler.link().get().set_name_filter(dev).execute();
-                if let Some(link) = links
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    state
-                        .nl_handler
-                        .link()
-                        .add()
-                        .vlan(iface, link.header.index, tag)
-                        .execute()
-                        .await
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
-                } else {
-                    Err(FError::NotFound)
-                }
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
+
+        // a failing FDB entry removes the VXLAN link
+        nl.inject_failure("append_fdb_entry", 0);
+        assert!(plugin.create_virtual_network(vnet.uuid).await.is_err());
+        assert_clean(&plugin, &nl, &store).await;
     }
 
-    async fn create_mcast_vxlan(
-        &self,
-        iface: String,
-        dev: String,
-        vni: u32,
-        mcast_addr: IPAddress,
-        port: u16,
-    ) -> FResult<()> {
-        log::trace!(
-            "create_mcast_vxlan {} {} {} {} {}",
-            iface,
-            dev,
-            vni,
-            mcast_addr,
-            port
+    #[async_std::test]
+    async fn vnet_vlan() {
+        let (plugin, nl, store) = test_plugin().await;
+        let mut settings = VirtualNetworkSettings {
+            l2_transport: L2Transport::VLAN,
+            vlan_tag: Some(100),
+            ..Default::default()
+        };
+        let mut vnet = test_vnet(None);
+        vnet.plugin_internals = Some(serde_json::to_vec(&settings).unwrap());
+        store.add_global_virtual_network(vnet.clone());
+
+        plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let vlan = nl
+            .links()
+            .into_iter()
+            .find(|l| matches!(l.kind, FakeLinkKind::VLAN { .. }))
+            .unwrap();
+        assert_eq!(
+            vlan.kind,
+            FakeLinkKind::VLAN {
+                dev: "eth0".to_string(),
+                tag: 100
+            }
         );
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                let mut links = state.nl_handler.link().get().set_name_filter(dev).execute();
-                if let Some(link) = links
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    let vxlan = state
-                        .nl_handler
-                        .link()
-                        .add()
-                        .vxlan(iface, vni)
-                        .link(link.header.index);
-
-                    let vxlan = match mcast_addr {
-                        IPAddress::V4(v4) => vxlan.group(v4),
-                        IPAddress::V6(v6) => vxlan.group6(v6),
-                    };
-
-                    vxlan
-                        .port(port)
-                        .execute()
-                        .await
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
-                } else {
-                    Err(FError::NotFound)
-                }
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+
+        // the tag is checked only for the network using it
+        settings.vlan_tag = Some(4095);
+        let mut vnet = test_vnet(None);
+        vnet.plugin_internals = Some(serde_json::to_vec(&settings).unwrap());
+        store.add_global_virtual_network(vnet.clone());
+        assert!(plugin.create_virtual_network(vnet.uuid).await.is_err());
+        assert_clean(&plugin, &nl, &store).await;
     }
 
-    async fn create_ptp_vxlan(
-        &self,
-        iface: String,
-        dev: String,
-        vni: u32,
-        local_addr: IPAddress,
-        remote_addr: IPAddress,
-        port: u16,
-    ) -> FResult<()> {
-        log::trace!(
-            "create_ptp_vxlan {} {} {} {} {} {}",
-            iface,
-            dev,
-            vni,
-            local_addr,
-            remote_addr,
-            port
+    #[async_std::test]
+    async fn link_events() {
+        let (plugin, nl, store) = test_plugin().await;
+        let vnet = test_vnet(None);
+        store.add_global_virtual_network(vnet.clone());
+        plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let bridge = plugin
+            .get_virtual_network_bridge(&store.get_virtual_network(vnet.uuid).await.unwrap())
+            .await
+            .unwrap();
+        let alias = nl.link(&bridge.if_name).unwrap().alias;
+        assert_eq!(alias, Some(owner_tag(&bridge.uuid)));
+
+        // links not owned by the plugin are ignored
+        plugin
+            .handle_netlink_event(
+                None,
+                NetlinkEvent::LinkRemoved {
+                    name: "eth1".to_string(),
+                    alias: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(vnet_failures(&store, vnet.uuid).await.is_empty());
+
+        nl.set_iface_down(bridge.if_name.clone()).await.unwrap();
+        plugin
+            .handle_netlink_event(
+                None,
+                NetlinkEvent::LinkAdded {
+                    name: bridge.if_name.clone(),
+                    alias: alias.clone(),
+                    up: false,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            vnet_failures(&store, vnet.uuid).await,
+            vec![format!("{} down", bridge.if_name)]
         );
-        let mut state = self.state.write().await;
-        state
-            .tokio_rt
-            .block_on(async {
-                let mut links = state.nl_handler.link().get().set_name_filter(dev).execute();
-                if let Some(link) = links
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    let vxlan = state
-                        .nl_handler
-                        .link()
-                        .add()
-                        .vxlan(iface, vni)
-                        .link(link.header.index);
-
-                    let vxlan = match local_addr {
-                        IPAddress::V4(v4) => vxlan.local(v4),
-                        IPAddress::V6(v6) => vxlan.local6(v6),
-                    };
 
-                    let vxlan = match remote_addr {
-                        IPAddress::V4(v4) => vxlan.remote(v4),
-                        IPAddress::V6(v6) => vxlan.remote6(v6),
-                    };
+        nl.add_iface_address(bridge.if_name.clone(), "10.20.0.1".parse().unwrap(), 24)
+            .await
+            .unwrap();
+        plugin
+            .handle_netlink_event(
+                None,
+                NetlinkEvent::LinkAdded {
+                    name: bridge.if_name.clone(),
+                    alias: alias.clone(),
+                    up: true,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(vnet_failures(&store, vnet.uuid).await.is_empty());
+        assert_eq!(
+            store.get_interface(bridge.uuid).await.unwrap().addresses,
+            vec!["10.20.0.1".parse().unwrap()]
+        );
 
-                    vxlan
-                        .port(port)
-                        .execute()
-                        .await
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
-                } else {
-                    Err(FError::NotFound)
-                }
-            })
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))
+        plugin
+            .handle_netlink_event(
+                None,
+                NetlinkEvent::LinkRemoved {
+                    name: bridge.if_name.clone(),
+                    alias,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            vnet_failures(&store, vnet.uuid).await,
+            vec![format!("{} removed", bridge.if_name)]
+        );
     }
 
-    async fn del_iface(&self, iface: String) -> FResult<()> {
-        log::trace!("del_iface {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .del(link.header.index)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
-    }
+    #[async_std::test]
+    async fn namespace_link_events() {
+        let (plugin, nl, store) = test_plugin().await;
+        let vnet = test_vnet(Some(test_ip_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+        let created = plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let ns_info = deserialize_network_internals(created.plugin_internals.as_ref().unwrap())
+            .unwrap()
+            .associated_netns
+            .unwrap();
+        let internal_bridge = store
+            .interfaces()
+            .into_iter()
+            .find(|i| i.net_ns.is_some() && matches!(i.kind, VirtualInterfaceKind::BRIDGE(_)))
+            .unwrap();
+        let ns_nl = nl.in_netns(&ns_info.ns_name);
 
-    async fn set_iface_master(&self, iface: String, master: String) -> FResult<()> {
-        log::trace!("set_iface_master {} {}", iface, master);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                let mut masters = state
-                    .nl_handler
-                    .link()
-                    .get()
-                    .set_name_filter(master)
-                    .execute();
-                if let Some(master) = masters
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    state
-                        .nl_handler
-                        .link()
-                        .set(link.header.index)
-                        .master(master.header.index)
-                        .execute()
-                        .await
-                        .map_err(|e| FError::NetworkingError(format!("{}", e)))
-                } else {
-                    log::error!("set_iface_master master not found");
-                    Err(FError::NotFound)
-                }
-            } else {
-                log::error!("set_iface_master iface not found");
-                Err(FError::NotFound)
+        // the events reach the plugin through the manager of the namespace
+        let addr: IPAddress = "10.30.0.1".parse().unwrap();
+        ns_nl
+            .add_iface_address(internal_bridge.if_name.clone(), addr, 24)
+            .await
+            .unwrap();
+        for _ in 0..50 {
+            let iface = store.get_interface(internal_bridge.uuid).await.unwrap();
+            if iface.addresses.contains(&addr) {
+                break;
             }
-        })
-    }
+            task::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(store
+            .get_interface(internal_bridge.uuid)
+            .await
+            .unwrap()
+            .addresses
+            .contains(&addr));
 
-    async fn del_iface_master(&self, iface: String) -> FResult<()> {
-        log::trace!("del_iface_master {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .nomaster()
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                log::error!("del_iface_master iface not found");
-                Err(FError::NotFound)
+        ns_nl
+            .del_iface(internal_bridge.if_name.clone())
+            .await
+            .unwrap();
+        let removed = format!("{} removed", internal_bridge.if_name);
+        for _ in 0..50 {
+            if vnet_failures(&store, vnet.uuid).await.contains(&removed) {
+                break;
             }
-        })
+            task::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(vnet_failures(&store, vnet.uuid).await, vec![removed]);
     }
 
-    async fn add_iface_address(&self, iface: String, addr: IPAddress, prefix: u8) -> FResult<()> {
-        log::trace!("add_iface_address {} {} {}", iface, addr, prefix);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .address()
-                    .add(link.header.index, addr, prefix)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
+    /// Makes the scheduled restarts due now
+    async fn skip_backoff(plugin: &LinuxNetwork) {
+        let mut guard = plugin.state.write().await;
+        let state = &mut *guard;
+        for restarts in state
+            .ns_manager_restarts
+            .values_mut()
+            .chain(state.dhcp_restarts.values_mut())
+        {
+            if restarts.due.is_some() {
+                restarts.due = Some(std::time::Instant::now());
             }
-        })
+        }
     }
 
-    async fn del_iface_address(&self, iface: String, addr: IPAddress) -> FResult<()> {
-        log::trace!("del_iface_address {} {}", iface, addr);
-        let mut state = self.state.write().await;
-        use netlink_packet_route::rtnl::address::nlas::Nla;
-        use netlink_packet_route::rtnl::address::AddressMessage;
-        state.tokio_rt.block_on(async {
-            let octets = match addr {
-                IPAddress::V4(a) => a.octets().to_vec(),
-                IPAddress::V6(a) => a.octets().to_vec(),
-            };
-            let mut nl_addresses = Vec::new();
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface.clone())
-                .execute();
-            if let Some(link) = links
-                .try_next()
+    /// Kills the process of a Namespace Manager and waits
+    /// for the supervision to schedule its restart
+    async fn kill_ns_manager_process(plugin: &LinuxNetwork, ns_uuid: Uuid) {
+        let pid = plugin.state.read().await.ns_managers[&ns_uuid]
+            .child
+            .as_ref()
+            .unwrap()
+            .id();
+        kill(Pid::from_raw(pid as i32), Signal::SIGKILL).unwrap();
+        for _ in 0..50 {
+            plugin.supervise_ns_managers().await;
+            if plugin
+                .state
+                .read()
                 .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+                .pending_ns_managers
+                .contains_key(&ns_uuid)
             {
-                let mut addresses = state
-                    .nl_handler
-                    .address()
-                    .get()
-                    .set_link_index_filter(link.header.index)
-                    .execute();
-                while let Some(msg) = addresses
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    for nla in &msg.nlas {
-                        match nla {
-                            Nla::Address(nl_addr) => {
-                                nl_addresses.push((msg.header.clone(), nl_addr.clone()))
-                            }
-                            _ => continue,
-                        }
-                    }
-                }
-                match nl_addresses.into_iter().find(|(_, x)| *x == octets) {
-                    Some((hdr, addr)) => {
-                        let msg = AddressMessage {
-                            header: hdr,
-                            nlas: vec![Nla::Address(addr)],
-                        };
-                        state
-                            .nl_handler
-                            .address()
-                            .del(msg)
-                            .execute()
-                            .await
-                            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-                        Ok(())
-                    }
-                    None => Err(FError::NotFound),
-                }
-            } else {
-                Err(FError::NotFound)
+                return;
             }
-        })
+            task::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("Namespace manager of {} not seen exiting", ns_uuid);
     }
 
-    async fn get_iface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>> {
-        log::trace!("get_iface_addresses {}", iface);
-        let mut state = self.state.write().await;
-        use netlink_packet_route::rtnl::address::nlas::Nla;
-        use netlink_packet_route::rtnl::address::AddressMessage;
-        state.tokio_rt.block_on(async {
-            let mut nl_addresses = Vec::new();
-            let mut f_addresses: Vec<IPAddress> = Vec::new();
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface.clone())
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                let mut addresses = state
-                    .nl_handler
-                    .address()
-                    .get()
-                    .set_link_index_filter(link.header.index)
-                    .execute();
-                while let Some(msg) = addresses
-                    .try_next()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-                {
-                    for nla in &msg.nlas {
-                        match nla {
-                            Nla::Address(nl_addr) => {
-                                nl_addresses.push((msg.header.clone(), nl_addr.clone()))
-                            }
-                            _ => continue,
-                        }
-                    }
-                }
-                for (_, x) in nl_addresses {
-                    if x.len() == 4 {
-                        let octects: [u8; 4] = [x[0], x[1], x[2], x[3]];
-                        f_addresses.push(IPAddress::from(octects))
-                    }
-                    if x.len() == 16 {
-                        let octects: [u8; 16] = [
-                            x[0], x[1], x[2], x[3], x[4], x[5], x[6], x[7], x[8], x[9], x[10],
-                            x[11], x[12], x[13], x[14], x[15],
-                        ];
-                        f_addresses.push(IPAddress::from(octects))
-                    }
-                }
-                Ok(f_addresses)
-            } else {
-                Err(FError::NotFound)
-            }
-        })
+    async fn last_supervision_event(plugin: &LinuxNetwork) -> SupervisionEventKind {
+        plugin
+            .get_supervision_events()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap()
+            .kind
     }
 
-    async fn set_iface_name(&self, iface: String, new_name: String) -> FResult<()> {
-        log::trace!("set_iface_name {} {}", iface, new_name);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .name(new_name)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
+    #[async_std::test]
+    async fn ns_manager_restart() {
+        let (mut plugin, nl, store) = test_plugin().await;
+        let launcher = FakeNSManagerLauncher::new(nl.clone()).with_process("sleep 60");
+        plugin.ns_launcher = Arc::new(launcher.clone());
+        let vnet = test_vnet(Some(test_ip_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+        let created = plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let ns_info = deserialize_network_internals(created.plugin_internals.as_ref().unwrap())
+            .unwrap()
+            .associated_netns
+            .unwrap();
+        assert_eq!(launcher.launches(), 1);
+
+        // restarted once the backoff elapsed
+        kill_ns_manager_process(&plugin, ns_info.ns_uuid).await;
+        assert!(plugin.get_ns_manager(&ns_info.ns_uuid).await.is_err());
+        assert_eq!(launcher.launches(), 1);
+        skip_backoff(&plugin).await;
+        plugin.supervise_ns_managers().await;
+        assert_eq!(launcher.launches(), 2);
+        assert!(plugin.get_ns_manager(&ns_info.ns_uuid).await.is_ok());
+        assert_eq!(
+            last_supervision_event(&plugin).await,
+            SupervisionEventKind::NSManagerRestarted {
+                ns_uuid: ns_info.ns_uuid,
+                attempt: 1,
             }
-        })
+        );
+
+        // given up after SUPERVISION_MAX_RESTARTS
+        launcher.set_failing(true);
+        kill_ns_manager_process(&plugin, ns_info.ns_uuid).await;
+        for _ in 0..SUPERVISION_MAX_RESTARTS {
+            skip_backoff(&plugin).await;
+            plugin.supervise_ns_managers().await;
+        }
+        assert_eq!(launcher.launches(), 1 + SUPERVISION_MAX_RESTARTS as usize);
+        assert!(matches!(
+            last_supervision_event(&plugin).await,
+            SupervisionEventKind::NSManagerFailed { ns_uuid, .. } if ns_uuid == ns_info.ns_uuid
+        ));
+        let failures = vnet_failures(&store, vnet.uuid).await;
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with(&format!("netns {}: manager failed", ns_info.ns_name)));
+        assert!(plugin.get_ns_manager(&ns_info.ns_uuid).await.is_err());
+        // no more attempts
+        skip_backoff(&plugin).await;
+        plugin.supervise_ns_managers().await;
+        assert_eq!(launcher.launches(), 1 + SUPERVISION_MAX_RESTARTS as usize);
+
+        // reconcile starts it again
+        launcher.set_failing(false);
+        plugin.reconcile().await.unwrap();
+        assert!(plugin.get_ns_manager(&ns_info.ns_uuid).await.is_ok());
+        assert!(vnet_failures(&store, vnet.uuid).await.is_empty());
+
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
     }
 
-    async fn set_iface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()> {
-        log::trace!("set_iface_mac {} {:?}", iface, address);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .address(address)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
+    async fn vnet_dhcp(store: &FakeStore, vnet_uuid: Uuid) -> VNetDHCP {
+        let vnet = store.get_virtual_network(vnet_uuid).await.unwrap();
+        deserialize_network_internals(vnet.plugin_internals.as_ref().unwrap())
+            .unwrap()
+            .dhcp
+            .unwrap()
     }
 
-    async fn set_iface_ns(&self, iface: String, netns: String) -> FResult<()> {
-        log::trace!("set_iface_ns {} {}", iface, netns);
-        const NETNS_PATH: &str = "/run/netns/";
-        let netns = format!("{}{}", NETNS_PATH, netns);
-        let mut state = self.state.write().await;
-        let nsfile = std::fs::File::open(netns)?;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .setns_by_fd(nsfile.into_raw_fd())
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
+    /// Waits up to 5 seconds for a file written by a [`FakeDHCPLauncher`]
+    async fn wait_for_file(path: &str) -> bool {
+        for _ in 0..50 {
+            if std::path::Path::new(path).exists() {
+                return true;
             }
-        })
+            task::sleep(Duration::from_millis(100)).await;
+        }
+        false
     }
 
-    async fn set_iface_default_ns(&self, iface: String) -> FResult<()> {
-        log::trace!("set_iface_default_ns {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .setns_by_pid(0)
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
+    fn remove_dhcp_markers(dhcp: &VNetDHCP) {
+        for ext in &["ready", "reloads"] {
+            let _ = std::fs::remove_file(format!("{}.{}", dhcp.conf, ext));
+        }
     }
 
-    async fn set_iface_up(&self, iface: String) -> FResult<()> {
-        log::trace!("set_iface_up {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .up()
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
-            }
-        })
+    /// Kills the dnsmasq of a virtual network and waits
+    /// for the supervision to schedule its restart
+    async fn kill_dhcp_server(plugin: &LinuxNetwork, vnet_uuid: Uuid) {
+        let pid = plugin.state.read().await.dhcp_servers[&vnet_uuid]
+            .child
+            .id();
+        kill(Pid::from_raw(pid as i32), Signal::SIGKILL).unwrap();
+        wait_for_dhcp_exit(plugin, vnet_uuid).await;
     }
 
-    async fn set_iface_down(&self, iface: String) -> FResult<()> {
-        log::trace!("set_iface_down {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
+    async fn wait_for_dhcp_exit(plugin: &LinuxNetwork, vnet_uuid: Uuid) {
+        for _ in 0..50 {
+            plugin.supervise_dhcp_servers().await;
+            if !plugin
+                .state
+                .read()
                 .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
+                .dhcp_servers
+                .contains_key(&vnet_uuid)
             {
-                state
-                    .nl_handler
-                    .link()
-                    .set(link.header.index)
-                    .down()
-                    .execute()
-                    .await
-                    .map_err(|e| FError::NetworkingError(format!("{}", e)))
-            } else {
-                Err(FError::NotFound)
+                return;
             }
-        })
+            task::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("dnsmasq of {} not seen exiting", vnet_uuid);
     }
 
-    async fn iface_exists(&self, iface: String) -> FResult<bool> {
-        log::trace!("iface_exists {}", iface);
-        let mut state = self.state.write().await;
-        state.tokio_rt.block_on(async {
-            let mut links = state
-                .nl_handler
-                .link()
-                .get()
-                .set_name_filter(iface)
-                .execute();
-            if let Some(link) = links
-                .try_next()
-                .await
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?
-            {
-                Ok(true)
-            } else {
-                Ok(false)
+    #[async_std::test]
+    async fn dhcp_server_restart() {
+        let (mut plugin, nl, store) = test_plugin().await;
+        let launcher = FakeDHCPLauncher::new();
+        plugin.dhcp_launcher = Arc::new(launcher.clone());
+        let vnet = test_vnet(Some(test_dhcp_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+        plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let dhcp = vnet_dhcp(&store, vnet.uuid).await;
+        assert_eq!(launcher.launches(), 1);
+
+        // restarted once the backoff elapsed
+        kill_dhcp_server(&plugin, vnet.uuid).await;
+        assert!(plugin
+            .state
+            .read()
+            .await
+            .pending_dhcp_servers
+            .contains_key(&vnet.uuid));
+        assert_eq!(launcher.launches(), 1);
+        skip_backoff(&plugin).await;
+        plugin.supervise_dhcp_servers().await;
+        assert_eq!(launcher.launches(), 2);
+        assert!(plugin
+            .state
+            .read()
+            .await
+            .dhcp_servers
+            .contains_key(&vnet.uuid));
+        assert_eq!(
+            last_supervision_event(&plugin).await,
+            SupervisionEventKind::DHCPRestarted {
+                vnet_uuid: vnet.uuid,
+                attempt: 1,
             }
-        })
+        );
+        assert!(plugin
+            .get_virtual_network_failures(vnet.uuid)
+            .await
+            .unwrap()
+            .is_empty());
+
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        remove_dhcp_markers(&dhcp);
+        assert_clean(&plugin, &nl, &store).await;
+        assert!(plugin.state.read().await.dhcp_servers.is_empty());
     }
 
-    async fn spawn_dnsmasq(&self, config_file: String) -> FResult<Child> {
-        let child = Command::new("dnsmasq")
-            .arg("-C")
-            .arg(config_file)
-            .stdin(Stdio::null())
-            .spawn()
-            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-        Ok(child)
+    #[async_std::test]
+    async fn dhcp_server_failure() {
+        let (mut plugin, nl, store) = test_plugin().await;
+        let launcher = FakeDHCPLauncher::with_script("exit 1");
+        plugin.dhcp_launcher = Arc::new(launcher.clone());
+        let vnet = test_vnet(Some(test_dhcp_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+        plugin.create_virtual_network(vnet.uuid).await.unwrap();
+
+        // each restart exits again
+        for _ in 0..SUPERVISION_MAX_RESTARTS {
+            wait_for_dhcp_exit(&plugin, vnet.uuid).await;
+            skip_backoff(&plugin).await;
+            plugin.supervise_dhcp_servers().await;
+        }
+        wait_for_dhcp_exit(&plugin, vnet.uuid).await;
+        assert_eq!(launcher.launches(), 1 + SUPERVISION_MAX_RESTARTS as usize);
+        assert!(matches!(
+            last_supervision_event(&plugin).await,
+            SupervisionEventKind::DHCPFailed { vnet_uuid, .. } if vnet_uuid == vnet.uuid
+        ));
+        let failures = plugin
+            .get_virtual_network_failures(vnet.uuid)
+            .await
+            .unwrap();
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with("dnsmasq: failed"));
+        // no more attempts
+        skip_backoff(&plugin).await;
+        plugin.supervise_dhcp_servers().await;
+        assert_eq!(launcher.launches(), 1 + SUPERVISION_MAX_RESTARTS as usize);
+        assert!(plugin.state.read().await.pending_dhcp_servers.is_empty());
+
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
     }
 
-    async fn create_dnsmasq_config(
-        &self,
-        iface: &str,
-        pid_file: &str,
-        lease_file: &str,
-        log_file: &str,
-        dhcp_start: IPAddress,
-        dhcp_end: IPAddress,
-        default_gw: IPAddress,
-        default_dns: IPAddress,
-    ) -> FResult<String> {
-        log::trace!(
-            "create_dnsmasq_config {} {} {} {} {} {} {}",
-            iface,
-            pid_file,
-            lease_file,
-            dhcp_start,
-            dhcp_end,
-            default_gw,
-            default_dns,
-        );
-        let mut context = Context::new();
-        let template_path = self
-            .get_path()
-            .join("*.conf")
-            .to_str()
-            .ok_or(FError::EncodingError)?
-            .to_string();
-        let templates =
-            Tera::new(&template_path).map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-        context.insert("dhcp_interface", iface);
-        context.insert("lease_file", lease_file);
-        context.insert("dhcp_pid", pid_file);
-        context.insert("dhcp_log", log_file);
-        context.insert("dhcp_start", &format!("{}", dhcp_start));
-        context.insert("dhcp_end", &format!("{}", dhcp_end));
-        context.insert("default_gw", &format!("{}", default_gw));
-        context.insert("default_dns", &format!("{}", default_dns));
+    #[async_std::test]
+    async fn dhcp_server_reload() {
+        let (mut plugin, nl, store) = test_plugin().await;
+        let launcher = FakeDHCPLauncher::new();
+        plugin.dhcp_launcher = Arc::new(launcher.clone());
+        let vnet = test_vnet(Some(test_dhcp_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+        plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let dhcp = vnet_dhcp(&store, vnet.uuid).await;
+        assert!(wait_for_file(&format!("{}.ready", dhcp.conf)).await);
+
+        // a new reservation is loaded with SIGHUP, without a restart
+        plugin
+            .add_dhcp_reservation(
+                vnet.uuid,
+                DHCPReservation {
+                    mac: MACAddress::new(2, 0, 0, 0, 0, 1),
+                    ip: "10.10.0.150".parse().unwrap(),
+                    hostname: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert!(wait_for_file(&format!("{}.reloads", dhcp.conf)).await);
+        plugin.supervise_dhcp_servers().await;
+        assert_eq!(launcher.launches(), 1);
+        assert!(plugin
+            .state
+            .read()
+            .await
+            .dhcp_servers
+            .contains_key(&vnet.uuid));
 
-        match templates.render("dnsmasq.conf", &context) {
-            Ok(t) => Ok(t),
-            Err(e) => {
-                log::error!("Parsing error(s): {} {}", e, e.source().unwrap());
-                Err(FError::NetworkingError(format!(
-                    "{} {}",
-                    e,
-                    e.source().unwrap()
-                )))
-            }
-        }
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        remove_dhcp_markers(&dhcp);
+        assert_clean(&plugin, &nl, &store).await;
     }
 
-    async fn configure_nat(&self, net: IpNetwork, iface: &str) -> FResult<String> {
-        let table_name = self.generate_random_nft_table_name();
-        let chain_name = String::from("postrouting");
-        // Create a batch. This is used to store all the netlink messages we will later send.
-        // Creating a new batch also automatically writes the initial batch begin message needed
-        // to tell netlink this is a single transaction that might arrive over multiple netlink packets.
-        let mut batch = Batch::new();
-        // Create a netfilter table operating on both IPv4 and IPv6 (ProtoFamily::Inet)
-        let table = Table::new(
-            &CString::new(table_name.clone())
-                .map_err(|e| FError::NetworkingError(format!("{}", e)))?,
-            ProtoFamily::Inet,
-        );
-        // Add the table to the batch with the `MsgType::Add` type, thus instructing netfilter to add
-        // this table under its `ProtoFamily::Inet` ruleset.
-        batch.add(&table, nftnl::MsgType::Add);
-
-        // Create a chain under the table we created above.
-        let mut chain = Chain::new(
-            &CString::new(chain_name).map_err(|e| FError::NetworkingError(format!("{}", e)))?,
-            &table,
+    #[async_std::test]
+    async fn reconcile_restores_virtual_network() {
+        let (plugin, nl, store) = test_plugin().await;
+        let vnet = test_vnet(Some(test_ip_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+        let created = plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let ns_info = deserialize_network_internals(created.plugin_internals.as_ref().unwrap())
+            .unwrap()
+            .associated_netns
+            .unwrap();
+        let bridge = plugin.get_virtual_network_bridge(&created).await.unwrap();
+        let internal_bridge = store
+            .interfaces()
+            .into_iter()
+            .find(|i| i.net_ns.is_some() && matches!(i.kind, VirtualInterfaceKind::BRIDGE(_)))
+            .unwrap();
+        let ns_nl = nl.in_netns(&ns_info.ns_name);
+
+        // lost while the plugin was down
+        plugin.remove_ns_manager(&ns_info.ns_uuid).await.unwrap();
+        nl.del_iface(bridge.if_name.clone()).await.unwrap();
+        ns_nl
+            .del_iface(internal_bridge.if_name.clone())
+            .await
+            .unwrap();
+        nl.del_nat_table(owner_table_name(&vnet.uuid))
+            .await
+            .unwrap();
+
+        let plugin = restarted_plugin(&nl, &store);
+        plugin.reconcile().await.unwrap();
+        assert!(plugin.get_ns_manager(&ns_info.ns_uuid).await.is_ok());
+        let br_link = nl.link(&bridge.if_name).unwrap();
+        assert!(br_link.up);
+        assert_eq!(br_link.addresses, vec![("10.10.0.1".parse().unwrap(), 24)]);
+        assert_eq!(br_link.alias, Some(owner_tag(&bridge.uuid)));
+        // the ports are attached again
+        assert_eq!(
+            nl.links()
+                .iter()
+                .filter(|l| l.master == Some(bridge.if_name.clone()))
+                .count(),
+            2
         );
+        assert!(ns_nl
+            .links()
+            .iter()
+            .any(|l| l.master == Some(internal_bridge.if_name.clone())));
+        assert!(nl.nat_tables().contains_key(&owner_table_name(&vnet.uuid)));
+        assert!(vnet_failures(&store, vnet.uuid).await.is_empty());
+
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
+    }
 
-        // Hook the chains to the input and output event hooks, with highest priority (priority zero).
-        // See the `Chain::set_hook` documentation for details.
-        chain.set_hook(nftnl::Hook::PostRouting, 0);
-        // Set the chain type.
-        // See the `Chain::set_type` documentation for details.
-        chain.set_type(nftnl::ChainType::Nat);
-
-        // Add the two chains to the batch with the `MsgType` to tell netfilter to create the chains
-        // under the table.
-        batch.add(&chain, nftnl::MsgType::Add);
-
-        // Create a new rule object under the input chain.
-        let mut natting_rule = Rule::new(&chain);
-
-        // Lookup the interface index of the default gw interface.
-        let iface_index = iface_index(iface)?;
-        //Type of payload is source address
-        natting_rule.add_expr(&nft_expr!(payload ipv4 saddr));
-
-        //netmask of the network
-        natting_rule.add_expr(&nft_expr!(bitwise mask net.mask(), xor 0u32));
-
-        //comparing ip portion of the address
-        natting_rule.add_expr(&nft_expr!(cmp == net.ip()));
-
-        // passing the index of output interface oif
-        natting_rule.add_expr(&nft_expr!(meta oif));
-
-        //use interface with this index
-        natting_rule.add_expr(&nft_expr!(cmp == iface_index));
-
-        // Add masquerading
-        natting_rule.add_expr(&nft_expr!(masquerade));
-
-        // Add the rule to the batch.
-        batch.add(&natting_rule, nftnl::MsgType::Add);
-
-        // === FINALIZE THE TRANSACTION AND SEND THE DATA TO NETFILTER ===
-
-        // Finalize the batch. This means the batch end message is written into the batch, telling
-        // netfilter the we reached the end of the transaction message. It's also converted to a type
-        // that implements `IntoIterator<Item = &'a [u8]>`, thus allowing us to get the raw netlink data
-        // out so it can be sent over a netlink socket to netfilter.
-        let finalized_batch = batch.finalize();
-
-        fn send_and_process(batch: &FinalizedBatch) -> FResult<()> {
-            // Create a netlink socket to netfilter.
-            let socket = mnl::Socket::new(mnl::Bus::Netfilter)?;
-            // Send all the bytes in the batch.
-            socket.send_all(batch)?;
-            // Try to parse the messages coming back from netfilter. This part is still very unclear.
-            let portid = socket.portid();
-            let mut buffer = vec![0; nftnl::nft_nlmsg_maxsize() as usize];
-            let very_unclear_what_this_is_for = 2;
-            while let Some(message) = socket_recv(&socket, &mut buffer[..])? {
-                match mnl::cb_run(message, very_unclear_what_this_is_for, portid)? {
-                    mnl::CbResult::Stop => {
-                        break;
-                    }
-                    mnl::CbResult::Ok => (),
-                }
-            }
-            Ok(())
+    #[async_std::test]
+    async fn reconcile_removes_orphans() {
+        let (plugin, nl, store) = test_plugin().await;
+        let vnet = test_vnet(Some(test_ip_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+        plugin.create_virtual_network(vnet.uuid).await.unwrap();
+
+        // left by an instance that crashed before writing the records
+        let orphan = Uuid::new_v4();
+        nl.create_bridge("orphan0".to_string()).await.unwrap();
+        nl.set_iface_alias("orphan0".to_string(), owner_tag(&orphan))
+            .await
+            .unwrap();
+        nl.add_nat_table(
+            owner_table_name(&orphan),
+            orphan,
+            vec!["10.20.0.0/24".parse().unwrap()],
+            "eth0".to_string(),
+        )
+        .await
+        .unwrap();
+        // not created by fog05
+        nl.create_bridge("br-user".to_string()).await.unwrap();
+
+        let plugin = restarted_plugin(&nl, &store);
+        plugin.reconcile().await.unwrap();
+        assert!(nl.link("orphan0").is_none());
+        assert!(!nl.nat_tables().contains_key(&owner_table_name(&orphan)));
+        assert!(nl.link("br-user").is_some());
+        assert!(nl.nat_tables().contains_key(&owner_table_name(&vnet.uuid)));
+        assert_eq!(store.interfaces().len(), 5);
+
+        nl.del_iface("br-user".to_string()).await.unwrap();
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
+    }
+
+    #[async_std::test]
+    async fn reconcile_during_creation() {
+        let (plugin, nl, store) = test_plugin().await;
+        let vnet = test_vnet(Some(test_ip_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+
+        // the creation stops before writing the network record,
+        // once its links and NAT table are in the kernel
+        let resume = store.pause_virtual_network_writes();
+        let creating = {
+            let plugin = plugin.clone();
+            let vnet_uuid = vnet.uuid;
+            task::spawn(async move { plugin.create_virtual_network(vnet_uuid).await })
+        };
+        while !nl.nat_tables().contains_key(&owner_table_name(&vnet.uuid)) {
+            task::sleep(Duration::from_millis(10)).await;
         }
 
-        fn socket_recv<'a>(socket: &mnl::Socket, buf: &'a mut [u8]) -> FResult<Option<&'a [u8]>> {
-            let ret = socket.recv(buf)?;
-            if ret > 0 {
-                Ok(Some(&buf[..ret]))
-            } else {
-                Ok(None)
+        plugin.reconcile().await.unwrap();
+        drop(resume);
+        let created = creating.await.unwrap();
+        assert!(nl.nat_tables().contains_key(&owner_table_name(&vnet.uuid)));
+        for iface in store.interfaces() {
+            if iface.net_ns.is_none() {
+                assert!(
+                    nl.link(&iface.if_name).is_some(),
+                    "{} removed",
+                    iface.if_name
+                );
             }
         }
+        assert_eq!(store.interfaces().len(), created.interfaces.len());
 
-        // Look up the interface index for a given interface name.
-        fn iface_index(name: &str) -> FResult<libc::c_uint> {
-            let c_name =
-                CString::new(name).map_err(|e| FError::NetworkingError(format!("{}", e)))?;
-            let index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
-            if index == 0 {
-                Err(FError::from(std::io::Error::last_os_error()))
-            } else {
-                Ok(index)
-            }
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
+    }
+
+    #[async_std::test]
+    async fn connection_point_create_rollback() {
+        let failures = [
+            ("add_netns", 0),
+            // the bridge is created by the namespace manager
+            ("create_bridge", 0),
+            ("create_veth", 0),
+            ("set_iface_ns", 0),
+            ("set_iface_master", 0),
+        ];
+        for (op, skip) in failures.iter() {
+            let (plugin, nl, store) = test_plugin().await;
+            nl.inject_failure(op, *skip);
+            assert!(
+                plugin.create_connection_point().await.is_err(),
+                "{} did not fail",
+                op
+            );
+            assert_clean(&plugin, &nl, &store).await;
         }
+    }
 
-        send_and_process(&finalized_batch)?;
-        Ok(table_name)
+    #[async_std::test]
+    async fn destroy_unbound_connection_point() {
+        let (mut plugin, nl, store) = test_plugin().await;
+        plugin.config.shutdown_policy = Some(ShutdownPolicy::Destroy);
+        plugin.create_connection_point().await.unwrap();
+
+        let (stop, _stopped) = async_std::channel::bounded(1);
+        plugin.stop(stop).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
     }
 
-    async fn clean_nat(&self, table_name: String) -> FResult<()> {
-        // Create a batch. This is used to store all the netlink messages we will later send.
-        // Creating a new batch also automatically writes the initial batch begin message needed
-        // to tell netlink this is a single transaction that might arrive over multiple netlink packets.
-        let mut batch = Batch::new();
-        // Create a netfilter table operating on both IPv4 and IPv6 (ProtoFamily::Inet)
-        let table = Table::new(
-            &CString::new(table_name).map_err(|e| FError::NetworkingError(format!("{}", e)))?,
-            ProtoFamily::Inet,
+    #[async_std::test]
+    async fn keep_leaves_dhcp_servers_running() {
+        let (mut plugin, _nl, store) = test_plugin().await;
+        plugin.dhcp_launcher = Arc::new(FakeDHCPLauncher::new());
+        plugin.create_default_virtual_network(false).await.unwrap();
+        let vnet = test_vnet(Some(test_dhcp_configuration()));
+        store.add_global_virtual_network(vnet.clone());
+        plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let dhcp = vnet_dhcp(&store, vnet.uuid).await;
+        let pid = plugin.state.read().await.dhcp_servers[&vnet.uuid]
+            .child
+            .id() as i32;
+
+        let (stop, _stopped) = async_std::channel::bounded(1);
+        plugin.stop(stop).await.unwrap();
+        assert!(store.get_virtual_network(Uuid::nil()).await.is_err());
+        assert!(store.get_virtual_network(vnet.uuid).await.is_ok());
+        assert!(plugin.state.read().await.dhcp_servers.is_empty());
+        assert!(kill(Pid::from_raw(pid), None).is_ok());
+
+        kill(Pid::from_raw(pid), Signal::SIGKILL).unwrap();
+        nix::sys::wait::waitpid(Pid::from_raw(pid), None).unwrap();
+        remove_dhcp_markers(&dhcp);
+    }
+
+    #[async_std::test]
+    async fn move_interface_between_namespaces() {
+        let (plugin, nl, store) = test_plugin().await;
+        let ns_a = plugin.create_network_namespace().await.unwrap();
+        let ns_b = plugin.create_network_namespace().await.unwrap();
+        let iface = plugin
+            .create_macvlan_interface("eth0".to_string())
+            .await
+            .unwrap();
+
+        plugin
+            .move_interface_info_namespace(iface.uuid, ns_a.uuid)
+            .await
+            .unwrap();
+        assert!(nl.in_netns(&ns_a.ns_name).link(&iface.if_name).is_some());
+
+        let iface = plugin
+            .move_interface_info_namespace(iface.uuid, ns_b.uuid)
+            .await
+            .unwrap();
+        assert_eq!(iface.net_ns, Some(ns_b.uuid));
+        assert!(nl.in_netns(&ns_a.ns_name).link(&iface.if_name).is_none());
+        assert!(nl.in_netns(&ns_b.ns_name).link(&iface.if_name).is_some());
+        assert!(nl.link(&iface.if_name).is_none());
+
+        let ns_a = store.get_network_namespace(ns_a.uuid).await.unwrap();
+        let ns_b = store.get_network_namespace(ns_b.uuid).await.unwrap();
+        assert!(ns_a.interfaces.is_empty());
+        assert_eq!(ns_b.interfaces, vec![iface.uuid]);
+        assert_eq!(
+            store.get_interface(iface.uuid).await.unwrap().net_ns,
+            Some(ns_b.uuid)
         );
-        // Add the table to the batch with the `MsgType::Del` type, thus instructing netfilter to remove
-        // this table under its `ProtoFamily::Inet` ruleset.
-        batch.add(&table, nftnl::MsgType::Del);
-
-        // === FINALIZE THE TRANSACTION AND SEND THE DATA TO NETFILTER ===
-
-        // Finalize the batch. This means the batch end message is written into the batch, telling
-        // netfilter the we reached the end of the transaction message. It's also converted to a type
-        // that implements `IntoIterator<Item = &'a [u8]>`, thus allowing us to get the raw netlink data
-        // out so it can be sent over a netlink socket to netfilter.
-        let finalized_batch = batch.finalize();
-
-        fn send_and_process(batch: &FinalizedBatch) -> FResult<()> {
-            // Create a netlink socket to netfilter.
-            let socket = mnl::Socket::new(mnl::Bus::Netfilter)?;
-            // Send all the bytes in the batch.
-            socket.send_all(batch)?;
-            // Try to parse the messages coming back from netfilter. This part is still very unclear.
-            let portid = socket.portid();
-            let mut buffer = vec![0; nftnl::nft_nlmsg_maxsize() as usize];
-            let very_unclear_what_this_is_for = 2;
-            while let Some(message) = socket_recv(&socket, &mut buffer[..])? {
-                match mnl::cb_run(message, very_unclear_what_this_is_for, portid)? {
-                    mnl::CbResult::Stop => {
-                        break;
-                    }
-                    mnl::CbResult::Ok => (),
-                }
-            }
-            Ok(())
-        }
+    }
 
-        fn socket_recv<'a>(socket: &mnl::Socket, buf: &'a mut [u8]) -> FResult<Option<&'a [u8]>> {
-            let ret = socket.recv(buf)?;
-            if ret > 0 {
-                Ok(Some(&buf[..ret]))
-            } else {
-                Ok(None)
-            }
-        }
+    #[async_std::test]
+    async fn connection_point_attach_detach() {
+        let (plugin, nl, store) = test_plugin().await;
+        let vnet = test_vnet(None);
+        store.add_global_virtual_network(vnet.clone());
+        let vnet = plugin.create_virtual_network(vnet.uuid).await.unwrap();
+        let bridge = plugin.get_virtual_network_bridge(&vnet).await.unwrap();
+
+        let cp = plugin.create_connection_point().await.unwrap();
+        let netns = store.get_network_namespace(cp.net_ns).await.unwrap();
+        let ns = nl.in_netns(&netns.ns_name);
+        let cp_bridge = store.get_interface(cp.bridge).await.unwrap();
+        let internal_veth = store.get_interface(cp.internal_veth).await.unwrap();
+        assert_eq!(
+            ns.link(&internal_veth.if_name).unwrap().master,
+            Some(cp_bridge.if_name.clone())
+        );
 
-        send_and_process(&finalized_batch)?;
-        Ok(())
+        plugin
+            .bind_connection_point_to_virtual_network(cp.uuid, vnet.uuid)
+            .await
+            .unwrap();
+        let external_veth = store.get_interface(cp.external_veth).await.unwrap();
+        assert_eq!(external_veth.parent, Some(bridge.uuid));
+        assert_eq!(
+            nl.link(&external_veth.if_name).unwrap().master,
+            Some(bridge.if_name.clone())
+        );
+
+        let iface = plugin
+            .create_macvlan_interface("eth0".to_string())
+            .await
+            .unwrap();
+        let iface = plugin
+            .bind_interface_to_connection_point(iface.uuid, cp.uuid)
+            .await
+            .unwrap();
+        assert_eq!(iface.net_ns, Some(cp.net_ns));
+        assert_eq!(iface.parent, Some(cp.bridge));
+        let link = ns.link(&iface.if_name).unwrap();
+        assert_eq!(link.master, Some(cp_bridge.if_name.clone()));
+        assert!(link.up);
+        assert!(plugin.delete_connection_point(cp.uuid).await.is_err());
+
+        let iface = plugin
+            .unbind_interface_from_connection_point(iface.uuid, cp.uuid)
+            .await
+            .unwrap();
+        assert_eq!(iface.net_ns, None);
+        assert!(ns.link(&iface.if_name).is_none());
+        assert!(nl.link(&iface.if_name).is_some());
+        plugin.delete_virtual_interface(iface.uuid).await.unwrap();
+
+        plugin
+            .unbind_connection_point_from_virtual_network(cp.uuid, vnet.uuid)
+            .await
+            .unwrap();
+        assert_eq!(nl.link(&external_veth.if_name).unwrap().master, None);
+        plugin.delete_connection_point(cp.uuid).await.unwrap();
+        assert!(nl.link(&external_veth.if_name).is_none());
+        assert!(store.connection_points().is_empty());
+
+        plugin.delete_virtual_network(vnet.uuid).await.unwrap();
+        assert_clean(&plugin, &nl, &store).await;
+    }
+
+    #[test]
+    fn invalid_config() {
+        let new_plugin = |config: LinuxNetworkConfig| {
+            let nl = FakeNetlinkBackend::new();
+            LinuxNetwork::new_with_backend(
+                std::process::id(),
+                config,
+                Arc::new(nl.clone()),
+                Arc::new(FakeStore::new()),
+                Arc::new(FakeNSManagerLauncher::new(nl)),
+            )
+        };
+        assert!(new_plugin(test_config()).is_ok());
+        let mut config = test_config();
+        config.name_prefix = Some("fos-node".to_string());
+        assert!(new_plugin(config).is_err());
+        let mut config = test_config();
+        config.monitoring_interveal = 0;
+        assert!(new_plugin(config).is_err());
+    }
+
+    #[test]
+    fn iface_names() {
+        assert!(validate_iface_name("fosbr0").is_ok());
+        assert!(validate_iface_name("fos0123456789ab").is_ok());
+        assert!(validate_iface_name("fos0123456789abc").is_err());
+        assert!(validate_iface_name("").is_err());
+        assert!(validate_iface_name("..").is_err());
+        assert!(validate_iface_name("br/0").is_err());
+        assert!(validate_iface_name("br 0").is_err());
+        assert!(validate_iface_name("eth0:1").is_err());
     }
 }
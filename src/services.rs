@@ -0,0 +1,622 @@
+/*********************************************************************************
+* Copyright (c) 2018,2020 ADLINK Technology Inc.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0, or the Apache Software License 2.0
+* which is available at https://www.apache.org/licenses/LICENSE-2.0.
+*
+* SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+* Contributors:
+*   ADLINK fog05 team, <fog05@adlink-labs.tech>
+*********************************************************************************/
+
+//! Services the plugin depends on: the local store, the Agent, the OS plugin
+//! and the Namespace Managers. Each one is a trait so the plugin can run
+//! on the in-memory implementations of [`fake`].
+
+pub mod fake;
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use async_std::sync::{Arc, Mutex};
+use async_std::task;
+
+use futures::stream::{BoxStream, StreamExt};
+use futures::FutureExt;
+
+use async_trait::async_trait;
+
+use fog05_sdk::agent::{AgentPluginInterfaceClient, OSClient};
+use fog05_sdk::fresult::{FError, FResult};
+use fog05_sdk::types::{
+    ConnectionPoint, IPAddress, NetworkNamespace, PluginKind, VirtualInterface, VirtualNetwork,
+};
+use fog05_sdk::zconnector::ZConnector;
+
+use ipnetwork::IpNetwork;
+
+use uuid::Uuid;
+
+use crate::errors::LinuxNetworkError;
+use crate::netlink::{NetlinkBackend, NetlinkEvent};
+use crate::types::{
+    NSManagerProcess, NamespaceManagerClient, NETLINK_EVENTS_POLL, NS_MANAGER_READY,
+};
+
+/// Records of the plugin, kept in the local store of the node
+#[async_trait]
+pub trait NetworkStore: Send + Sync {
+    /// Returns the descriptor of a virtual network from the global store
+    async fn get_global_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork>;
+    async fn get_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork>;
+    async fn add_virtual_network(&self, vnet: &VirtualNetwork) -> FResult<()>;
+    async fn remove_virtual_network(&self, vnet_uuid: Uuid) -> FResult<()>;
+    async fn get_all_virtual_networks(&self) -> FResult<Vec<VirtualNetwork>>;
+    async fn get_interface(&self, iface_uuid: Uuid) -> FResult<VirtualInterface>;
+    async fn add_interface(&self, iface: &VirtualInterface) -> FResult<()>;
+    async fn remove_interface(&self, iface_uuid: Uuid) -> FResult<()>;
+    async fn get_network_namespace(&self, ns_uuid: Uuid) -> FResult<NetworkNamespace>;
+    async fn add_network_namespace(&self, netns: &NetworkNamespace) -> FResult<()>;
+    async fn remove_network_namespace(&self, ns_uuid: Uuid) -> FResult<()>;
+    async fn get_all_network_namespaces(&self) -> FResult<Vec<NetworkNamespace>>;
+    async fn get_connection_point(&self, cp_uuid: Uuid) -> FResult<ConnectionPoint>;
+    async fn add_connection_point(&self, cp: &ConnectionPoint) -> FResult<()>;
+    async fn remove_connection_point(&self, cp_uuid: Uuid) -> FResult<()>;
+    async fn get_all_connection_points(&self) -> FResult<Vec<ConnectionPoint>>;
+}
+
+/// Calls of the Agent used by the plugin
+#[async_trait]
+pub trait AgentService: Send + Sync {
+    async fn get_node_uuid(&self) -> FResult<Uuid>;
+    async fn register_plugin(&self, plugin_uuid: Uuid, kind: PluginKind) -> FResult<()>;
+    async fn unregister_plugin(&self, plugin_uuid: Uuid) -> FResult<()>;
+}
+
+/// Calls of the OS plugin used by the plugin
+#[async_trait]
+pub trait OSService: Send + Sync {
+    async fn store_file(&self, content: Vec<u8>, file_path: String) -> FResult<()>;
+}
+
+/// Operations of a Namespace Manager, they act inside its network namespace,
+/// see [`crate::types::NamespaceManager`]
+#[async_trait]
+pub trait NSManagerOps: Send + Sync {
+    async fn verify_server(&self) -> FResult<bool>;
+    async fn set_virtual_interface_up(&self, iface: String) -> FResult<()>;
+    async fn set_virtual_interface_down(&self, iface: String) -> FResult<()>;
+    async fn check_virtual_interface_exists(&self, iface: String) -> FResult<bool>;
+    async fn move_virtual_interface_into_default_ns(&self, iface: String) -> FResult<()>;
+    async fn set_virtual_interface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()>;
+    async fn set_virtual_interface_name(&self, iface: String, name: String) -> FResult<()>;
+    async fn set_virtual_interface_alias(&self, iface: String, alias: String) -> FResult<()>;
+    async fn del_virtual_interface_address(&self, iface: String, addr: IPAddress) -> FResult<()>;
+    async fn get_virtual_interface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>>;
+    async fn add_virtual_interface_address(
+        &self,
+        iface: String,
+        addr: Option<IpNetwork>,
+    ) -> FResult<Vec<IPAddress>>;
+    async fn set_virtual_interface_master(&self, iface: String, master: String) -> FResult<()>;
+    async fn set_virtual_interface_nomaster(&self, iface: String) -> FResult<()>;
+    async fn del_virtual_interface(&self, iface: String) -> FResult<()>;
+    async fn add_virtual_interface_ptp_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        local_addr: IPAddress,
+        remote_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()>;
+    async fn add_virtual_interface_mcast_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        mcast_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()>;
+    async fn add_virtual_interface_vlan(&self, iface: String, dev: String, tag: u16)
+        -> FResult<()>;
+    async fn add_virtual_interface_veth(&self, iface_i: String, iface_e: String) -> FResult<()>;
+    async fn add_virtual_interface_bridge(&self, br_name: String) -> FResult<()>;
+    async fn list_interfaces(&self) -> FResult<Vec<String>>;
+    async fn next_netlink_events(&self) -> FResult<Vec<NetlinkEvent>>;
+}
+
+/// Starts the Namespace Manager of a network namespace
+#[async_trait]
+pub trait NSManagerLauncher: Send + Sync {
+    /// Returns once the manager is serving
+    async fn launch(&self, ns_name: String, ns_uuid: Uuid) -> FResult<NSManagerProcess>;
+}
+
+/// Starts the DHCP server of a virtual network
+#[async_trait]
+pub trait DHCPLauncher: Send + Sync {
+    /// If a namespace is given the server is started inside it
+    async fn launch(&self, config_file: String, netns: Option<String>) -> FResult<Child>;
+}
+
+/// Store backed by the zenoh connector of the node
+pub struct ZConnectorStore {
+    connector: Arc<ZConnector>,
+}
+
+impl ZConnectorStore {
+    pub fn new(connector: Arc<ZConnector>) -> Self {
+        Self { connector }
+    }
+}
+
+#[async_trait]
+impl NetworkStore for ZConnectorStore {
+    async fn get_global_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork> {
+        self.connector.global.get_virtual_network(vnet_uuid).await
+    }
+    async fn get_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork> {
+        self.connector.local.get_virtual_network(vnet_uuid).await
+    }
+    async fn add_virtual_network(&self, vnet: &VirtualNetwork) -> FResult<()> {
+        self.connector.local.add_virutal_network(vnet).await
+    }
+    async fn remove_virtual_network(&self, vnet_uuid: Uuid) -> FResult<()> {
+        self.connector.local.remove_virtual_network(vnet_uuid).await
+    }
+    async fn get_all_virtual_networks(&self) -> FResult<Vec<VirtualNetwork>> {
+        self.connector.local.get_all_virtual_networks().await
+    }
+    async fn get_interface(&self, iface_uuid: Uuid) -> FResult<VirtualInterface> {
+        self.connector.local.get_interface(iface_uuid).await
+    }
+    async fn add_interface(&self, iface: &VirtualInterface) -> FResult<()> {
+        self.connector.local.add_interface(iface).await
+    }
+    async fn remove_interface(&self, iface_uuid: Uuid) -> FResult<()> {
+        self.connector.local.remove_interface(iface_uuid).await
+    }
+    async fn get_network_namespace(&self, ns_uuid: Uuid) -> FResult<NetworkNamespace> {
+        self.connector.local.get_network_namespace(ns_uuid).await
+    }
+    async fn add_network_namespace(&self, netns: &NetworkNamespace) -> FResult<()> {
+        self.connector.local.add_network_namespace(netns).await
+    }
+    async fn remove_network_namespace(&self, ns_uuid: Uuid) -> FResult<()> {
+        self.connector.local.remove_network_namespace(ns_uuid).await
+    }
+    async fn get_all_network_namespaces(&self) -> FResult<Vec<NetworkNamespace>> {
+        self.connector.local.get_all_network_namespaces().await
+    }
+    async fn get_connection_point(&self, cp_uuid: Uuid) -> FResult<ConnectionPoint> {
+        self.connector.local.get_connection_point(cp_uuid).await
+    }
+    async fn add_connection_point(&self, cp: &ConnectionPoint) -> FResult<()> {
+        self.connector.local.add_connection_point(cp).await
+    }
+    async fn remove_connection_point(&self, cp_uuid: Uuid) -> FResult<()> {
+        self.connector.local.remove_connection_point(cp_uuid).await
+    }
+    async fn get_all_connection_points(&self) -> FResult<Vec<ConnectionPoint>> {
+        self.connector.local.get_all_connection_points().await
+    }
+}
+
+#[async_trait]
+impl AgentService for AgentPluginInterfaceClient {
+    async fn get_node_uuid(&self) -> FResult<Uuid> {
+        AgentPluginInterfaceClient::get_node_uuid(self).await?
+    }
+    async fn register_plugin(&self, plugin_uuid: Uuid, kind: PluginKind) -> FResult<()> {
+        AgentPluginInterfaceClient::register_plugin(self, plugin_uuid, kind).await??;
+        Ok(())
+    }
+    async fn unregister_plugin(&self, plugin_uuid: Uuid) -> FResult<()> {
+        AgentPluginInterfaceClient::unregister_plugin(self, plugin_uuid).await??;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OSService for OSClient {
+    async fn store_file(&self, content: Vec<u8>, file_path: String) -> FResult<()> {
+        OSClient::store_file(self, content, file_path).await??;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NSManagerOps for NamespaceManagerClient {
+    async fn verify_server(&self) -> FResult<bool> {
+        Ok(NamespaceManagerClient::verify_server(self).await?)
+    }
+    async fn set_virtual_interface_up(&self, iface: String) -> FResult<()> {
+        NamespaceManagerClient::set_virtual_interface_up(self, iface).await?
+    }
+    async fn set_virtual_interface_down(&self, iface: String) -> FResult<()> {
+        NamespaceManagerClient::set_virtual_interface_down(self, iface).await?
+    }
+    async fn check_virtual_interface_exists(&self, iface: String) -> FResult<bool> {
+        NamespaceManagerClient::check_virtual_interface_exists(self, iface).await?
+    }
+    async fn move_virtual_interface_into_default_ns(&self, iface: String) -> FResult<()> {
+        NamespaceManagerClient::move_virtual_interface_into_default_ns(self, iface).await?
+    }
+    async fn set_virtual_interface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()> {
+        NamespaceManagerClient::set_virtual_interface_mac(self, iface, address).await?
+    }
+    async fn set_virtual_interface_name(&self, iface: String, name: String) -> FResult<()> {
+        NamespaceManagerClient::set_virtual_interface_name(self, iface, name).await?
+    }
+    async fn set_virtual_interface_alias(&self, iface: String, alias: String) -> FResult<()> {
+        NamespaceManagerClient::set_virtual_interface_alias(self, iface, alias).await?
+    }
+    async fn del_virtual_interface_address(&self, iface: String, addr: IPAddress) -> FResult<()> {
+        NamespaceManagerClient::del_virtual_interface_address(self, iface, addr).await?
+    }
+    async fn get_virtual_interface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>> {
+        NamespaceManagerClient::get_virtual_interface_addresses(self, iface).await?
+    }
+    async fn add_virtual_interface_address(
+        &self,
+        iface: String,
+        addr: Option<IpNetwork>,
+    ) -> FResult<Vec<IPAddress>> {
+        NamespaceManagerClient::add_virtual_interface_address(self, iface, addr).await?
+    }
+    async fn set_virtual_interface_master(&self, iface: String, master: String) -> FResult<()> {
+        NamespaceManagerClient::set_virtual_interface_master(self, iface, master).await?
+    }
+    async fn set_virtual_interface_nomaster(&self, iface: String) -> FResult<()> {
+        NamespaceManagerClient::set_virtual_interface_nomaster(self, iface).await?
+    }
+    async fn del_virtual_interface(&self, iface: String) -> FResult<()> {
+        NamespaceManagerClient::del_virtual_interface(self, iface).await?
+    }
+    async fn add_virtual_interface_ptp_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        local_addr: IPAddress,
+        remote_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()> {
+        NamespaceManagerClient::add_virtual_interface_ptp_vxlan(
+            self,
+            iface,
+            dev,
+            vni,
+            local_addr,
+            remote_addr,
+            port,
+        )
+        .await?
+    }
+    async fn add_virtual_interface_mcast_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        mcast_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()> {
+        NamespaceManagerClient::add_virtual_interface_mcast_vxlan(
+            self, iface, dev, vni, mcast_addr, port,
+        )
+        .await?
+    }
+    async fn add_virtual_interface_vlan(
+        &self,
+        iface: String,
+        dev: String,
+        tag: u16,
+    ) -> FResult<()> {
+        NamespaceManagerClient::add_virtual_interface_vlan(self, iface, dev, tag).await?
+    }
+    async fn add_virtual_interface_veth(&self, iface_i: String, iface_e: String) -> FResult<()> {
+        NamespaceManagerClient::add_virtual_interface_veth(self, iface_i, iface_e).await?
+    }
+    async fn add_virtual_interface_bridge(&self, br_name: String) -> FResult<()> {
+        NamespaceManagerClient::add_virtual_interface_bridge(self, br_name).await?
+    }
+    async fn list_interfaces(&self) -> FResult<Vec<String>> {
+        NamespaceManagerClient::list_interfaces(self).await?
+    }
+    async fn next_netlink_events(&self) -> FResult<Vec<NetlinkEvent>> {
+        NamespaceManagerClient::next_netlink_events(self).await?
+    }
+}
+
+/// Namespace Manager operations done with a netlink backend
+/// of the namespace, as served by fos-net-linux-ns-manager.
+/// The DHCP client is started in the namespace of the calling process.
+#[derive(Clone)]
+pub struct NetlinkNSManager {
+    nl: Arc<dyn NetlinkBackend>,
+    /// Changes of the namespace not yet passed to the plugin
+    events: Arc<Mutex<Option<BoxStream<'static, NetlinkEvent>>>>,
+}
+
+impl NetlinkNSManager {
+    pub fn new(nl: Arc<dyn NetlinkBackend>) -> Self {
+        Self {
+            nl,
+            events: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Starts queuing the netlink events of the namespace,
+    /// the manager does it before reporting it is serving
+    pub async fn subscribe(&self) -> FResult<()> {
+        let events = self.nl.subscribe().await?;
+        *self.events.lock().await = Some(events);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NSManagerOps for NetlinkNSManager {
+    async fn verify_server(&self) -> FResult<bool> {
+        Ok(true)
+    }
+    async fn set_virtual_interface_up(&self, iface: String) -> FResult<()> {
+        self.nl.set_iface_up(iface).await
+    }
+    async fn set_virtual_interface_down(&self, iface: String) -> FResult<()> {
+        self.nl.set_iface_down(iface).await
+    }
+    async fn check_virtual_interface_exists(&self, iface: String) -> FResult<bool> {
+        self.nl.iface_exists(iface).await
+    }
+    async fn move_virtual_interface_into_default_ns(&self, iface: String) -> FResult<()> {
+        self.nl.set_iface_default_ns(iface).await
+    }
+    async fn set_virtual_interface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()> {
+        self.nl.set_iface_mac(iface, address).await
+    }
+    async fn set_virtual_interface_name(&self, iface: String, name: String) -> FResult<()> {
+        self.nl.set_iface_name(iface, name).await
+    }
+    async fn set_virtual_interface_alias(&self, iface: String, alias: String) -> FResult<()> {
+        self.nl.set_iface_alias(iface, alias).await
+    }
+    async fn del_virtual_interface_address(&self, iface: String, addr: IPAddress) -> FResult<()> {
+        self.nl.del_iface_address(iface, addr).await
+    }
+    async fn get_virtual_interface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>> {
+        self.nl.get_iface_addresses(iface).await
+    }
+    async fn add_virtual_interface_address(
+        &self,
+        iface: String,
+        addr: Option<IpNetwork>,
+    ) -> FResult<Vec<IPAddress>> {
+        log::trace!("add_virtual_interface_address {} {:?}", iface, addr);
+        match addr {
+            Some(addr) => {
+                self.nl
+                    .add_iface_address(iface.clone(), addr.ip(), addr.prefix())
+                    .await?;
+                self.nl.get_iface_addresses(iface).await
+            }
+            None => {
+                log::trace!("Using DHCP");
+                // If the address is None we spawn a DHCP client
+                // and then we the the address from netlink
+                let dhclient_iface = iface.clone();
+                let res = task::spawn_blocking(move || {
+                    Command::new("dhclient")
+                        .arg("-i")
+                        .arg(dhclient_iface)
+                        .status()
+                })
+                .await
+                .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+                log::trace!("DHCP Client exited with {:?}", res);
+                self.nl.get_iface_addresses(iface).await
+            }
+        }
+    }
+    async fn set_virtual_interface_master(&self, iface: String, master: String) -> FResult<()> {
+        self.nl.set_iface_master(iface, master).await
+    }
+    async fn set_virtual_interface_nomaster(&self, iface: String) -> FResult<()> {
+        self.nl.del_iface_master(iface).await
+    }
+    async fn del_virtual_interface(&self, iface: String) -> FResult<()> {
+        self.nl.del_iface(iface).await
+    }
+    async fn add_virtual_interface_ptp_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        local_addr: IPAddress,
+        remote_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()> {
+        self.nl
+            .create_ptp_vxlan(iface, dev, vni, local_addr, remote_addr, port)
+            .await
+    }
+    async fn add_virtual_interface_mcast_vxlan(
+        &self,
+        iface: String,
+        dev: String,
+        vni: u32,
+        mcast_addr: IPAddress,
+        port: u16,
+    ) -> FResult<()> {
+        self.nl
+            .create_mcast_vxlan(iface.clone(), dev, vni, mcast_addr, port)
+            .await?;
+        self.nl.set_iface_up(iface).await
+    }
+    async fn add_virtual_interface_vlan(
+        &self,
+        iface: String,
+        dev: String,
+        tag: u16,
+    ) -> FResult<()> {
+        self.nl.create_vlan(iface.clone(), dev, tag).await?;
+        self.nl.set_iface_up(iface).await
+    }
+    async fn add_virtual_interface_veth(&self, iface_i: String, iface_e: String) -> FResult<()> {
+        self.nl
+            .create_veth(iface_i.clone(), iface_e.clone())
+            .await?;
+        self.nl.set_iface_up(iface_i).await?;
+        self.nl.set_iface_up(iface_e).await
+    }
+    async fn add_virtual_interface_bridge(&self, br_name: String) -> FResult<()> {
+        self.nl.create_bridge(br_name.clone()).await?;
+        self.nl.set_iface_up(br_name).await
+    }
+    async fn list_interfaces(&self) -> FResult<Vec<String>> {
+        self.nl.dump_links().await
+    }
+    async fn next_netlink_events(&self) -> FResult<Vec<NetlinkEvent>> {
+        let mut guard = self.events.lock().await;
+        if guard.is_none() {
+            *guard = Some(self.nl.subscribe().await?);
+        }
+        let events = guard
+            .as_mut()
+            .ok_or_else(|| FError::NetworkingError("Netlink events not subscribed".to_string()))?;
+        let mut batch = Vec::new();
+        // waits for the first event, then takes the ones already queued
+        match async_std::future::timeout(Duration::from_secs(NETLINK_EVENTS_POLL), events.next())
+            .await
+        {
+            Ok(Some(event)) => batch.push(event),
+            Ok(None) => {
+                *guard = None;
+                return Err(FError::NetworkingError(
+                    "Netlink events stream closed".to_string(),
+                ));
+            }
+            Err(_) => return Ok(batch),
+        }
+        while let Some(Some(event)) = events.next().now_or_never() {
+            batch.push(event);
+        }
+        Ok(batch)
+    }
+}
+
+/// Runs each Namespace Manager as a fos-net-linux-ns-manager process,
+/// reached through zenoh
+pub struct ProcessNSManagerLauncher {
+    z: Arc<zenoh::net::Session>,
+    locator: String,
+    timeout: Duration,
+}
+
+impl ProcessNSManagerLauncher {
+    pub fn new(z: Arc<zenoh::net::Session>, locator: String, timeout: Duration) -> Self {
+        Self {
+            z,
+            locator,
+            timeout,
+        }
+    }
+}
+
+#[async_trait]
+impl NSManagerLauncher for ProcessNSManagerLauncher {
+    /// Spawns the manager and waits for it to be ready, the manager
+    /// writes [`NS_MANAGER_READY`] on stdout once it serves, if it does not
+    /// within the timeout it is killed.
+    async fn launch(&self, ns_name: String, ns_uuid: Uuid) -> FResult<NSManagerProcess> {
+        let mut child = Command::new("fos-net-linux-ns-manager")
+            .arg("--netns")
+            .arg(&ns_name)
+            .arg("--id")
+            .arg(format!("{}", ns_uuid))
+            .arg("--locator")
+            .arg(self.locator.clone())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| FError::NetworkingError(format!("{}", e)))?;
+
+        let stdout = child.stdout.take().ok_or(FError::NotConnected)?;
+        // the read blocks until the manager writes or exits,
+        // killing the manager on timeout unblocks it
+        let ready = task::spawn_blocking(move || {
+            let mut reader = BufReader::new(stdout);
+            let mut line = String::new();
+            let ready = reader
+                .read_line(&mut line)
+                .map(|_| line.trim() == NS_MANAGER_READY)
+                .unwrap_or(false);
+            (ready, reader)
+        });
+        let err = match async_std::future::timeout(self.timeout, ready).await {
+            Ok((true, reader)) => {
+                // stdout is drained until the manager exits,
+                // closing it would make its writes fail with EPIPE
+                let log_ns_name = ns_name.clone();
+                task::spawn_blocking(move || {
+                    for line in reader.lines() {
+                        match line {
+                            Ok(line) => log::debug!("[{}] {}", log_ns_name, line),
+                            Err(_) => break,
+                        }
+                    }
+                });
+                None
+            }
+            Ok((false, _)) => Some(LinuxNetworkError::NSManagerExited {
+                ns_name: ns_name.clone(),
+            }),
+            Err(_) => Some(LinuxNetworkError::NSManagerTimeout {
+                ns_name: ns_name.clone(),
+                timeout: self.timeout,
+            }),
+        };
+        if let Some(err) = err {
+            log::error!("{}", err);
+            // waiting for the child blocks
+            task::spawn_blocking(move || {
+                let _ = child.kill();
+                let _ = child.wait();
+            })
+            .await;
+            return Err(err.into());
+        }
+
+        Ok(NSManagerProcess {
+            child: Some(child),
+            ns_name,
+            client: Arc::new(NamespaceManagerClient::new(self.z.clone(), ns_uuid)),
+        })
+    }
+}
+
+/// Runs dnsmasq, it stays in foreground so the child is dnsmasq itself
+pub struct ProcessDHCPLauncher;
+
+#[async_trait]
+impl DHCPLauncher for ProcessDHCPLauncher {
+    async fn launch(&self, config_file: String, netns: Option<String>) -> FResult<Child> {
+        let mut cmd = match netns {
+            Some(ns_name) => {
+                let mut cmd = Command::new("ip");
+                cmd.arg("netns").arg("exec").arg(ns_name).arg("dnsmasq");
+                cmd
+            }
+            None => Command::new("dnsmasq"),
+        };
+        cmd.arg("-k")
+            .arg("-C")
+            .arg(config_file)
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(|e| FError::NetworkingError(format!("{}", e)))
+    }
+}
@@ -0,0 +1,353 @@
+/*********************************************************************************
+* Copyright (c) 2018,2020 ADLINK Technology Inc.
+*
+* This program and the accompanying materials are made available under the
+* terms of the Eclipse Public License 2.0 which is available at
+* http://www.eclipse.org/legal/epl-2.0, or the Apache Software License 2.0
+* which is available at https://www.apache.org/licenses/LICENSE-2.0.
+*
+* SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+* Contributors:
+*   ADLINK fog05 team, <fog05@adlink-labs.tech>
+*********************************************************************************/
+
+//! In-memory store, Agent and OS plugin, and Namespace Managers running
+//! on a [`FakeNetlinkBackend`], together they let the plugin run
+//! without zenoh nor root privileges.
+
+use std::collections::HashMap;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use async_std::channel::{bounded, Receiver, Sender};
+use async_std::sync::Arc;
+
+use async_trait::async_trait;
+
+use fog05_sdk::fresult::{FError, FResult};
+use fog05_sdk::types::{
+    ConnectionPoint, NetworkNamespace, PluginKind, VirtualInterface, VirtualNetwork,
+};
+
+use uuid::Uuid;
+
+use crate::errors::LinuxNetworkError;
+use crate::netlink::fake::FakeNetlinkBackend;
+use crate::services::{
+    AgentService, DHCPLauncher, NSManagerLauncher, NetlinkNSManager, NetworkStore, OSService,
+};
+use crate::types::NSManagerProcess;
+
+#[derive(Default)]
+struct FakeStoreState {
+    global_vnets: HashMap<Uuid, VirtualNetwork>,
+    vnets: HashMap<Uuid, VirtualNetwork>,
+    interfaces: HashMap<Uuid, VirtualInterface>,
+    netns: HashMap<Uuid, NetworkNamespace>,
+    cps: HashMap<Uuid, ConnectionPoint>,
+}
+
+/// In-memory implementation of [`NetworkStore`]
+#[derive(Default)]
+pub struct FakeStore {
+    state: Mutex<FakeStoreState>,
+    paused_vnet_writes: Mutex<Option<Receiver<()>>>,
+}
+
+impl FakeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a descriptor to the global store, as done by the orchestrator
+    pub fn add_global_virtual_network(&self, vnet: VirtualNetwork) {
+        self.state
+            .lock()
+            .unwrap()
+            .global_vnets
+            .insert(vnet.uuid, vnet);
+    }
+
+    pub fn virtual_networks(&self) -> Vec<VirtualNetwork> {
+        self.state.lock().unwrap().vnets.values().cloned().collect()
+    }
+
+    pub fn interfaces(&self) -> Vec<VirtualInterface> {
+        self.state
+            .lock()
+            .unwrap()
+            .interfaces
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub fn network_namespaces(&self) -> Vec<NetworkNamespace> {
+        self.state.lock().unwrap().netns.values().cloned().collect()
+    }
+
+    pub fn connection_points(&self) -> Vec<ConnectionPoint> {
+        self.state.lock().unwrap().cps.values().cloned().collect()
+    }
+
+    /// Makes the next write of a virtual network wait
+    /// until the returned sender sends or is dropped
+    pub fn pause_virtual_network_writes(&self) -> Sender<()> {
+        let (tx, rx) = bounded(1);
+        *self.paused_vnet_writes.lock().unwrap() = Some(rx);
+        tx
+    }
+}
+
+fn get<T: Clone>(records: &HashMap<Uuid, T>, uuid: &Uuid) -> FResult<T> {
+    records.get(uuid).cloned().ok_or(FError::NotFound)
+}
+
+fn remove<T>(records: &mut HashMap<Uuid, T>, uuid: &Uuid) -> FResult<()> {
+    records.remove(uuid).map(|_| ()).ok_or(FError::NotFound)
+}
+
+#[async_trait]
+impl NetworkStore for FakeStore {
+    async fn get_global_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork> {
+        get(&self.state.lock().unwrap().global_vnets, &vnet_uuid)
+    }
+    async fn get_virtual_network(&self, vnet_uuid: Uuid) -> FResult<VirtualNetwork> {
+        get(&self.state.lock().unwrap().vnets, &vnet_uuid)
+    }
+    async fn add_virtual_network(&self, vnet: &VirtualNetwork) -> FResult<()> {
+        let paused = self.paused_vnet_writes.lock().unwrap().take();
+        if let Some(resume) = paused {
+            let _ = resume.recv().await;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.vnets.insert(vnet.uuid, vnet.clone());
+        Ok(())
+    }
+    async fn remove_virtual_network(&self, vnet_uuid: Uuid) -> FResult<()> {
+        remove(&mut self.state.lock().unwrap().vnets, &vnet_uuid)
+    }
+    async fn get_all_virtual_networks(&self) -> FResult<Vec<VirtualNetwork>> {
+        Ok(self.virtual_networks())
+    }
+    async fn get_interface(&self, iface_uuid: Uuid) -> FResult<VirtualInterface> {
+        get(&self.state.lock().unwrap().interfaces, &iface_uuid)
+    }
+    async fn add_interface(&self, iface: &VirtualInterface) -> FResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.interfaces.insert(iface.uuid, iface.clone());
+        Ok(())
+    }
+    async fn remove_interface(&self, iface_uuid: Uuid) -> FResult<()> {
+        remove(&mut self.state.lock().unwrap().interfaces, &iface_uuid)
+    }
+    async fn get_network_namespace(&self, ns_uuid: Uuid) -> FResult<NetworkNamespace> {
+        get(&self.state.lock().unwrap().netns, &ns_uuid)
+    }
+    async fn add_network_namespace(&self, netns: &NetworkNamespace) -> FResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.netns.insert(netns.uuid, netns.clone());
+        Ok(())
+    }
+    async fn remove_network_namespace(&self, ns_uuid: Uuid) -> FResult<()> {
+        remove(&mut self.state.lock().unwrap().netns, &ns_uuid)
+    }
+    async fn get_all_network_namespaces(&self) -> FResult<Vec<NetworkNamespace>> {
+        Ok(self.network_namespaces())
+    }
+    async fn get_connection_point(&self, cp_uuid: Uuid) -> FResult<ConnectionPoint> {
+        get(&self.state.lock().unwrap().cps, &cp_uuid)
+    }
+    async fn add_connection_point(&self, cp: &ConnectionPoint) -> FResult<()> {
+        let mut state = self.state.lock().unwrap();
+        state.cps.insert(cp.uuid, cp.clone());
+        Ok(())
+    }
+    async fn remove_connection_point(&self, cp_uuid: Uuid) -> FResult<()> {
+        remove(&mut self.state.lock().unwrap().cps, &cp_uuid)
+    }
+    async fn get_all_connection_points(&self) -> FResult<Vec<ConnectionPoint>> {
+        Ok(self.connection_points())
+    }
+}
+
+/// Agent answering with a fixed node UUID, it records the registered plugins
+pub struct FakeAgent {
+    node_uuid: Uuid,
+    plugins: Mutex<Vec<Uuid>>,
+}
+
+impl FakeAgent {
+    pub fn new(node_uuid: Uuid) -> Self {
+        Self {
+            node_uuid,
+            plugins: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn plugins(&self) -> Vec<Uuid> {
+        self.plugins.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl AgentService for FakeAgent {
+    async fn get_node_uuid(&self) -> FResult<Uuid> {
+        Ok(self.node_uuid)
+    }
+    async fn register_plugin(&self, plugin_uuid: Uuid, _kind: PluginKind) -> FResult<()> {
+        self.plugins.lock().unwrap().push(plugin_uuid);
+        Ok(())
+    }
+    async fn unregister_plugin(&self, plugin_uuid: Uuid) -> FResult<()> {
+        let mut plugins = self.plugins.lock().unwrap();
+        let pos = plugins
+            .iter()
+            .position(|uuid| *uuid == plugin_uuid)
+            .ok_or(FError::NotFound)?;
+        plugins.remove(pos);
+        Ok(())
+    }
+}
+
+/// OS plugin keeping the stored files in memory
+#[derive(Default)]
+pub struct FakeOS {
+    files: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl FakeOS {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().get(path).cloned()
+    }
+}
+
+#[async_trait]
+impl OSService for FakeOS {
+    async fn store_file(&self, content: Vec<u8>, file_path: String) -> FResult<()> {
+        self.files.lock().unwrap().insert(file_path, content);
+        Ok(())
+    }
+}
+
+/// Runs the Namespace Managers in process, each on the
+/// [`FakeNetlinkBackend`] of its namespace
+#[derive(Clone, Default)]
+pub struct FakeNSManagerLauncher {
+    nl: FakeNetlinkBackend,
+    process: Option<String>,
+    failing: Arc<AtomicBool>,
+    launches: Arc<AtomicUsize>,
+}
+
+impl FakeNSManagerLauncher {
+    pub fn new(nl: FakeNetlinkBackend) -> Self {
+        Self {
+            nl,
+            ..Default::default()
+        }
+    }
+
+    /// Gives each manager a process running the given shell script,
+    /// the plugin supervises it as the one of a real manager
+    pub fn with_process(mut self, script: &str) -> Self {
+        self.process = Some(script.to_string());
+        self
+    }
+
+    /// Makes the next launches fail as a manager exiting before it is ready
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+
+    pub fn launches(&self) -> usize {
+        self.launches.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl NSManagerLauncher for FakeNSManagerLauncher {
+    async fn launch(&self, ns_name: String, _ns_uuid: Uuid) -> FResult<NSManagerProcess> {
+        self.launches.fetch_add(1, Ordering::SeqCst);
+        // as the real manager, it cannot start without its namespace
+        if !self.nl.namespaces().contains(&ns_name) || self.failing.load(Ordering::SeqCst) {
+            return Err(LinuxNetworkError::NSManagerExited { ns_name }.into());
+        }
+        let child = match &self.process {
+            Some(script) => Some(
+                Command::new("sh")
+                    .arg("-c")
+                    .arg(script)
+                    .stdin(Stdio::null())
+                    .spawn()
+                    .map_err(|e| FError::NetworkingError(format!("{}", e)))?,
+            ),
+            None => None,
+        };
+        let nl = Arc::new(self.nl.in_netns(&ns_name));
+        let ns_manager = NetlinkNSManager::new(nl);
+        ns_manager.subscribe().await?;
+        Ok(NSManagerProcess {
+            child,
+            ns_name,
+            client: Arc::new(ns_manager),
+        })
+    }
+}
+
+/// Runs a shell script in place of dnsmasq, it gets the
+/// configuration file as `$1`
+#[derive(Clone)]
+pub struct FakeDHCPLauncher {
+    script: String,
+    launches: Arc<AtomicUsize>,
+}
+
+impl FakeDHCPLauncher {
+    /// The server creates `<config file>.ready` once it handles SIGHUP,
+    /// then keeps running and, as dnsmasq reloading its files, appends
+    /// a line to `<config file>.reloads` on each SIGHUP
+    pub fn new() -> Self {
+        Self::with_script(
+            "trap 'echo reload >> \"$1.reloads\"' HUP; : > \"$1.ready\"; \
+             while true; do sleep 0.1; done",
+        )
+    }
+
+    pub fn with_script(script: &str) -> Self {
+        Self {
+            script: script.to_string(),
+            launches: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn launches(&self) -> usize {
+        self.launches.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for FakeDHCPLauncher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl DHCPLauncher for FakeDHCPLauncher {
+    async fn launch(&self, config_file: String, _netns: Option<String>) -> FResult<Child> {
+        self.launches.fetch_add(1, Ordering::SeqCst);
+        Command::new("sh")
+            .arg("-c")
+            .arg(&self.script)
+            .arg("dnsmasq")
+            .arg(config_file)
+            .stdin(Stdio::null())
+            .spawn()
+            .map_err(|e| FError::NetworkingError(format!("{}", e)))
+    }
+}
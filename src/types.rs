@@ -19,16 +19,16 @@ extern crate serde_json;
 use serde::{Deserialize, Serialize};
 
 use async_std::prelude::FutureExt;
-use async_std::sync::{Arc, RwLock};
+use async_std::sync::{Arc, Mutex, RwLock};
 
 use futures::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::convert::TryFrom;
 use std::str;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use fog05_sdk::agent::{AgentPluginInterfaceClient, OSClient};
 use fog05_sdk::fresult::{FError, FResult};
-use fog05_sdk::types::IPAddress;
+use fog05_sdk::types::{IPAddress, MACAddress};
 
 use zenoh::*;
 use znrpc_macros::znservice;
@@ -38,6 +38,49 @@ use uuid::Uuid;
 
 use ipnetwork::IpNetwork;
 
+use crate::netlink::{NetlinkBackend, NetlinkEvent};
+use crate::services::{
+    AgentService, DHCPLauncher, NSManagerLauncher, NSManagerOps, NetworkStore, OSService,
+};
+
+/// Line written on stdout by a Namespace Manager once it is serving
+pub const NS_MANAGER_READY: &str = "READY";
+
+/// Default seconds to wait for a Namespace Manager to be ready
+pub const NS_MANAGER_DEFAULT_TIMEOUT: u64 = 10;
+
+/// Seconds a Namespace Manager waits for netlink events before
+/// answering an empty batch, short so a poll does not hold the manager
+pub const NETLINK_EVENTS_POLL: u64 = 1;
+
+/// Restarts of a supervised process that exits again before
+/// SUPERVISION_STABLE_TIME, it is marked failed after them
+pub const SUPERVISION_MAX_RESTARTS: u32 = 5;
+
+/// Seconds before the first restart of a supervised process,
+/// doubled at each following attempt
+pub const SUPERVISION_BACKOFF: u64 = 1;
+
+/// Seconds a supervised process has to run before its
+/// previous restarts are forgotten
+pub const SUPERVISION_STABLE_TIME: u64 = 60;
+
+/// Supervision events kept for the introspection, the oldest are dropped
+pub const MAX_SUPERVISION_EVENTS: usize = 100;
+
+/// Default DHCP lease time in seconds
+pub const DEFAULT_DHCP_LEASE_TIME: u64 = 86400;
+
+/// Seconds given to dnsmasq to exit after SIGTERM before it is killed
+pub const DNSMASQ_STOP_TIMEOUT: u64 = 5;
+
+/// Default prefix of the names of interfaces and network namespaces
+pub const DEFAULT_NAME_PREFIX: &str = "fos";
+
+/// Maximum length of a prefix, leaving at least 8 digits
+/// of the UUID in interface names
+pub const MAX_NAME_PREFIX_LEN: usize = 7;
+
 pub type LinuxNetworkStateGuard<'a> = async_std::sync::RwLockReadGuard<'a, LinuxNetworkState>;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -50,24 +93,163 @@ pub struct LinuxNetworkConfig {
     pub monitoring_interveal: u64,
     pub overlay_iface: Option<String>,
     pub dataplane_iface: Option<String>,
+    pub macvlan_mode: Option<MACVLANMode>,
+    pub ns_manager_timeout: Option<u64>,
+    /// Default DHCP options of new virtual networks
+    pub dhcp_options: Option<DHCPOptions>,
+    pub name_prefix: Option<String>,
+    pub shutdown_policy: Option<ShutdownPolicy>,
 }
 
 pub struct LinuxNetworkState {
     pub uuid: Option<Uuid>,
-    pub tokio_rt: tokio::runtime::Runtime,
-    pub nl_handler: rtnetlink::Handle,
-    pub ns_managers: HashMap<Uuid, (u32, NamespaceManagerClient)>,
+    /// Runtime of the rtnetlink backend, None with other backends
+    pub tokio_rt: Option<tokio::runtime::Runtime>,
+    pub ns_managers: HashMap<Uuid, NSManagerProcess>,
+    pub dhcp_servers: HashMap<Uuid, DHCPProcess>,
+    /// Locks serializing the updates of interface and virtual network
+    /// records, by UUID, only the ones in use are kept
+    pub record_locks: HashMap<Uuid, Arc<Mutex<()>>>,
+    /// Restarts of the Namespace Managers that exited at least once
+    pub ns_manager_restarts: HashMap<Uuid, Restarts>,
+    /// Namespace Managers waiting for their restart, with the namespace name
+    pub pending_ns_managers: HashMap<Uuid, String>,
+    /// Namespaces whose manager is no longer restarted, with the reason
+    pub failed_ns_managers: HashMap<Uuid, String>,
+    /// Restarts of the DHCP servers that exited at least once, by virtual network
+    pub dhcp_restarts: HashMap<Uuid, Restarts>,
+    /// DHCP servers waiting for their restart, with their namespace
+    pub pending_dhcp_servers: HashMap<Uuid, (VNetDHCP, Option<String>)>,
+    pub supervision_events: VecDeque<SupervisionEvent>,
+}
+
+/// Restart attempts of a supervised process
+#[derive(Debug, Clone, Copy)]
+pub struct Restarts {
+    /// Restarts since the process was last stable
+    pub attempts: u32,
+    /// When the process was last started
+    pub started: Instant,
+    /// When the next restart is due, None while the process runs
+    pub due: Option<Instant>,
+}
+
+impl Restarts {
+    pub fn new() -> Self {
+        Self {
+            attempts: 0,
+            started: Instant::now(),
+            due: None,
+        }
+    }
+
+    /// Records an exit of the process, see [`Restarts::failed`]
+    pub fn exited(&mut self) -> bool {
+        if self.started.elapsed() >= Duration::from_secs(SUPERVISION_STABLE_TIME) {
+            self.attempts = 0;
+        }
+        self.failed()
+    }
+
+    /// Schedules the next restart after an exit or a failed restart,
+    /// returns false once SUPERVISION_MAX_RESTARTS were made
+    pub fn failed(&mut self) -> bool {
+        if self.attempts >= SUPERVISION_MAX_RESTARTS {
+            self.due = None;
+            return false;
+        }
+        self.due = Some(Instant::now() + Duration::from_secs(SUPERVISION_BACKOFF << self.attempts));
+        self.attempts += 1;
+        true
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.due.map_or(false, |due| Instant::now() >= due)
+    }
+
+    pub fn restarted(&mut self) {
+        self.started = Instant::now();
+        self.due = None;
+    }
+}
+
+impl Default for Restarts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Restarts and failures of the processes supervised by the plugin,
+/// returned by the introspection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SupervisionEvent {
+    /// Seconds since the UNIX epoch
+    pub time: u64,
+    pub kind: SupervisionEventKind,
+}
+
+impl SupervisionEvent {
+    pub fn new(kind: SupervisionEventKind) -> Self {
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            kind,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum SupervisionEventKind {
+    NSManagerRestarted {
+        ns_uuid: Uuid,
+        attempt: u32,
+    },
+    /// The manager is no longer restarted
+    NSManagerFailed {
+        ns_uuid: Uuid,
+        reason: String,
+    },
+    DHCPRestarted {
+        vnet_uuid: Uuid,
+        attempt: u32,
+    },
+    /// The server is no longer restarted
+    DHCPFailed {
+        vnet_uuid: Uuid,
+        reason: String,
+    },
+}
+
+/// A running Namespace Manager and the client used to reach it
+pub struct NSManagerProcess {
+    /// None for managers running inside the plugin process
+    pub child: Option<std::process::Child>,
+    pub ns_name: String,
+    pub client: Arc<dyn NSManagerOps>,
+}
+
+/// A running dnsmasq serving a virtual network
+pub struct DHCPProcess {
+    pub child: std::process::Child,
+    pub dhcp: VNetDHCP,
+    pub netns: Option<String>,
 }
 
 #[derive(Clone)]
 pub struct LinuxNetwork {
-    pub z: Arc<zenoh::net::Session>,
-    pub connector: Arc<fog05_sdk::zconnector::ZConnector>,
+    /// None when the plugin runs on in-memory services,
+    /// see [`LinuxNetwork::new_with_backend`]
+    pub z: Option<Arc<zenoh::net::Session>>,
+    pub store: Arc<dyn NetworkStore>,
     pub pid: u32,
-    pub agent: Option<AgentPluginInterfaceClient>,
-    pub os: Option<OSClient>,
+    pub agent: Option<Arc<dyn AgentService>>,
+    pub os: Option<Arc<dyn OSService>>,
     pub config: LinuxNetworkConfig,
     pub state: Arc<RwLock<LinuxNetworkState>>,
+    pub nl: Arc<dyn NetlinkBackend>,
+    pub ns_launcher: Arc<dyn NSManagerLauncher>,
+    pub dhcp_launcher: Arc<dyn DHCPLauncher>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -76,6 +258,53 @@ pub struct VNetDHCP {
     pub pid_file: String,
     pub conf: String,
     pub log_file: String,
+    /// dhcp-hostsfile and dhcp-optsfile, reloaded on SIGHUP
+    #[serde(default)]
+    pub hosts_file: Option<String>,
+    #[serde(default)]
+    pub opts_file: Option<String>,
+    /// What the configuration is rendered from
+    #[serde(default)]
+    pub settings: Option<VNetDHCPSettings>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VNetDHCPSettings {
+    pub iface: String,
+    pub dhcp_start: IPAddress,
+    pub dhcp_end: IPAddress,
+    pub gateway: Option<IPAddress>,
+    pub dns: Vec<IPAddress>,
+    #[serde(default)]
+    pub options: DHCPOptions,
+}
+
+/// DHCP options of a virtual network, all but the lease time
+/// are written in the dhcp-optsfile of dnsmasq
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DHCPOptions {
+    /// Seconds, DEFAULT_DHCP_LEASE_TIME if not set
+    pub lease_time: Option<u64>,
+    pub domain: Option<String>,
+    pub ntp_servers: Vec<IPAddress>,
+    pub mtu: Option<u16>,
+    /// Entries with the dhcp-option syntax, e.g. `option:tftp-server,10.240.0.5`
+    pub extra: Vec<String>,
+}
+
+/// A lease given by the dnsmasq of a virtual network
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DHCPLease {
+    /// Seconds since the epoch, 0 for leases that never expire
+    pub expiry: u64,
+    /// None for IPv6 leases, they are identified by the client DUID
+    pub mac: Option<MACAddress>,
+    pub ip: IPAddress,
+    pub hostname: Option<String>,
+    pub client_id: Option<String>,
+    /// Interface of the virtual network with the MAC of the lease
+    pub interface: Option<Uuid>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -89,6 +318,108 @@ pub struct VirtualNetworkInternals {
     pub dhcp: Option<VNetDHCP>,
     pub associated_netns: Option<VNetNetns>,
     pub associated_tables: Vec<String>,
+    #[serde(default)]
+    pub transport: Option<VNetTransport>,
+    /// Kernel objects that could not be restored at startup
+    #[serde(default)]
+    pub failures: Vec<String>,
+    #[serde(default)]
+    pub reservations: Vec<DHCPReservation>,
+}
+
+/// Settings of a virtual network given by the orchestrator, as JSON
+/// in the `plugin_internals` of the descriptor in the global store
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct VirtualNetworkSettings {
+    pub l2_transport: L2Transport,
+    /// 802.1Q tag of the network, required with the VLAN transport
+    pub vlan_tag: Option<u16>,
+    /// Unicast VXLAN peers, when empty BUM traffic is sent
+    /// to the multicast group of the descriptor
+    pub vxlan_peers: Vec<IPAddress>,
+}
+
+/// A static DHCP address, written as dhcp-host in the hosts file of dnsmasq
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DHCPReservation {
+    pub mac: MACAddress,
+    pub ip: IPAddress,
+    pub hostname: Option<String>,
+}
+
+/// How the virtual network reaches the other nodes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum VNetTransport {
+    /// VXLAN with BUM traffic sent to a multicast group
+    MCastVXLAN {
+        vni: u32,
+        mcast_addr: IPAddress,
+        port: u16,
+    },
+    /// VXLAN with BUM traffic replicated to each peer (head-end replication),
+    /// the peers are kept only here as the VXLANKind of the uplink
+    /// has no room for them
+    UCastVXLAN {
+        vni: u32,
+        port: u16,
+        peers: Vec<IPAddress>,
+    },
+    /// 802.1Q tag on the dataplane interface
+    VLAN { tag: u16, dev: String },
+}
+
+/// Transport used for a L2 virtual network
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum L2Transport {
+    VXLAN,
+    VLAN,
+}
+
+impl Default for L2Transport {
+    fn default() -> Self {
+        L2Transport::VXLAN
+    }
+}
+
+/// What happens to the managed networks when the plugin stops
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ShutdownPolicy {
+    /// Only the default network is removed, the other networks are
+    /// left in place and reconciled at the next start
+    Keep,
+    /// Every virtual network, namespace and connection point is removed
+    Destroy,
+}
+
+impl Default for ShutdownPolicy {
+    fn default() -> Self {
+        ShutdownPolicy::Keep
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GRETunnelKind {
+    GRE,
+    GRETAP,
+    IP6GRE,
+    IP6GRETAP,
+}
+
+/// Modes of MACVLAN interfaces, values are the MACVLAN_MODE_* flags
+/// from linux/if_link.h
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum MACVLANMode {
+    PRIVATE = 1,
+    VEPA = 2,
+    BRIDGE = 4,
+    PASSTHRU = 8,
+}
+
+impl Default for MACVLANMode {
+    fn default() -> Self {
+        MACVLANMode::BRIDGE
+    }
 }
 
 pub fn serialize_network_internals(data: &VirtualNetworkInternals) -> FResult<Vec<u8>> {
@@ -104,6 +435,13 @@ pub fn deserialize_network_internals(raw_data: &[u8]) -> FResult<VirtualNetworkI
     .map_err(|e| FError::NetworkingError(format!("{}", e)))
 }
 
+pub fn deserialize_network_settings(raw_data: &[u8]) -> FResult<VirtualNetworkSettings> {
+    serde_json::from_str::<VirtualNetworkSettings>(
+        std::str::from_utf8(raw_data).map_err(|e| FError::NetworkingError(format!("{}", e)))?,
+    )
+    .map_err(|e| FError::NetworkingError(format!("{}", e)))
+}
+
 pub fn serialize_plugin_config(data: &LinuxNetworkConfig) -> FResult<Vec<u8>> {
     Ok(serde_yaml::to_string(data)
         .map_err(|e| FError::NetworkingError(format!("{}", e)))?
@@ -125,6 +463,7 @@ pub trait NamespaceManager {
     async fn move_virtual_interface_into_default_ns(&self, iface: String) -> FResult<()>;
     async fn set_virtual_interface_mac(&self, iface: String, address: Vec<u8>) -> FResult<()>;
     async fn set_virtual_interface_name(&self, iface: String, name: String) -> FResult<()>;
+    async fn set_virtual_interface_alias(&self, iface: String, alias: String) -> FResult<()>;
     async fn del_virtual_interface_address(&self, iface: String, addr: IPAddress) -> FResult<()>;
     async fn get_virtual_interface_addresses(&self, iface: String) -> FResult<Vec<IPAddress>>;
     async fn add_virtual_interface_address(
@@ -157,4 +496,40 @@ pub trait NamespaceManager {
     async fn add_virtual_interface_veth(&self, iface_i: String, iface_e: String) -> FResult<()>;
     async fn add_virtual_interface_bridge(&self, br_name: String) -> FResult<()>;
     async fn list_interfaces(&self) -> FResult<Vec<String>>;
+    /// Returns the link and address changes of the namespace since the
+    /// previous call, waiting up to NETLINK_EVENTS_POLL for the first one
+    async fn next_netlink_events(&self) -> FResult<Vec<NetlinkEvent>>;
+}
+
+/// Plugin specific calls, used by operators and tools
+#[znservice(timeout_s = 60, prefix = "/fos/local")]
+pub trait LinuxNetworkIntrospection {
+    /// Returns the active DHCP leases of a virtual network
+    async fn get_virtual_network_leases(&self, vnet_uuid: Uuid) -> FResult<Vec<DHCPLease>>;
+    /// Returns the active DHCP lease of an interface, found by its MAC
+    async fn get_virtual_interface_lease(&self, intf_uuid: Uuid) -> FResult<Option<DHCPLease>>;
+    async fn get_dhcp_reservations(&self, vnet_uuid: Uuid) -> FResult<Vec<DHCPReservation>>;
+    async fn get_dhcp_options(&self, vnet_uuid: Uuid) -> FResult<DHCPOptions>;
+    /// Replaces the DHCP options of a virtual network, dnsmasq is
+    /// reloaded, or restarted if the lease time changed
+    async fn set_dhcp_options(&self, vnet_uuid: Uuid, options: DHCPOptions)
+        -> FResult<DHCPOptions>;
+    /// Adds a static address to the DHCP server of a virtual network
+    async fn add_dhcp_reservation(
+        &self,
+        vnet_uuid: Uuid,
+        reservation: DHCPReservation,
+    ) -> FResult<Vec<DHCPReservation>>;
+    /// Removes the static address of the given MAC
+    async fn remove_dhcp_reservation(
+        &self,
+        vnet_uuid: Uuid,
+        mac: MACAddress,
+    ) -> FResult<Vec<DHCPReservation>>;
+    /// Returns the latest restarts and failures of the Namespace Managers
+    /// and DHCP servers, oldest first
+    async fn get_supervision_events(&self) -> FResult<Vec<SupervisionEvent>>;
+    /// Returns what could not be restored or kept running
+    /// for a virtual network, e.g. a DHCP server no longer restarted
+    async fn get_virtual_network_failures(&self, vnet_uuid: Uuid) -> FResult<Vec<String>>;
 }